@@ -0,0 +1,189 @@
+//! Ambient occlusion, a cheap stand-in for global illumination: creases, corners and the
+//! ground under bodies receive less of the light bouncing around the scene.
+
+use crate::camera::Camera;
+use crate::canvas::{Canvas, Color};
+use crate::computed_intersection::ComputedIntersection;
+use crate::random::Rng;
+use crate::sampling::cosine_weighted_hemisphere;
+use crate::world::World;
+use crate::F;
+
+/// Estimates how open the surroundings of a point are, by casting short rays in random
+/// directions around the normal and counting the ones which escape.
+///
+/// The rays are distributed by the cosine of their angle to the normal, so bodies in front
+/// of the surface count more than ones grazing it, just like they would block more light.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmbientOcclusion {
+    /// Rays cast at every point
+    pub samples: usize,
+    /// How far away bodies may be and still occlude, in world units
+    pub distance: F,
+    pub seed: u64,
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        AmbientOcclusion {
+            samples: 16,
+            distance: 1.0,
+            seed: 0,
+        }
+    }
+}
+
+impl AmbientOcclusion {
+    pub fn new(samples: usize, distance: F) -> Self {
+        AmbientOcclusion {
+            samples,
+            distance,
+            ..Self::default()
+        }
+    }
+
+    /// Fraction of the rays leaving the hit which travel `distance` without hitting anything,
+    /// 1 for a point out in the open and 0 for one buried in a crack.
+    pub fn visibility(&self, world: &World, computed: &ComputedIntersection, rng: &mut Rng) -> F {
+        if self.samples == 0 {
+            return 1.0;
+        }
+
+        let unoccluded = (0..self.samples)
+            .filter(|_| {
+                let direction = cosine_weighted_hemisphere(computed.normal_vec, rng);
+
+                !world.is_occluded_along(
+                    computed.over_point,
+                    direction,
+                    self.distance,
+                    computed.time,
+                )
+            })
+            .count();
+
+        unoccluded as F / self.samples as F
+    }
+
+    /// Shades every pixel by the visibility at the first body seen through it, white where
+    /// rays miss everything. Materials and lights are ignored, which makes for a quick look
+    /// at the shapes of a scene.
+    pub fn render(&self, camera: &Camera, world: &World) -> Canvas {
+        camera.render_samples(camera.samples_per_pixel, self.seed, |ray, rng| {
+            let intersections = world.intersect(ray);
+
+            match intersections.hit() {
+                Some(hit) => {
+                    let computed = hit.get_computed(ray, &intersections);
+                    let visibility = self.visibility(world, &computed, rng);
+
+                    Color::new(visibility, visibility, visibility)
+                }
+                None => Color::white(),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use crate::fuzzy_eq::*;
+    use crate::matrix::Matrix;
+    use crate::ray::Ray;
+    use crate::sphere::Sphere;
+    use crate::tuple::Tuple;
+    use std::f64::consts::PI;
+
+    /// Looks straight down at a floor made of a flattened sphere.
+    fn floor_from_above(bodies: Vec<Body>) -> (World, Ray) {
+        let floor = Sphere::new(Some(Matrix::scaling(100.0, 0.01, 100.0)));
+        let mut all = vec![Body::from(floor)];
+        all.extend(bodies);
+
+        let ray = Ray::new(Tuple::point(0.0, 5.0, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+
+        (World::new(all, vec![]), ray)
+    }
+
+    fn visibility(ao: &AmbientOcclusion, world: &World, ray: Ray) -> F {
+        let intersections = world.intersect(ray);
+        let computed = intersections
+            .hit()
+            .unwrap()
+            .get_computed(ray, &intersections);
+
+        ao.visibility(world, &computed, &mut Rng::new(7))
+    }
+
+    #[test]
+    fn an_open_surface_is_fully_visible() {
+        let (world, ray) = floor_from_above(vec![]);
+
+        assert_fuzzy_eq!(visibility(&AmbientOcclusion::default(), &world, ray), 1.0);
+    }
+
+    #[test]
+    fn a_surface_under_a_low_ceiling_is_fully_occluded() {
+        let ceiling = Sphere::new(Some(
+            Matrix::translation(0.0, 0.5, 0.0) * Matrix::scaling(100.0, 0.01, 100.0),
+        ));
+        let (world, _) = floor_from_above(vec![Body::from(ceiling)]);
+        // Looking at the floor from between floor and ceiling
+        let ray = Ray::new(Tuple::point(0.0, 0.25, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+
+        let ao = AmbientOcclusion::new(16, 1000.0);
+        assert_fuzzy_eq!(visibility(&ao, &world, ray), 0.0);
+    }
+
+    #[test]
+    fn bodies_farther_than_the_distance_do_not_occlude() {
+        let ceiling = Sphere::new(Some(
+            Matrix::translation(0.0, 2.0, 0.0) * Matrix::scaling(100.0, 0.01, 100.0),
+        ));
+        let (world, _) = floor_from_above(vec![Body::from(ceiling)]);
+        let ray = Ray::new(Tuple::point(0.0, 1.0, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+
+        assert_fuzzy_eq!(
+            visibility(&AmbientOcclusion::new(16, 1.5), &world, ray),
+            1.0
+        );
+        assert_fuzzy_eq!(
+            visibility(&AmbientOcclusion::new(16, 1000.0), &world, ray),
+            0.0
+        );
+    }
+
+    #[test]
+    fn a_wall_blocks_about_half_of_the_hemisphere_at_its_foot() {
+        let wall = Sphere::new(Some(
+            Matrix::translation(-100.0, 0.0, 0.0) * Matrix::scaling(100.0, 100.0, 100.0),
+        ));
+        let (world, _) = floor_from_above(vec![Body::from(wall)]);
+        let ray = Ray::new(Tuple::point(0.001, 5.0, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+
+        let ao = AmbientOcclusion::new(4000, 1000.0);
+        assert!((visibility(&ao, &world, ray) - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn rendering_ambient_occlusion() {
+        let ball = Sphere::new(Some(Matrix::translation(0.0, 1.01, 0.0)));
+        let (world, _) = floor_from_above(vec![Body::from(ball)]);
+        let camera = Camera::new(11, 11, PI / 2.0).with_transform(Matrix::view_transform(
+            Tuple::point(0.0, 2.0, -3.0),
+            Tuple::point(0.0, 0.5, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ));
+
+        let canvas = AmbientOcclusion::new(64, 1.0).render(&camera, &world);
+
+        // The sky and the top of the ball are out in the open, where the ball touches the floor
+        // is not
+        assert_fuzzy_eq!(canvas.pixel_at(5, 0), Color::white());
+        assert!(canvas.pixel_at(5, 3).red > 0.9);
+        assert!(canvas.pixel_at(5, 6).red < 0.5);
+        assert!(canvas.pixel_at(5, 7).red < canvas.pixel_at(5, 10).red);
+    }
+}
@@ -0,0 +1,295 @@
+//! Arbitrary output variables: extra images rendered next to the final one, describing what
+//! each pixel sees rather than how it looks. Compositors use them to pick out bodies or fog up
+//! the distance, denoisers to tell noise from detail.
+
+use crate::body::Intersectable;
+use crate::camera::Camera;
+use crate::canvas::{Canvas, Color};
+use crate::computed_intersection::ComputedIntersection;
+use crate::random::Rng;
+use crate::ray::Ray;
+use crate::world::World;
+use crate::F;
+
+/// The rendered image, together with buffers of the same size describing the first body seen
+/// through every pixel.
+///
+/// The buffers are taken from the very rays the image is rendered with, which keeps their
+/// edges lined up with the ones of the image. Normals and albedo are filtered across the pixel
+/// like the image itself. Depth and object IDs must not be blended at the silhouettes of
+/// bodies, so they are taken from the ray closest to the center of the pixel.
+#[derive(Debug)]
+pub struct RenderBuffers {
+    /// The final image
+    pub beauty: Canvas,
+    /// Distance of the hit in front of the camera, measured along the viewing direction, in
+    /// all three channels. Infinite where rays miss everything.
+    pub depth: Canvas,
+    /// Normal of the hit in world space, facing the camera, with x, y and z in the red,
+    /// green and blue channels. Black where rays miss everything.
+    pub normal: Canvas,
+    /// Color of the hit surface, before any light falls on it
+    pub albedo: Canvas,
+    object_ids: Vec<Option<usize>>,
+}
+
+impl RenderBuffers {
+    /// Renders the world as seen by the camera with the given function, e.g. a path tracer's
+    /// `radiance`, and the given number of samples per pixel. Adaptive sampling is not used,
+    /// as it picks its rays by the colors alone.
+    pub fn render<R>(camera: &Camera, world: &World, samples: usize, seed: u64, radiance: R) -> Self
+    where
+        R: Fn(Ray, &mut Rng) -> Color + Sync,
+    {
+        let forward = camera.forward();
+
+        let pixels = camera.tiles.map_pixels(camera.hsize, camera.vsize, |x, y| {
+            let mut rng = Rng::for_pixel(x, y, seed);
+            let mut pixel = Pixel::default();
+
+            camera.for_each_sample(x, y, samples, &mut rng, |ray, weight, (dx, dy), rng| {
+                let (color, hit) = match ray {
+                    Some(ray) => (radiance(ray, rng), first_hit(world, ray)),
+                    None => (Color::black(), None),
+                };
+                pixel.weight += weight;
+                pixel.beauty = pixel.beauty + color * weight;
+
+                if let Some((_, ref computed)) = hit {
+                    let n = computed.normal_vec;
+                    pixel.normal = pixel.normal + Color::new(n.x, n.y, n.z) * weight;
+                    pixel.albedo = pixel.albedo + computed.body.material().color() * weight;
+                }
+
+                let distance = dx * dx + dy * dy;
+                if distance < pixel.closest {
+                    pixel.closest = distance;
+                    pixel.depth = match (ray, hit) {
+                        (Some(ray), Some((index, computed))) => {
+                            pixel.object_id = Some(index);
+                            (computed.position - ray.origin).dot(forward)
+                        }
+                        _ => {
+                            pixel.object_id = None;
+                            F::INFINITY
+                        }
+                    };
+                }
+            });
+
+            pixel
+        });
+
+        let mut beauty = Canvas::new(camera.hsize, camera.vsize);
+        let mut depth = Canvas::new(camera.hsize, camera.vsize);
+        let mut normal = Canvas::new(camera.hsize, camera.vsize);
+        let mut albedo = Canvas::new(camera.hsize, camera.vsize);
+        let mut object_ids = Vec::with_capacity(pixels.len());
+
+        for (index, pixel) in pixels.into_iter().enumerate() {
+            let (x, y) = (index % camera.hsize, index / camera.hsize);
+            let average = |sum: Color| {
+                if pixel.weight == 0.0 {
+                    Color::black()
+                } else {
+                    sum * (1.0 / pixel.weight)
+                }
+            };
+
+            beauty.write_pixel(x, y, average(pixel.beauty));
+            normal.write_pixel(x, y, average(pixel.normal));
+            albedo.write_pixel(x, y, average(pixel.albedo));
+            depth.write_pixel(x, y, Color::new(pixel.depth, pixel.depth, pixel.depth));
+            object_ids.push(pixel.object_id);
+        }
+
+        RenderBuffers {
+            beauty,
+            depth,
+            normal,
+            albedo,
+            object_ids,
+        }
+    }
+
+    /// Index into the bodies of the world of the body seen closest to the center of the pixel.
+    pub fn object_id(&self, x: usize, y: usize) -> Option<usize> {
+        self.object_ids[y * self.beauty.width + x]
+    }
+
+    /// White where the body with the given index is seen, black everywhere else.
+    pub fn mask(&self, object_id: usize) -> Canvas {
+        let mut canvas = Canvas::new(self.beauty.width, self.beauty.height);
+
+        for y in 0..canvas.height {
+            for x in 0..canvas.width {
+                if self.object_id(x, y) == Some(object_id) {
+                    canvas.write_pixel(x, y, Color::white());
+                }
+            }
+        }
+
+        canvas
+    }
+}
+
+/// Filtered sums of the samples of a pixel, and what the sample closest to its center sees.
+struct Pixel {
+    beauty: Color,
+    normal: Color,
+    albedo: Color,
+    weight: F,
+    /// Squared distance of the closest sample so far from the center of the pixel
+    closest: F,
+    depth: F,
+    object_id: Option<usize>,
+}
+
+impl Default for Pixel {
+    fn default() -> Self {
+        Pixel {
+            beauty: Color::black(),
+            normal: Color::black(),
+            albedo: Color::black(),
+            weight: 0.0,
+            closest: F::INFINITY,
+            depth: F::INFINITY,
+            object_id: None,
+        }
+    }
+}
+
+/// The first hit along the ray, together with the index of the body hit.
+fn first_hit(world: &World, ray: Ray) -> Option<(usize, ComputedIntersection)> {
+    let intersections = world.intersect(ray);
+    let hit = intersections.hit()?;
+    let index = hit.body_index?;
+
+    Some((index, hit.get_computed(ray, &intersections)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use crate::fuzzy_eq::*;
+    use crate::material::{Material, Phong};
+    use crate::matrix::Matrix;
+    use crate::path_tracer::PathTracer;
+    use crate::sphere::Sphere;
+    use crate::triangle::Triangle;
+    use crate::tuple::Tuple;
+    use std::f64::consts::PI;
+
+    fn camera() -> Camera {
+        Camera::new(11, 11, PI / 2.0).with_transform(Matrix::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ))
+    }
+
+    #[test]
+    fn buffers_describe_the_body_seen_through_a_pixel() {
+        let w = crate::world::default_world();
+
+        let buffers = camera().render_with_aovs(&w);
+
+        assert_fuzzy_eq!(
+            buffers.beauty.pixel_at(5, 5),
+            camera().render(&w).pixel_at(5, 5)
+        );
+        assert_fuzzy_eq!(buffers.depth.pixel_at(5, 5), Color::new(4.0, 4.0, 4.0));
+        assert_fuzzy_eq!(buffers.normal.pixel_at(5, 5), Color::new(0.0, 0.0, -1.0));
+        assert_fuzzy_eq!(buffers.albedo.pixel_at(5, 5), Color::new(0.8, 1.0, 0.6));
+        assert_eq!(buffers.object_id(5, 5), Some(0));
+    }
+
+    #[test]
+    fn buffers_are_empty_where_rays_miss_everything() {
+        let w = crate::world::default_world();
+
+        let buffers = camera().render_with_aovs(&w);
+
+        assert_eq!(buffers.depth.pixel_at(0, 0).red, F::INFINITY);
+        assert_fuzzy_eq!(buffers.normal.pixel_at(0, 0), Color::black());
+        assert_fuzzy_eq!(buffers.albedo.pixel_at(0, 0), Color::black());
+        assert_eq!(buffers.object_id(0, 0), None);
+    }
+
+    #[test]
+    fn depth_is_measured_along_the_viewing_direction() {
+        let wall = Triangle::new(
+            Tuple::point(-100.0, -100.0, 0.0),
+            Tuple::point(100.0, -100.0, 0.0),
+            Tuple::point(0.0, 100.0, 0.0),
+        );
+        let w = World::new(vec![Body::from(wall)], vec![]);
+
+        let buffers = camera().render_with_aovs(&w);
+
+        // The ray through the corner travels farther, the wall is just as far away
+        assert_fuzzy_eq!(buffers.depth.pixel_at(0, 10), Color::new(5.0, 5.0, 5.0));
+    }
+
+    #[test]
+    fn masks_pick_out_a_single_body() {
+        let left = Sphere::with_material(
+            Material::from(Phong::with_color(Color::new(1.0, 0.0, 0.0))),
+            Some(Matrix::translation(-2.0, 0.0, 0.0)),
+        );
+        let right = Sphere::new(Some(Matrix::translation(2.0, 0.0, 0.0)));
+        let w = World::new(vec![Body::from(left), Body::from(right)], vec![]);
+
+        let buffers = camera().render_with_aovs(&w);
+        let mask = buffers.mask(1);
+
+        assert_eq!(buffers.object_id(2, 5), Some(0));
+        assert_eq!(buffers.object_id(8, 5), Some(1));
+        assert_fuzzy_eq!(buffers.albedo.pixel_at(2, 5), Color::new(1.0, 0.0, 0.0));
+        assert_fuzzy_eq!(mask.pixel_at(2, 5), Color::black());
+        assert_fuzzy_eq!(mask.pixel_at(8, 5), Color::white());
+    }
+
+    #[test]
+    fn the_path_tracer_renders_buffers_too() {
+        let w = crate::world::default_world();
+
+        let buffers = PathTracer::new(4).render_with_aovs(&camera(), &w);
+        let image = PathTracer::new(4).render(&camera(), &w);
+
+        // The very same paths as the image. The depth comes from a jittered ray, which hits
+        // the sphere a little off its front.
+        for y in 0..11 {
+            for x in 0..11 {
+                assert_fuzzy_eq!(buffers.beauty.pixel_at(x, y), image.pixel_at(x, y));
+            }
+        }
+
+        assert!((buffers.depth.pixel_at(5, 5).red - 4.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn supersampled_buffers_line_up_with_the_image() {
+        let wall = Triangle::with_material(
+            Material::from(Phong {
+                emissive: Color::white(),
+                ambient: 0.0,
+                diffuse: 0.0,
+                ..Phong::default()
+            }),
+            Tuple::point(0.0, -10.0, 0.0),
+            Tuple::point(0.0, 10.0, 0.0),
+            Tuple::point(20.0, 0.0, 0.0),
+        );
+        let w = World::new(vec![Body::from(wall)], vec![]);
+
+        let buffers = camera().with_samples_per_pixel(16).render_with_aovs(&w);
+
+        // The edge of the triangle runs through the center column of pixels, which the image
+        // and the albedo both see the same part of
+        let edge = buffers.beauty.pixel_at(5, 5).red;
+        assert!(edge > 0.2 && edge < 0.8);
+        assert_fuzzy_eq!(buffers.albedo.pixel_at(5, 5).red, edge);
+    }
+}
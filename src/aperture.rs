@@ -0,0 +1,161 @@
+use std::f64::consts::PI;
+
+use crate::F;
+
+/// Opening of a camera lens. Out of focus highlights (bokeh) take on its shape.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Aperture {
+    /// Infinitely small, everything is in focus
+    #[default]
+    Pinhole,
+    /// Round opening of the given radius
+    Disk { radius: F },
+    /// Regular polygon, like the blades of an iris diaphragm form. Built by `polygon`, which
+    /// makes sure there are enough blades.
+    Polygon(PolygonAperture),
+}
+
+/// Regular polygon with `blades` corners at the given radius. `rotation` turns it around the
+/// optical axis. The fields stay private, so the number of blades is always checked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolygonAperture {
+    radius: F,
+    blades: usize,
+    rotation: F,
+}
+
+impl Aperture {
+    /// Panics for fewer than 3 blades, which do not enclose any area.
+    pub fn polygon(radius: F, blades: usize, rotation: F) -> Self {
+        if blades < 3 {
+            panic!("A polygonal aperture needs at least 3 blades.");
+        }
+
+        Aperture::Polygon(PolygonAperture {
+            radius,
+            blades,
+            rotation,
+        })
+    }
+
+    /// Point on the lens, relative to its center, uniformly distributed over the opening.
+    /// `u1` and `u2` are in `[0, 1)`.
+    pub fn sample(&self, u1: F, u2: F) -> (F, F) {
+        match *self {
+            Aperture::Pinhole => (0.0, 0.0),
+            Aperture::Disk { radius } => {
+                let (x, y) = concentric_disk(u1, u2);
+                (x * radius, y * radius)
+            }
+            Aperture::Polygon(PolygonAperture {
+                radius,
+                blades,
+                rotation,
+            }) => {
+                // Pick one of the triangles between the center and two neighbouring corners,
+                // then a point within it
+                let sector = ((u1 * blades as F) as usize).min(blades - 1);
+                let u1 = u1 * blades as F - sector as F;
+
+                let corner = |i: usize| {
+                    let angle = rotation + 2.0 * PI * i as F / blades as F;
+                    (radius * angle.cos(), radius * angle.sin())
+                };
+                let ((x1, y1), (x2, y2)) = (corner(sector), corner(sector + 1));
+
+                let scale = u1.sqrt();
+                (
+                    scale * (x1 * (1.0 - u2) + x2 * u2),
+                    scale * (y1 * (1.0 - u2) + y2 * u2),
+                )
+            }
+        }
+    }
+}
+
+/// Maps the unit square onto the unit disk, keeping neighbouring points together, so
+/// stratified samples stay stratified (Shirley and Chiu).
+fn concentric_disk(u1: F, u2: F) -> (F, F) {
+    let (a, b) = (2.0 * u1 - 1.0, 2.0 * u2 - 1.0);
+
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let (radius, angle) = if a.abs() > b.abs() {
+        (a, PI / 4.0 * (b / a))
+    } else {
+        (b, PI / 2.0 - PI / 4.0 * (a / b))
+    };
+
+    (radius * angle.cos(), radius * angle.sin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzy_eq::*;
+    use crate::random::Rng;
+    use crate::EPSILON;
+
+    #[test]
+    fn a_pinhole_has_no_area() {
+        assert_eq!(Aperture::Pinhole.sample(0.3, 0.8), (0.0, 0.0));
+    }
+
+    #[test]
+    fn the_center_of_the_square_maps_to_the_center_of_the_disk() {
+        assert_eq!(Aperture::Disk { radius: 2.0 }.sample(0.5, 0.5), (0.0, 0.0));
+    }
+
+    #[test]
+    fn disk_samples_cover_the_disk_evenly() {
+        let aperture = Aperture::Disk { radius: 2.0 };
+        let mut rng = Rng::new(3);
+        let samples = 10000;
+
+        let points: Vec<(F, F)> = (0..samples)
+            .map(|_| aperture.sample(rng.next_f64(), rng.next_f64()))
+            .collect();
+        // Half of the area lies within a radius of 2 / sqrt(2)
+        let inner = points
+            .iter()
+            .filter(|(x, y)| x.hypot(*y) < (2.0 as F).sqrt())
+            .count();
+
+        assert!(points.iter().all(|(x, y)| x.hypot(*y) <= 2.0 + EPSILON));
+        assert!((inner as F / samples as F - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn polygon_samples_stay_within_the_polygon() {
+        let aperture = Aperture::polygon(1.0, 6, 0.0);
+        let mut rng = Rng::new(5);
+        // Distance of the edges of a hexagon from its center
+        let inradius = (PI / 6.0).cos();
+
+        for _ in 0..1000 {
+            let (x, y) = aperture.sample(rng.next_f64(), rng.next_f64());
+            let angle = y.atan2(x).rem_euclid(PI / 3.0) - PI / 6.0;
+
+            assert!(x.hypot(y) * angle.cos() <= inradius + EPSILON);
+        }
+    }
+
+    #[test]
+    fn polygon_samples_reach_the_corners() {
+        let aperture = Aperture::polygon(1.0, 5, 0.3);
+
+        // The first corner is reached at the end of the first of five sectors
+        let (x, y) = aperture.sample(0.2 - 1e-9, 0.0);
+
+        assert_fuzzy_eq!(x, (0.3 as F).cos());
+        assert_fuzzy_eq!(y, (0.3 as F).sin());
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 3 blades")]
+    fn a_polygon_needs_at_least_3_blades() {
+        Aperture::polygon(1.0, 2, 0.0);
+    }
+}
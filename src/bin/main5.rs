@@ -1,16 +1,14 @@
 extern crate ray_tracer_challenge as raytracer;
 
-use itertools::Itertools;
-use rayon::prelude::*;
-use raytracer::light::PointLight;
 use raytracer::material::*;
 use std::fs::write;
-use std::sync::Mutex;
 use raytracer::body::*;
 use raytracer::canvas::to_png::*;
 use raytracer::canvas::*;
 use raytracer::ray::*;
+use raytracer::sky::*;
 use raytracer::sphere::*;
+use raytracer::tile::*;
 use raytracer::tuple::*;
 
 use indicatif::ProgressBar;
@@ -20,16 +18,16 @@ fn main() {
     let wall_position_z = 5.0;
     let wall_size = 10.0;
 
-    let canvas_size = 1024;
+    let canvas_size: usize = 1024;
     let canvas_pixel_world_size = wall_size / canvas_size as f64;
 
 
     let material = Material::from(Phong::with_color(Color::new(1.0, 0.75, 1.0)));
     let sphere = Sphere::with_material(material, None);
 
-    let light = PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::new(0.0, 0.0, 1.0));
-
-    let canvas_mutex = Mutex::new(Canvas::new(canvas_size, canvas_size));
+    // Afternoon sun from the upper left, behind the viewer
+    let sky = Sky::new(Tuple::vector(-1.0, 1.0, -1.0), 3.0);
+    let light = sky.sun(1.0);
 
     println!(
         "Raytracing {} pixels. Please be patient...",
@@ -39,43 +37,44 @@ fn main() {
     let progress = ProgressBar::new(canvas_size.pow(2) as u64);
     progress.set_draw_rate(5);
 
-    (0..canvas_size) // x
-        .cartesian_product(0..canvas_size) // y
-        .par_bridge()
-        .for_each(|(x, y)| {
-            let half = wall_size / 2.0;
-            let world_x = -half + (x as f64) * canvas_pixel_world_size;
-            let world_y = half - (y as f64) * canvas_pixel_world_size;
-
-            let wall_point = Tuple::point(world_x, world_y, wall_position_z);
+    let tiles = TileRenderer::default().with_order(TileOrder::Spiral);
+    let canvas = tiles.render(canvas_size, canvas_size, |x, y| {
+        let half = wall_size / 2.0;
+        let world_x = -half + (x as f64) * canvas_pixel_world_size;
+        let world_y = half - (y as f64) * canvas_pixel_world_size;
 
-            let ray = Ray::new(ray_origin, (wall_point - ray_origin).normalize());
+        let wall_point = Tuple::point(world_x, world_y, wall_position_z);
 
-            let xs = sphere.intersect(ray);
-            
-            let hit = xs.hit();
+        let ray = Ray::new(ray_origin, (wall_point - ray_origin).normalize());
 
-            if let Some(hit) = hit {
-                let position = ray.position(hit.t);
-                let normal_vec = hit.body.normal_vector(position);
-                let eye_vec = -ray.direction;
-                let color = hit.body.material().lighting(light, position, eye_vec, normal_vec);
+        let xs = sphere.intersect(ray);
 
+        let hit = xs.hit();
 
-                let mut canvas = canvas_mutex.lock().unwrap();
-                canvas.write_pixel(x, y, color);
+        let color = match hit {
+            Some(hit) => {
+                let computed = hit.get_computed(ray, &xs);
+                computed.body.material().lighting(
+                    &light,
+                    computed.position,
+                    computed.eye_vec,
+                    computed.normal_vec,
+                    1.0,
+                )
             }
-          
-            progress.inc(1);
+            None => sky.radiance(ray.direction),
+        };
+
+        progress.inc(1);
+
+        color
     });
 
     progress.finish();
 
     println!("Writing ./output.png");
 
-    let canvas = canvas_mutex.lock().unwrap();
     let png = canvas.to_png();
-    drop(canvas);
     write("./output.png", png).expect("Could not write ouput.png to disk.");
 
     println!("Writing ./output.ppm");
@@ -0,0 +1,128 @@
+extern crate ray_tracer_challenge as raytracer;
+
+use std::f64::consts::PI;
+use std::fs::write;
+use std::path::Path;
+use std::time::Duration;
+
+use raytracer::body::*;
+use raytracer::camera::*;
+use raytracer::canvas::to_png::*;
+use raytracer::canvas::*;
+use raytracer::filter::*;
+use raytracer::light::*;
+use raytracer::material::*;
+use raytracer::matrix::*;
+use raytracer::path_tracer::*;
+use raytracer::progressive::*;
+use raytracer::sphere::*;
+use raytracer::triangle::*;
+use raytracer::tuple::*;
+use raytracer::world::*;
+
+// Two triangles spanning the parallelogram given by a corner and two edges
+fn quad(material: Material, corner: Tuple, edge1: Tuple, edge2: Tuple) -> Vec<Body> {
+    vec![
+        Body::from(Triangle::with_material(
+            material.clone(),
+            corner,
+            corner + edge1,
+            corner + edge1 + edge2,
+        )),
+        Body::from(Triangle::with_material(
+            material,
+            corner,
+            corner + edge1 + edge2,
+            corner + edge2,
+        )),
+    ]
+}
+
+fn main() {
+    let white = Material::from(Phong::with_color(Color::new(0.75, 0.75, 0.75)));
+    let red = Material::from(Phong::with_color(Color::new(0.75, 0.2, 0.2)));
+    let green = Material::from(Phong::with_color(Color::new(0.2, 0.75, 0.2)));
+    let lamp = Material::from(Phong {
+        emissive: Color::new(12.0, 12.0, 12.0),
+        ..Phong::default()
+    });
+    let glass = Material::from(Phong {
+        color: Color::black(),
+        transparency: 0.9,
+        reflective: 0.9,
+        refractive_index: 1.5,
+        ..Phong::default()
+    });
+    let gold = Material::from(CookTorrance::new(Color::new(1.0, 0.78, 0.34), 0.1, 0.3, 1.0));
+
+    let x = Tuple::vector(2.0, 0.0, 0.0);
+    let y = Tuple::vector(0.0, 2.0, 0.0);
+    let z = Tuple::vector(0.0, 0.0, 2.0);
+
+    let mut bodies = vec![];
+    // floor, ceiling, back wall, left and right wall of a box from (-1, 0, -1) to (1, 2, 1)
+    bodies.extend(quad(white.clone(), Tuple::point(-1.0, 0.0, -1.0), z, x));
+    bodies.extend(quad(white.clone(), Tuple::point(-1.0, 2.0, -1.0), x, z));
+    bodies.extend(quad(white, Tuple::point(-1.0, 0.0, 1.0), y, x));
+    bodies.extend(quad(red, Tuple::point(-1.0, 0.0, -1.0), y, z));
+    bodies.extend(quad(green, Tuple::point(1.0, 0.0, -1.0), z, y));
+    bodies.extend(quad(
+        lamp,
+        Tuple::point(-0.25, 1.999, -0.25),
+        Tuple::vector(0.5, 0.0, 0.0),
+        Tuple::vector(0.0, 0.0, 0.5),
+    ));
+    bodies.push(Body::from(Sphere::with_material(
+        glass,
+        Some(Matrix::translation(-0.4, 0.35, -0.2) * Matrix::scaling(0.35, 0.35, 0.35)),
+    )));
+    bodies.push(Body::from(Sphere::with_material(
+        gold,
+        Some(Matrix::translation(0.45, 0.35, 0.35) * Matrix::scaling(0.35, 0.35, 0.35)),
+    )));
+
+    // Matches the lamp, just below it, so the ray tracer gets soft shadows as well
+    let light = RectangleLight::new(
+        Tuple::point(-0.25, 1.99, -0.25),
+        Tuple::vector(0.5, 0.0, 0.0),
+        4,
+        Tuple::vector(0.0, 0.0, 0.5),
+        4,
+        Color::new(1.0, 1.0, 1.0),
+    );
+    let world = World::new(bodies, vec![Light::from(light)]);
+
+    let camera = Camera::new(400, 400, PI / 3.0)
+        .with_samples_per_pixel(16)
+        .with_filter(Filter::mitchell())
+        .with_transform(Matrix::view_transform(
+            Tuple::point(0.0, 1.0, -3.5),
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ));
+
+    println!("Ray tracing ./output_ray_traced.png");
+    let canvas = camera.render(&world);
+    write("./output_ray_traced.png", canvas.to_png())
+        .expect("Could not write output_ray_traced.png to disk.");
+
+    // Written after every pass, so there is something to look at long before it is done. A
+    // render which gets killed picks up from its last checkpoint when started again.
+    println!("Path tracing ./output_path_traced.png. Please be patient...");
+    let path_tracer = PathTracer::default();
+    let emitters = PathTracer::emitters(&world);
+    ProgressiveRenderer::new(64, 4)
+        .render_with_checkpoints(
+            &camera,
+            |ray, rng| path_tracer.radiance(&world, &emitters, ray, rng),
+            Path::new("./output_path_traced.checkpoint"),
+            Duration::from_secs(60),
+            |_, accumulator| {
+                write("./output_path_traced.png", accumulator.canvas().to_png())
+                    .expect("Could not write output_path_traced.png to disk.")
+            },
+        )
+        .expect("Could not save the checkpoint of the path traced render.");
+
+    println!("Everything done.");
+}
@@ -1,19 +1,28 @@
+use crate::emitter::Emitter;
 use crate::material::Material;
+use crate::random::Rng;
 use crate::ray::*;
 use crate::sphere::*;
+use crate::triangle::*;
 use crate::intersections::*;
 use crate::tuple::*;
+use crate::F;
 
 
 pub trait Intersectable {
     fn intersect(&self, ray: Ray) -> Intersections;
     fn normal_vector(&self, point: Tuple) -> Tuple;
     fn material(&self) -> Material;
+    /// Texture coordinates of a point on the surface.
+    fn uv(&self, point: Tuple) -> (F, F);
+    /// Normalized surface directions along which the texture coordinates u and v grow.
+    fn tangents(&self, point: Tuple) -> (Tuple, Tuple);
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Body {
-    Sphere(Sphere)
+    Sphere(Sphere),
+    Triangle(Triangle),
 }
 
 impl From<Sphere> for Body {
@@ -22,40 +31,105 @@ impl From<Sphere> for Body {
     }
 }
 
+impl From<Triangle> for Body {
+    fn from(triangle: Triangle) -> Self {
+        Body::Triangle(triangle)
+    }
+}
+
+impl Body {
+    pub fn is_moving(&self) -> bool {
+        match *self {
+            Body::Sphere(ref sphere) => sphere.is_moving(),
+            Body::Triangle(ref triangle) => triangle.is_moving(),
+        }
+    }
+
+    /// The body frozen where it is at the given time, in case it moves.
+    pub fn at_time(&self, time: F) -> Body {
+        match *self {
+            Body::Sphere(ref sphere) => Body::from(sphere.at_time(time)),
+            Body::Triangle(ref triangle) => Body::from(triangle.at_time(time)),
+        }
+    }
+}
+
 impl Intersectable for Body {
     fn intersect(&self, ray: Ray) -> Intersections {
         match *self {
             Body::Sphere(ref sphere) => sphere.intersect(ray),
+            Body::Triangle(ref triangle) => triangle.intersect(ray),
         }
     }
 
     fn normal_vector(&self, point: Tuple) -> Tuple {
         match *self {
             Body::Sphere(ref sphere) => sphere.normal_vector(point),
+            Body::Triangle(ref triangle) => triangle.normal_vector(point),
         }
     }
 
     fn material(&self) -> Material {
         match *self {
             Body::Sphere(ref shpere) => shpere.material(),
+            Body::Triangle(ref triangle) => triangle.material(),
+        }
+    }
+
+    fn uv(&self, point: Tuple) -> (F, F) {
+        match *self {
+            Body::Sphere(ref sphere) => sphere.uv(point),
+            Body::Triangle(ref triangle) => triangle.uv(point),
+        }
+    }
+
+    fn tangents(&self, point: Tuple) -> (Tuple, Tuple) {
+        match *self {
+            Body::Sphere(ref sphere) => sphere.tangents(point),
+            Body::Triangle(ref triangle) => triangle.tangents(point),
         }
     }
 }
 
+impl Emitter for Body {
+    fn sample_surface(&self, rng: &mut Rng) -> Tuple {
+        match *self {
+            Body::Sphere(ref sphere) => sphere.sample_surface(rng),
+            Body::Triangle(ref triangle) => triangle.sample_surface(rng),
+        }
+    }
 
+    fn area_pdf(&self, point: Tuple) -> F {
+        match *self {
+            Body::Sphere(ref sphere) => sphere.area_pdf(point),
+            Body::Triangle(ref triangle) => triangle.area_pdf(point),
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::fuzzy_eq::*;
+    use crate::matrix::Matrix;
 
     #[test]
     fn an_intersection_encapsulates_t_and_object() {
         let s = Sphere::new(None);
         
-        let i = Intersection::new(3.5, Body::from(s));
+        let i = Intersection::new(3.5, Body::from(s.clone()));
 
         assert_fuzzy_eq!(i.t, 3.5);
         assert_eq!(i.body, Body::from(s));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn only_bodies_with_motion_are_moving() {
+        let s = Sphere::new(None);
+        let moving = s.clone().with_motion(Matrix::translation(1.0, 0.0, 0.0));
+
+        assert!(!Body::from(s).is_moving());
+        assert!(Body::from(moving.clone()).is_moving());
+        assert!(!Body::from(moving).at_time(0.5).is_moving());
+    }
+}
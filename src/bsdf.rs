@@ -0,0 +1,345 @@
+use crate::canvas::Color;
+use crate::computed_intersection::ComputedIntersection;
+use crate::material::*;
+use crate::random::Rng;
+use crate::ray::Ray;
+use crate::sampling::*;
+use crate::tuple::Tuple;
+use crate::F;
+use std::f64::consts::PI;
+
+/// How light scatters at a surface hit, as seen by the path tracer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bsdf {
+    /// Lambertian surface, scattering light equally in all directions
+    Diffuse { albedo: Color },
+    /// Mirror, which gets blurry with increasing fuzz
+    Metal { albedo: Color, fuzz: F },
+    /// Clear glass like material, which either reflects or refracts
+    Dielectric,
+}
+
+/// Direction a path continues in, along with how much of the light from that direction
+/// reaches the previous vertex of the path.
+#[derive(Debug, Clone, Copy)]
+pub struct BsdfSample {
+    pub ray: Ray,
+    pub weight: Color,
+    /// Probability density per solid angle of the picked direction, meaningless for
+    /// specular scattering
+    pub pdf: F,
+}
+
+/// Scattering, which can not only be sampled, but also evaluated for arbitrary directions.
+/// That is what lets the path tracer aim at light sources directly.
+pub trait Scattering {
+    /// Continues the path at the given hit. `None` means the light got absorbed.
+    fn sample(&self, computed: &ComputedIntersection, rng: &mut Rng) -> Option<BsdfSample>;
+
+    /// Fraction of the light arriving from `direction`, which gets scattered towards the
+    /// eye, including the cosine falloff with the angle of incidence.
+    fn eval(&self, computed: &ComputedIntersection, direction: Tuple) -> Color;
+
+    /// Probability density per solid angle, with which `sample` picks `direction`.
+    fn pdf(&self, computed: &ComputedIntersection, direction: Tuple) -> F;
+
+    /// Whether light only scatters into a few exact directions, which can never be hit by
+    /// aiming at a light source.
+    fn is_specular(&self) -> bool;
+}
+
+impl Bsdf {
+    /// Picks the scattering behaviour for a hit on the given material. Materials which mix
+    /// refraction, mirror reflection and diffuse scattering pick one of them at random, in
+    /// proportion to how much each one contributes.
+    pub fn choose(material: &Material, rng: &mut Rng) -> Self {
+        let (diffuse_albedo, metal) = match *material {
+            Material::Phong(Phong {
+                color, diffuse, ..
+            })
+            | Material::BlinnPhong(BlinnPhong {
+                color, diffuse, ..
+            }) => (
+                color * diffuse,
+                (
+                    material.reflective(),
+                    Bsdf::Metal {
+                        albedo: Color::white(),
+                        fuzz: 0.0,
+                    },
+                ),
+            ),
+            Material::CookTorrance(CookTorrance {
+                color,
+                metallic,
+                roughness,
+                reflective,
+                ..
+            }) => (
+                color,
+                (
+                    metallic.max(reflective),
+                    Bsdf::Metal {
+                        albedo: color,
+                        fuzz: roughness.powi(2),
+                    },
+                ),
+            ),
+        };
+        let transparency = material.transparency();
+        let (metal_probability, metal_bsdf) = metal;
+
+        let choice = rng.next_f64();
+
+        if choice < transparency {
+            Bsdf::Dielectric
+        } else if choice < transparency + metal_probability {
+            metal_bsdf
+        } else {
+            Bsdf::Diffuse {
+                albedo: diffuse_albedo,
+            }
+        }
+    }
+}
+
+impl Scattering for Bsdf {
+    fn sample(&self, computed: &ComputedIntersection, rng: &mut Rng) -> Option<BsdfSample> {
+        match *self {
+            Bsdf::Diffuse { albedo } => {
+                // With cosine weighted sampling, the cosine term and the lambertian
+                // 1 / PI cancel out with the probability density.
+                let direction = cosine_weighted_hemisphere(computed.normal_vec, rng);
+
+                Some(BsdfSample {
+                    ray: Ray::new(computed.over_point, direction).with_time(computed.time),
+                    weight: albedo,
+                    pdf: self.pdf(computed, direction),
+                })
+            }
+            Bsdf::Metal { albedo, fuzz } => {
+                let direction =
+                    (computed.reflect_vec.normalize() + in_unit_sphere(rng) * fuzz).normalize();
+
+                if direction.dot(computed.normal_vec) <= 0.0 {
+                    // Fuzz pushed the reflection below the surface
+                    return None;
+                }
+
+                Some(BsdfSample {
+                    ray: Ray::new(computed.over_point, direction).with_time(computed.time),
+                    weight: albedo,
+                    pdf: 0.0,
+                })
+            }
+            Bsdf::Dielectric => {
+                let n_ratio = computed.n1 / computed.n2;
+                let cos_i = computed.eye_vec.dot(computed.normal_vec);
+                let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
+
+                // Pick reflection or refraction in proportion to the Fresnel reflectance,
+                // total internal reflection always ends up reflecting.
+                let ray = if rng.next_f64() < computed.schlick() {
+                    Ray::new(computed.over_point, computed.reflect_vec).with_time(computed.time)
+                } else {
+                    let cos_t = (1.0 - sin2_t).sqrt();
+                    let direction = computed.normal_vec * (n_ratio * cos_i - cos_t)
+                        - computed.eye_vec * n_ratio;
+
+                    Ray::new(computed.under_point, direction).with_time(computed.time)
+                };
+
+                Some(BsdfSample {
+                    ray,
+                    weight: Color::white(),
+                    pdf: 0.0,
+                })
+            }
+        }
+    }
+
+    fn eval(&self, computed: &ComputedIntersection, direction: Tuple) -> Color {
+        match *self {
+            Bsdf::Diffuse { albedo } => {
+                let cosine = direction.dot(computed.normal_vec);
+
+                if cosine <= 0.0 {
+                    Color::black()
+                } else {
+                    albedo * (cosine / PI)
+                }
+            }
+            Bsdf::Metal { .. } | Bsdf::Dielectric => Color::black(),
+        }
+    }
+
+    fn pdf(&self, computed: &ComputedIntersection, direction: Tuple) -> F {
+        match *self {
+            Bsdf::Diffuse { .. } => direction.dot(computed.normal_vec).max(0.0) / PI,
+            Bsdf::Metal { .. } | Bsdf::Dielectric => 0.0,
+        }
+    }
+
+    fn is_specular(&self) -> bool {
+        // Fuzzy metals count as specular as well, as the density of the fuzz is unknown
+        !matches!(*self, Bsdf::Diffuse { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use crate::fuzzy_eq::*;
+    use crate::intersections::*;
+    use crate::sphere::Sphere;
+    use crate::tuple::Tuple;
+
+    fn computed_front_hit(material: Material) -> ComputedIntersection {
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let body = Body::from(Sphere::with_material(material, None));
+        let xs = Intersections::from(vec![
+            Intersection::new(4.0, body.clone()),
+            Intersection::new(6.0, body),
+        ]);
+
+        xs[0].get_computed(r, &xs)
+    }
+
+    #[test]
+    fn opaque_phong_materials_scatter_diffusely() {
+        let material = Material::from(Phong::with_color(Color::new(1.0, 0.5, 0.0)));
+        let mut rng = Rng::new(0);
+
+        let bsdf = Bsdf::choose(&material, &mut rng);
+
+        assert_eq!(
+            bsdf,
+            Bsdf::Diffuse {
+                albedo: Color::new(0.9, 0.45, 0.0)
+            }
+        );
+    }
+
+    #[test]
+    fn fully_transparent_materials_are_dielectrics() {
+        let material = Material::from(Phong {
+            transparency: 1.0,
+            refractive_index: 1.5,
+            ..Phong::default()
+        });
+        let mut rng = Rng::new(0);
+
+        assert_eq!(Bsdf::choose(&material, &mut rng), Bsdf::Dielectric);
+    }
+
+    #[test]
+    fn fully_metallic_cook_torrance_materials_are_metals() {
+        let material = Material::from(CookTorrance::new(Color::new(1.0, 0.8, 0.3), 0.1, 0.5, 1.0));
+        let mut rng = Rng::new(0);
+
+        assert_eq!(
+            Bsdf::choose(&material, &mut rng),
+            Bsdf::Metal {
+                albedo: Color::new(1.0, 0.8, 0.3),
+                fuzz: 0.25
+            }
+        );
+    }
+
+    #[test]
+    fn diffuse_samples_leave_the_surface() {
+        let computed = computed_front_hit(Material::default());
+        let bsdf = Bsdf::Diffuse {
+            albedo: Color::white(),
+        };
+        let mut rng = Rng::new(1);
+
+        for _ in 0..100 {
+            let sample = bsdf.sample(&computed, &mut rng).unwrap();
+
+            assert!(sample.ray.direction.dot(computed.normal_vec) >= 0.0);
+            assert_fuzzy_eq!(sample.ray.origin, computed.over_point);
+        }
+    }
+
+    #[test]
+    fn perfect_metals_reflect_in_the_mirror_direction() {
+        let computed = computed_front_hit(Material::default());
+        let bsdf = Bsdf::Metal {
+            albedo: Color::new(0.5, 0.5, 0.5),
+            fuzz: 0.0,
+        };
+        let mut rng = Rng::new(1);
+
+        let sample = bsdf.sample(&computed, &mut rng).unwrap();
+
+        assert_fuzzy_eq!(sample.ray.direction, Tuple::vector(0.0, 0.0, -1.0));
+        assert_fuzzy_eq!(sample.weight, Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn dielectrics_mostly_refract_at_normal_incidence() {
+        let computed = computed_front_hit(Material::from(Phong {
+            transparency: 1.0,
+            refractive_index: 1.5,
+            ..Phong::default()
+        }));
+        let mut rng = Rng::new(1);
+        let samples = 1000;
+
+        let refracted = (0..samples)
+            .filter(|_| {
+                let sample = Bsdf::Dielectric.sample(&computed, &mut rng).unwrap();
+                sample.ray.direction.dot(computed.normal_vec) < 0.0
+            })
+            .count();
+
+        // Schlick reflectance is 0.04 when looking straight at glass
+        let refracted_fraction = refracted as F / samples as F;
+        assert!((refracted_fraction - 0.96).abs() < 0.02);
+    }
+
+    #[test]
+    fn diffuse_samples_match_their_evaluation() {
+        let computed = computed_front_hit(Material::default());
+        let bsdf = Bsdf::Diffuse {
+            albedo: Color::new(0.8, 0.6, 0.4),
+        };
+        let mut rng = Rng::new(2);
+
+        for _ in 0..100 {
+            let sample = bsdf.sample(&computed, &mut rng).unwrap();
+            let direction = sample.ray.direction;
+
+            assert_fuzzy_eq!(sample.pdf, bsdf.pdf(&computed, direction));
+            assert_fuzzy_eq!(bsdf.eval(&computed, direction) * (1.0 / sample.pdf), sample.weight);
+        }
+    }
+
+    #[test]
+    fn diffuse_surfaces_scatter_nothing_from_below() {
+        let computed = computed_front_hit(Material::default());
+        let bsdf = Bsdf::Diffuse {
+            albedo: Color::white(),
+        };
+        let below = Tuple::vector(0.0, 0.0, 1.0);
+
+        assert_fuzzy_eq!(bsdf.eval(&computed, below), Color::black());
+        assert_fuzzy_eq!(bsdf.pdf(&computed, below), 0.0);
+    }
+
+    #[test]
+    fn only_diffuse_scattering_is_not_specular() {
+        assert!(!Bsdf::Diffuse {
+            albedo: Color::white()
+        }
+        .is_specular());
+        assert!(Bsdf::Metal {
+            albedo: Color::white(),
+            fuzz: 0.2
+        }
+        .is_specular());
+        assert!(Bsdf::Dielectric.is_specular());
+    }
+}
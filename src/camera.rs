@@ -0,0 +1,959 @@
+use std::f64::consts::PI;
+
+use crate::aov::RenderBuffers;
+use crate::aperture::Aperture;
+use crate::canvas::{Canvas, Color};
+use crate::filter::Filter;
+use crate::matrix::Matrix;
+use crate::random::Rng;
+use crate::ray::Ray;
+use crate::sampling::{shuffle, stratified_2d};
+use crate::tile::TileRenderer;
+use crate::tuple::Tuple;
+use crate::world::World;
+use crate::F;
+
+/// Maps the pixels of a canvas onto rays into the world. The camera sits at the origin and
+/// looks towards -z, until moved around with a view transformation. How the pixels spread
+/// across the view depends on the projection, perspective being the default.
+///
+/// With more than one sample per pixel, the rays are jittered across the footprint of the
+/// reconstruction filter, which then weights them into the color of the pixel.
+///
+/// A perspective or orthographic camera with an aperture other than a pinhole only keeps
+/// things at the focal distance sharp. Just supersampled rays pass through random points of
+/// the lens though, a single ray per pixel and adaptive sampling look through its center and
+/// see everything in focus.
+///
+/// Supersampled rays are also spread across the time the shutter is open, which blurs moving
+/// bodies. Other rays are taken the moment the shutter opens.
+///
+/// Adaptive sampling replaces that for plain ray tracing: only pixels with differently colored
+/// corners get more rays, which is much cheaper when most of the picture is flat.
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    pub hsize: usize,
+    pub vsize: usize,
+    pub field_of_view: F,
+    pub projection: Projection,
+    pub samples_per_pixel: usize,
+    pub filter: Filter,
+    pub adaptive_sampling: Option<AdaptiveSampling>,
+    pub aperture: Aperture,
+    /// Distance of the plane in focus, measured along the viewing direction
+    pub focal_distance: F,
+    /// Time the shutter opens, within the `[0, 1]` bodies move in
+    pub shutter_open: F,
+    pub shutter_close: F,
+    /// How the image is split up between threads
+    pub tiles: TileRenderer,
+    transform: Matrix<4>,
+    inverse_transform: Matrix<4>,
+    pixel_size: F,
+    half_width: F,
+    half_height: F,
+}
+
+impl Camera {
+    pub fn new(hsize: usize, vsize: usize, field_of_view: F) -> Self {
+        // The canvas is one unit in front of the camera
+        let half_view = (field_of_view / 2.0).tan();
+        let aspect = hsize as F / vsize as F;
+
+        let (half_width, half_height) = if aspect >= 1.0 {
+            (half_view, half_view / aspect)
+        } else {
+            (half_view * aspect, half_view)
+        };
+
+        Camera {
+            hsize,
+            vsize,
+            field_of_view,
+            projection: Projection::default(),
+            samples_per_pixel: 1,
+            filter: Filter::default(),
+            adaptive_sampling: None,
+            aperture: Aperture::default(),
+            focal_distance: 1.0,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            tiles: TileRenderer::default(),
+            transform: Matrix::identity(),
+            inverse_transform: Matrix::identity(),
+            pixel_size: half_width * 2.0 / hsize as F,
+            half_width,
+            half_height,
+        }
+    }
+
+    pub fn with_transform(mut self, transform: Matrix<4>) -> Self {
+        self.transform = transform;
+        self.inverse_transform = transform.inverse();
+        self
+    }
+
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    pub fn with_samples_per_pixel(mut self, samples_per_pixel: usize) -> Self {
+        self.samples_per_pixel = samples_per_pixel;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Refines pixels, whose colors differ by more than `threshold` in any channel, by
+    /// splitting them into quadrants up to `max_depth` times.
+    pub fn with_adaptive_sampling(mut self, threshold: F, max_depth: usize) -> Self {
+        self.adaptive_sampling = Some(AdaptiveSampling {
+            threshold,
+            max_depth,
+        });
+        self
+    }
+
+    pub fn with_depth_of_field(mut self, aperture: Aperture, focal_distance: F) -> Self {
+        self.aperture = aperture;
+        self.focal_distance = focal_distance;
+        self
+    }
+
+    pub fn with_shutter(mut self, open: F, close: F) -> Self {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+
+    pub fn with_tiles(mut self, tiles: TileRenderer) -> Self {
+        self.tiles = tiles;
+        self
+    }
+
+    pub fn transform(&self) -> Matrix<4> {
+        self.transform
+    }
+
+    pub fn pixel_size(&self) -> F {
+        self.pixel_size
+    }
+
+    /// Ray through the center of the given pixel, if the projection has one there.
+    pub fn ray_for_pixel(&self, px: usize, py: usize) -> Option<Ray> {
+        self.ray_through(px as F + 0.5, py as F + 0.5)
+    }
+
+    /// Ray through an arbitrary position on the canvas, given in pixel units. There is none
+    /// for positions outside of the image circle of a fisheye camera.
+    pub fn ray_through(&self, x: F, y: F) -> Option<Ray> {
+        self.ray_through_lens(x, y, (0.0, 0.0))
+    }
+
+    /// Ray leaving the given point of the lens, relative to its center, which passes through
+    /// the focal plane where the ray through the center of the lens does. Only perspective
+    /// and orthographic cameras have a lens, the others ignore it.
+    pub fn ray_through_lens(&self, x: F, y: F, (lens_x, lens_y): (F, F)) -> Option<Ray> {
+        let (origin, direction) = self.projection_ray(x, y)?;
+
+        let (origin, direction) = match self.projection {
+            Projection::Perspective | Projection::Orthographic { .. } => {
+                // The canvas is one unit in front of the camera, the focal plane further away
+                let focus = origin + direction * (self.focal_distance / -direction.z);
+                let origin = origin + Tuple::vector(lens_x, lens_y, 0.0);
+
+                (origin, focus - origin)
+            }
+            Projection::Fisheye | Projection::Equirectangular => (origin, direction),
+        };
+
+        let origin = self.inverse_transform * origin;
+        let direction = (self.inverse_transform * direction).normalize();
+
+        Some(Ray::new(origin, direction).with_time(self.shutter_open))
+    }
+
+    /// Origin and direction of the ray through the given position on the canvas, before the
+    /// camera is moved into place.
+    fn projection_ray(&self, x: F, y: F) -> Option<(Tuple, Tuple)> {
+        let origin = Tuple::point(0.0, 0.0, 0.0);
+        // Both in [-1, 1] along the longer side of the canvas, with x growing towards the left
+        // (+x, as the camera looks towards -z) and y growing upwards
+        let longer_side = self.hsize.max(self.vsize) as F;
+        let u = (self.hsize as F - 2.0 * x) / longer_side;
+        let v = (self.vsize as F - 2.0 * y) / longer_side;
+
+        match self.projection {
+            Projection::Perspective => {
+                // Untransformed coordinates of the position on the canvas in world space
+                let world_x = self.half_width - x * self.pixel_size;
+                let world_y = self.half_height - y * self.pixel_size;
+
+                Some((origin, Tuple::vector(world_x, world_y, -1.0)))
+            }
+            Projection::Orthographic { size } => Some((
+                Tuple::point(u * size / 2.0, v * size / 2.0, 0.0),
+                Tuple::vector(0.0, 0.0, -1.0),
+            )),
+            Projection::Fisheye => {
+                let radius = u.hypot(v);
+                if radius > 1.0 {
+                    return None;
+                }
+
+                // Equidistant: the angle to the viewing direction grows with the distance from
+                // the center of the image
+                let theta = radius * self.field_of_view / 2.0;
+                let (u, v) = if radius > 0.0 {
+                    (u / radius, v / radius)
+                } else {
+                    (0.0, 0.0)
+                };
+
+                Some((
+                    origin,
+                    Tuple::vector(theta.sin() * u, theta.sin() * v, -theta.cos()),
+                ))
+            }
+            Projection::Equirectangular => {
+                // Longitude from the viewing direction, and angle from straight up
+                let phi = (x / self.hsize as F - 0.5) * 2.0 * PI;
+                let theta = y / self.vsize as F * PI;
+
+                Some((
+                    origin,
+                    Tuple::vector(
+                        -theta.sin() * phi.sin(),
+                        theta.cos(),
+                        -theta.sin() * phi.cos(),
+                    ),
+                ))
+            }
+        }
+    }
+
+    /// Renders the world with plain (Whitted style) ray tracing.
+    pub fn render(&self, world: &World) -> Canvas {
+        match self.adaptive_sampling {
+            Some(adaptive_sampling) => adaptive_sampling.render(self, &|ray| world.color_at(ray)),
+            None => self.render_samples(self.samples_per_pixel, 0, |ray, _| world.color_at(ray)),
+        }
+    }
+
+    /// Renders the world together with the extra buffers compositors and denoisers need.
+    pub fn render_with_aovs(&self, world: &World) -> RenderBuffers {
+        RenderBuffers::render(self, world, self.samples_per_pixel, 0, |ray, _| {
+            world.color_at(ray)
+        })
+    }
+
+    /// Direction the camera is looking at, in world space.
+    pub(crate) fn forward(&self) -> Tuple {
+        (self.inverse_transform * Tuple::vector(0.0, 0.0, -1.0)).normalize()
+    }
+
+    /// Fills a canvas with the filtered colors of the given number of rays per pixel. A single
+    /// sample goes straight through the center of the pixel.
+    pub(crate) fn render_samples<R>(&self, samples: usize, seed: u64, radiance: R) -> Canvas
+    where
+        R: Fn(Ray, &mut Rng) -> Color + Sync,
+    {
+        self.render_pixels(|x, y| {
+            let mut rng = Rng::for_pixel(x, y, seed);
+            let (mut sum, mut total_weight) = (Color::black(), 0.0);
+
+            self.for_each_sample(x, y, samples, &mut rng, |ray, weight, _, rng| {
+                // Positions outside of the image still count, as black
+                if let Some(ray) = ray {
+                    sum = sum + radiance(ray, rng) * weight;
+                }
+                total_weight += weight;
+            });
+
+            if total_weight == 0.0 {
+                Color::black()
+            } else {
+                sum * (1.0 / total_weight)
+            }
+        })
+    }
+
+    /// Hands the rays of the pixel to `sample`, together with their filter weights and their
+    /// offsets from the center of the pixel. A single sample goes straight through the center
+    /// of the pixel, more are spread out like the ones of `sample_pixel`. Positions the
+    /// projection has no ray for come without one.
+    pub(crate) fn for_each_sample<S>(
+        &self,
+        x: usize,
+        y: usize,
+        samples: usize,
+        rng: &mut Rng,
+        mut sample: S,
+    ) where
+        S: FnMut(Option<Ray>, F, (F, F), &mut Rng),
+    {
+        if samples <= 1 {
+            let ray = self.ray_through_lens(x as F + 0.5, y as F + 0.5, (0.0, 0.0));
+            sample(ray, 1.0, (0.0, 0.0), rng);
+        } else {
+            self.for_each_jittered_sample(x, y, samples, rng, sample);
+        }
+    }
+
+    /// Sends the given number of rays through the pixel, spread across the footprint of the
+    /// filter, the lens and the time the shutter is open. Returns the sum of their colors,
+    /// weighted by the filter, together with the sum of the weights.
+    pub(crate) fn sample_pixel<R>(
+        &self,
+        x: usize,
+        y: usize,
+        samples: usize,
+        rng: &mut Rng,
+        radiance: &R,
+    ) -> (Color, F)
+    where
+        R: Fn(Ray, &mut Rng) -> Color,
+    {
+        let (mut sum, mut total_weight) = (Color::black(), 0.0);
+
+        self.for_each_jittered_sample(x, y, samples, rng, |ray, weight, _, rng| {
+            // Positions outside of the image still count, as black
+            if let Some(ray) = ray {
+                sum = sum + radiance(ray, rng) * weight;
+            }
+            total_weight += weight;
+        });
+
+        (sum, total_weight)
+    }
+
+    fn for_each_jittered_sample<S>(
+        &self,
+        x: usize,
+        y: usize,
+        samples: usize,
+        rng: &mut Rng,
+        mut sample: S,
+    ) where
+        S: FnMut(Option<Ray>, F, (F, F), &mut Rng),
+    {
+        let radius = self.filter.radius();
+        let positions = stratified_2d(samples, rng);
+        // The points of both sets are ordered by column, pair them up randomly, so positions
+        // within the pixel and on the lens do not move in step
+        let mut lens_positions = stratified_2d(samples, rng);
+        shuffle(&mut lens_positions, rng);
+
+        for ((u, v), (lens_u, lens_v)) in positions.into_iter().zip(lens_positions) {
+            let (dx, dy) = ((2.0 * u - 1.0) * radius, (2.0 * v - 1.0) * radius);
+            let weight = self.filter.weight(dx, dy);
+            let ray = self.ray_through_lens(
+                x as F + 0.5 + dx,
+                y as F + 0.5 + dy,
+                self.aperture.sample(lens_u, lens_v),
+            );
+            let time =
+                self.shutter_open + (self.shutter_close - self.shutter_open) * rng.next_f64();
+
+            sample(ray.map(|ray| ray.with_time(time)), weight, (dx, dy), rng);
+        }
+    }
+
+    /// Fills a canvas in parallel, tile by tile, with the colors of the given function.
+    pub(crate) fn render_pixels<P>(&self, pixel_color: P) -> Canvas
+    where
+        P: Fn(usize, usize) -> Color + Sync,
+    {
+        self.tiles.render(self.hsize, self.vsize, pixel_color)
+    }
+}
+
+/// How a camera spreads the pixels of the canvas across its view.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Projection {
+    /// Pinhole camera, whose field of view spans the longer side of the canvas
+    #[default]
+    Perspective,
+    /// Parallel rays, as in technical drawings. `size` is the extent of the view in world
+    /// units along the longer side of the canvas.
+    Orthographic { size: F },
+    /// Equidistant fisheye lens, with the field of view (which may exceed PI) across the
+    /// longer side of the canvas. The corners outside of the image circle stay black.
+    Fisheye,
+    /// The full sphere around the camera, as used by panoramas and VR previews, with the
+    /// viewing direction in the center. Canvases with a 2:1 aspect ratio keep it undistorted.
+    Equirectangular,
+}
+
+/// Settings for adaptive supersampling, where each pixel starts out with rays through its
+/// corners and its center. Squares whose colors differ too much are split into four, which
+/// are sampled the same way, reusing the rays already traced.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    /// Largest difference between the colors of a square, in any channel, which is still
+    /// considered flat
+    pub threshold: F,
+    /// How often a pixel may be split at most
+    pub max_depth: usize,
+}
+
+impl AdaptiveSampling {
+    /// Renders the image, tracing the corners of all pixels up front. Neighbouring pixels
+    /// share the rays through their common corners, so a flat pixel costs one more ray.
+    fn render<C>(&self, camera: &Camera, color_at: &C) -> Canvas
+    where
+        C: Fn(Ray) -> Color + Sync,
+    {
+        let columns = camera.hsize + 1;
+        let corners = camera.tiles.map_pixels(columns, camera.vsize + 1, |x, y| {
+            trace_through(camera, x as F, y as F, color_at)
+        });
+
+        camera.render_pixels(|x, y| {
+            let corner = |x: usize, y: usize| corners[y * columns + x];
+            let corners = [
+                corner(x, y),
+                corner(x + 1, y),
+                corner(x, y + 1),
+                corner(x + 1, y + 1),
+            ];
+
+            self.pixel_color(camera, x, y, corners, color_at)
+        })
+    }
+
+    /// Color of the pixel, whose corners (top left, top right, bottom left, bottom right) are
+    /// already known.
+    fn pixel_color<C>(
+        &self,
+        camera: &Camera,
+        x: usize,
+        y: usize,
+        corners: [Color; 4],
+        color_at: &C,
+    ) -> Color
+    where
+        C: Fn(Ray) -> Color,
+    {
+        let color_through = |x: F, y: F| trace_through(camera, x, y, color_at);
+
+        self.square_color(&color_through, (x as F, y as F), 1.0, corners, 0)
+    }
+
+    /// Average color of the square with the given top left corner and size, whose corners
+    /// (top left, top right, bottom left, bottom right) are already known.
+    fn square_color<C>(
+        &self,
+        color_through: &C,
+        (x, y): (F, F),
+        size: F,
+        [top_left, top_right, bottom_left, bottom_right]: [Color; 4],
+        depth: usize,
+    ) -> Color
+    where
+        C: Fn(F, F) -> Color,
+    {
+        let half = size / 2.0;
+        let center = color_through(x + half, y + half);
+        let colors = [top_left, top_right, bottom_left, bottom_right, center];
+
+        if depth >= self.max_depth || contrast(&colors) <= self.threshold {
+            return colors
+                .into_iter()
+                .fold(Color::black(), |sum, color| sum + color)
+                * 0.2;
+        }
+
+        let top = color_through(x + half, y);
+        let left = color_through(x, y + half);
+        let right = color_through(x + size, y + half);
+        let bottom = color_through(x + half, y + size);
+
+        let quadrants = [
+            ((x, y), [top_left, top, left, center]),
+            ((x + half, y), [top, top_right, center, right]),
+            ((x, y + half), [left, center, bottom_left, bottom]),
+            ((x + half, y + half), [center, right, bottom, bottom_right]),
+        ];
+
+        quadrants
+            .into_iter()
+            .fold(Color::black(), |sum, (corner, corners)| {
+                sum + self.square_color(color_through, corner, half, corners, depth + 1)
+            })
+            * 0.25
+    }
+}
+
+/// Color seen through the given position on the canvas, black where the camera has no ray.
+fn trace_through<C>(camera: &Camera, x: F, y: F, color_at: &C) -> Color
+where
+    C: Fn(Ray) -> Color,
+{
+    match camera.ray_through_lens(x, y, (0.0, 0.0)) {
+        Some(ray) => color_at(ray),
+        None => Color::black(),
+    }
+}
+
+/// Largest difference between the given colors in any channel.
+fn contrast(colors: &[Color]) -> F {
+    let channels: [fn(&Color) -> F; 3] = [|c| c.red, |c| c.green, |c| c.blue];
+
+    channels
+        .iter()
+        .map(|channel| {
+            let values = colors.iter().map(channel);
+            let max = values.clone().fold(F::NEG_INFINITY, F::max);
+            let min = values.fold(F::INFINITY, F::min);
+            max - min
+        })
+        .fold(0.0, F::max)
+}
+
+/// Looks at the straight edge of a glowing triangle, which cuts the center column of pixels
+/// in half.
+#[cfg(test)]
+pub(crate) fn edge_of_a_triangle() -> (World, Camera) {
+    use crate::body::Body;
+    use crate::material::{Material, Phong};
+    use crate::triangle::Triangle;
+
+    let triangle = Body::from(Triangle::with_material(
+        Material::from(Phong {
+            emissive: Color::white(),
+            ..Phong::default()
+        }),
+        Tuple::point(0.0, -10.0, 0.0),
+        Tuple::point(0.0, 10.0, 0.0),
+        Tuple::point(20.0, 0.0, 0.0),
+    ));
+    let w = World::new(vec![triangle], vec![]);
+    let c = Camera::new(3, 3, PI / 2.0).with_transform(Matrix::view_transform(
+        Tuple::point(0.0, 0.0, -1.0),
+        Tuple::point(0.0, 0.0, 0.0),
+        Tuple::vector(0.0, 1.0, 0.0),
+    ));
+
+    (w, c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::*;
+    use crate::environment::Background;
+    use crate::fuzzy_eq::*;
+    use crate::material::*;
+    use crate::sphere::Sphere;
+    use crate::world::default_world;
+    use std::cell::Cell;
+    use std::f64::consts::FRAC_1_SQRT_2;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn constructing_a_camera() {
+        let c = Camera::new(160, 120, PI / 2.0);
+
+        assert_eq!(c.hsize, 160);
+        assert_eq!(c.vsize, 120);
+        assert_fuzzy_eq!(c.field_of_view, PI / 2.0);
+        assert_fuzzy_eq!(c.transform(), Matrix::identity());
+    }
+
+    #[test]
+    fn the_pixel_size_for_a_horizontal_canvas() {
+        let c = Camera::new(200, 125, PI / 2.0);
+
+        assert_fuzzy_eq!(c.pixel_size(), 0.01);
+    }
+
+    #[test]
+    fn the_pixel_size_for_a_vertical_canvas() {
+        let c = Camera::new(125, 200, PI / 2.0);
+
+        assert_fuzzy_eq!(c.pixel_size(), 0.01);
+    }
+
+    #[test]
+    fn constructing_a_ray_through_the_center_of_the_canvas() {
+        let c = Camera::new(201, 101, PI / 2.0);
+        let r = c.ray_for_pixel(100, 50).unwrap();
+
+        assert_fuzzy_eq!(r.origin, Tuple::point(0.0, 0.0, 0.0));
+        assert_fuzzy_eq!(r.direction, Tuple::vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn constructing_a_ray_through_a_corner_of_the_canvas() {
+        let c = Camera::new(201, 101, PI / 2.0);
+        let r = c.ray_for_pixel(0, 0).unwrap();
+
+        assert_fuzzy_eq!(r.origin, Tuple::point(0.0, 0.0, 0.0));
+        assert_fuzzy_eq!(r.direction, Tuple::vector(0.66519, 0.33259, -0.66851));
+    }
+
+    #[test]
+    fn constructing_a_ray_when_the_camera_is_transformed() {
+        let c = Camera::new(201, 101, PI / 2.0)
+            .with_transform(Matrix::rotation_y(PI / 4.0) * Matrix::translation(0.0, -2.0, 5.0));
+        let r = c.ray_for_pixel(100, 50).unwrap();
+
+        let sqrt2_over_2 = (2.0 as F).sqrt() / 2.0;
+
+        assert_fuzzy_eq!(r.origin, Tuple::point(0.0, 2.0, -5.0));
+        assert_fuzzy_eq!(r.direction, Tuple::vector(sqrt2_over_2, 0.0, -sqrt2_over_2));
+    }
+
+    #[test]
+    fn rendering_a_world_with_a_camera() {
+        let w = default_world();
+        let from = Tuple::point(0.0, 0.0, -5.0);
+        let to = Tuple::point(0.0, 0.0, 0.0);
+        let up = Tuple::vector(0.0, 1.0, 0.0);
+        let c = Camera::new(11, 11, PI / 2.0).with_transform(Matrix::view_transform(from, to, up));
+
+        let image = c.render(&w);
+
+        assert_fuzzy_eq!(image.pixel_at(5, 5), Color::new(0.38066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    fn a_single_sample_per_pixel_leaves_edges_jagged() {
+        let (w, c) = edge_of_a_triangle();
+
+        let image = c.render(&w);
+
+        assert_fuzzy_eq!(image.pixel_at(1, 1), Color::white());
+    }
+
+    #[test]
+    fn supersampling_blends_colors_along_edges() {
+        let (w, c) = edge_of_a_triangle();
+
+        let image = c.with_samples_per_pixel(64).render(&w);
+        let edge = image.pixel_at(1, 1).red;
+
+        assert!(edge > 0.3 && edge < 0.7);
+        assert_fuzzy_eq!(image.pixel_at(2, 1), Color::white());
+        assert_fuzzy_eq!(image.pixel_at(0, 1), Color::black());
+    }
+
+    #[test]
+    fn wider_filters_blend_in_neighbouring_pixels() {
+        let (w, c) = edge_of_a_triangle();
+
+        let image = c
+            .with_samples_per_pixel(64)
+            .with_filter(Filter::Tent { radius: 1.5 })
+            .render(&w);
+
+        assert!(image.pixel_at(0, 1).red > 0.0);
+        assert!(image.pixel_at(2, 1).red < 1.0);
+    }
+
+    // Colors seen through the corners of the pixel, as `AdaptiveSampling::render` traces them
+    fn traced_corners<C>(c: &Camera, x: usize, y: usize, color_at: &C) -> [Color; 4]
+    where
+        C: Fn(Ray) -> Color,
+    {
+        let (x, y) = (x as F, y as F);
+
+        [(x, y), (x + 1.0, y), (x, y + 1.0), (x + 1.0, y + 1.0)]
+            .map(|(x, y)| trace_through(c, x, y, color_at))
+    }
+
+    #[test]
+    fn adaptive_sampling_shares_the_corners_of_flat_pixels() {
+        let c = Camera::new(3, 3, PI / 2.0);
+        let adaptive_sampling = AdaptiveSampling {
+            threshold: 0.1,
+            max_depth: 3,
+        };
+        let rays = AtomicUsize::new(0);
+
+        let image = adaptive_sampling.render(&c, &|_| {
+            rays.fetch_add(1, Ordering::Relaxed);
+            Color::new(0.2, 0.4, 0.6)
+        });
+
+        // The 4 x 4 corners of the pixels, and the center of every pixel
+        assert_eq!(rays.into_inner(), 16 + 9);
+        assert_fuzzy_eq!(image.pixel_at(1, 1), Color::new(0.2, 0.4, 0.6));
+    }
+
+    #[test]
+    fn adaptive_sampling_refines_pixels_along_edges() {
+        let (w, c) = edge_of_a_triangle();
+        let c = c.with_adaptive_sampling(0.1, 3);
+        let adaptive_sampling = c.adaptive_sampling.unwrap();
+        let corners = traced_corners(&c, 1, 1, &|ray| w.color_at(ray));
+        let rays = Cell::new(0);
+
+        let edge = adaptive_sampling.pixel_color(&c, 1, 1, corners, &|ray| {
+            rays.set(rays.get() + 1);
+            w.color_at(ray)
+        });
+
+        assert!(rays.get() > 1);
+        assert!(edge.red > 0.3 && edge.red < 0.7);
+
+        let image = c.render(&w);
+        assert_fuzzy_eq!(image.pixel_at(1, 1), edge);
+    }
+
+    #[test]
+    fn adaptive_sampling_stops_at_the_maximum_depth() {
+        let (w, c) = edge_of_a_triangle();
+        let adaptive_sampling = AdaptiveSampling {
+            threshold: 0.1,
+            max_depth: 0,
+        };
+        let corners = traced_corners(&c, 1, 1, &|ray| w.color_at(ray));
+        let rays = Cell::new(0);
+
+        adaptive_sampling.pixel_color(&c, 1, 1, corners, &|ray| {
+            rays.set(rays.get() + 1);
+            w.color_at(ray)
+        });
+
+        // Just the center, the corners are known already
+        assert_eq!(rays.get(), 1);
+    }
+
+    #[test]
+    fn contrast_is_the_largest_difference_in_any_channel() {
+        let colors = [Color::new(0.1, 0.5, 0.0), Color::new(0.3, 0.2, 0.0)];
+
+        assert_fuzzy_eq!(contrast(&colors), 0.3);
+    }
+
+    #[test]
+    fn a_pinhole_camera_looks_through_the_center_of_the_lens() {
+        let c = Camera::new(201, 101, PI / 2.0)
+            .with_transform(Matrix::rotation_y(PI / 4.0) * Matrix::translation(0.0, -2.0, 5.0))
+            .with_depth_of_field(Aperture::Pinhole, 3.0);
+        let r = c
+            .ray_through_lens(20.5, 30.5, c.aperture.sample(0.2, 0.9))
+            .unwrap();
+        let expected = c.ray_through(20.5, 30.5).unwrap();
+
+        assert_fuzzy_eq!(r.origin, expected.origin);
+        assert_fuzzy_eq!(r.direction, expected.direction);
+    }
+
+    #[test]
+    fn rays_through_the_lens_meet_on_the_focal_plane() {
+        let c = Camera::new(201, 101, PI / 2.0)
+            .with_transform(Matrix::translation(0.0, -2.0, 5.0))
+            .with_depth_of_field(Aperture::Disk { radius: 0.5 }, 4.0);
+        let center = c.ray_through(20.5, 30.5).unwrap();
+        // The focal plane lies 4 units in front of the camera, at z = -9
+        let focus = center.position(4.0 / -center.direction.z);
+
+        for lens in [(0.5, 0.0), (-0.3, 0.2), (0.0, -0.5)] {
+            let r = c.ray_through_lens(20.5, 30.5, lens).unwrap();
+
+            assert_fuzzy_eq!(r.origin, Tuple::point(lens.0, lens.1 + 2.0, -5.0));
+            assert_fuzzy_eq!(r.position((focus.z - r.origin.z) / r.direction.z), focus);
+        }
+    }
+
+    #[test]
+    fn positions_within_the_pixel_and_on_the_lens_are_uncorrelated() {
+        let c =
+            Camera::new(11, 11, PI / 2.0).with_depth_of_field(Aperture::Disk { radius: 1.0 }, 1.0);
+        let mut rng = Rng::new(17);
+        let mut pairs = vec![];
+
+        for _ in 0..16 {
+            c.for_each_sample(5, 5, 64, &mut rng, |ray, _, (dx, _), _| {
+                // The camera sits at the origin, rays leave from their point on the lens
+                pairs.push((dx, ray.unwrap().origin.x));
+            });
+        }
+
+        let n = pairs.len() as F;
+        let (mean_x, mean_y) = pairs
+            .iter()
+            .fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x / n, sy + y / n));
+        let (covariance, variance_x, variance_y) =
+            pairs.iter().fold((0.0, 0.0, 0.0), |(c, vx, vy), (x, y)| {
+                let (x, y) = (x - mean_x, y - mean_y);
+                (c + x * y, vx + x * x, vy + y * y)
+            });
+        let correlation = covariance / (variance_x * variance_y).sqrt();
+
+        assert!(correlation.abs() < 0.1);
+    }
+
+    #[test]
+    fn a_wide_aperture_blurs_what_is_out_of_focus() {
+        let (w, c) = edge_of_a_triangle();
+        let aperture = Aperture::polygon(1.5, 6, 0.0);
+
+        let sharp = c
+            .with_samples_per_pixel(64)
+            .with_depth_of_field(aperture, 1.0)
+            .render(&w);
+        let blurred = c
+            .with_samples_per_pixel(64)
+            .with_depth_of_field(aperture, 3.0)
+            .render(&w);
+
+        assert_fuzzy_eq!(sharp.pixel_at(2, 1), Color::white());
+        assert!(blurred.pixel_at(2, 1).red < 0.95);
+        assert!(blurred.pixel_at(0, 1).red > 0.05);
+    }
+
+    #[test]
+    fn rays_are_taken_when_the_shutter_opens() {
+        let c = Camera::new(201, 101, PI / 2.0).with_shutter(0.25, 0.75);
+
+        assert_fuzzy_eq!(c.ray_for_pixel(100, 50).unwrap().time, 0.25);
+    }
+
+    #[test]
+    fn moving_bodies_are_blurred() {
+        let glowing = Material::from(Phong {
+            emissive: Color::white(),
+            ..Phong::default()
+        });
+        // Moves from the center of the outer column of pixels on one side to the other side
+        let sphere = Sphere::with_material(glowing, Some(Matrix::translation(-3.3, 0.0, 0.0)))
+            .with_motion(Matrix::translation(3.3, 0.0, 0.0));
+        let w = World::new(vec![Body::from(sphere)], vec![]);
+        let c = Camera::new(3, 3, PI / 2.0).with_transform(Matrix::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ));
+
+        let still = c.with_shutter(0.0, 0.0).with_samples_per_pixel(64).render(&w);
+        let blurred = c.with_samples_per_pixel(64).render(&w);
+
+        let (left, right) = (still.pixel_at(0, 1).red, still.pixel_at(2, 1).red);
+        assert!(left.max(right) > 0.0 && left.min(right) == 0.0);
+        assert!(blurred.pixel_at(0, 1).red > 0.0 && blurred.pixel_at(0, 1).red < 1.0);
+        assert!(blurred.pixel_at(2, 1).red > 0.0 && blurred.pixel_at(2, 1).red < 1.0);
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let c = Camera::new(200, 100, PI / 2.0)
+            .with_projection(Projection::Orthographic { size: 10.0 });
+
+        let corner = c.ray_for_pixel(0, 0).unwrap();
+        let center = c.ray_through(100.0, 50.0).unwrap();
+
+        assert_fuzzy_eq!(corner.origin, Tuple::point(4.975, 2.475, 0.0));
+        assert_fuzzy_eq!(corner.direction, Tuple::vector(0.0, 0.0, -1.0));
+        assert_fuzzy_eq!(center.origin, Tuple::point(0.0, 0.0, 0.0));
+        assert_fuzzy_eq!(center.direction, Tuple::vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn orthographic_rays_follow_the_camera_transform() {
+        let c = Camera::new(200, 100, PI / 2.0)
+            .with_projection(Projection::Orthographic { size: 10.0 })
+            .with_transform(Matrix::view_transform(
+                Tuple::point(0.0, 10.0, 0.0),
+                Tuple::point(0.0, 0.0, 0.0),
+                Tuple::vector(0.0, 0.0, 1.0),
+            ));
+
+        let r = c.ray_through(100.0, 50.0).unwrap();
+
+        assert_fuzzy_eq!(r.origin, Tuple::point(0.0, 10.0, 0.0));
+        assert_fuzzy_eq!(r.direction, Tuple::vector(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn fisheye_angles_grow_with_the_distance_from_the_center() {
+        let c = Camera::new(100, 100, PI).with_projection(Projection::Fisheye);
+
+        assert_fuzzy_eq!(
+            c.ray_through(50.0, 50.0).unwrap().direction,
+            Tuple::vector(0.0, 0.0, -1.0)
+        );
+        // Half way to the left edge is a quarter of the field of view to the left
+        assert_fuzzy_eq!(
+            c.ray_through(25.0, 50.0).unwrap().direction,
+            Tuple::vector(FRAC_1_SQRT_2, 0.0, -FRAC_1_SQRT_2)
+        );
+        assert_fuzzy_eq!(
+            c.ray_through(50.0, 0.0).unwrap().direction,
+            Tuple::vector(0.0, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn a_fisheye_may_look_behind_the_camera() {
+        let c = Camera::new(100, 100, 2.0 * PI).with_projection(Projection::Fisheye);
+
+        assert_fuzzy_eq!(
+            c.ray_through(0.0, 50.0).unwrap().direction,
+            Tuple::vector(0.0, 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn the_corners_of_a_fisheye_image_stay_black() {
+        let w = World::default().with_background(Background::from(Color::white()));
+        let c = Camera::new(5, 5, PI).with_projection(Projection::Fisheye);
+
+        assert!(c.ray_through_lens(0.0, 0.0, (0.0, 0.0)).is_none());
+        assert!(c.ray_for_pixel(0, 0).is_none());
+
+        let image = c.render(&w);
+        assert_fuzzy_eq!(image.pixel_at(0, 0), Color::black());
+        assert_fuzzy_eq!(image.pixel_at(2, 2), Color::white());
+    }
+
+    #[test]
+    fn an_equirectangular_camera_sees_all_around() {
+        let c = Camera::new(200, 100, PI / 2.0).with_projection(Projection::Equirectangular);
+
+        assert_fuzzy_eq!(
+            c.ray_through(100.0, 50.0).unwrap().direction,
+            Tuple::vector(0.0, 0.0, -1.0)
+        );
+        assert_fuzzy_eq!(
+            c.ray_through(50.0, 50.0).unwrap().direction,
+            Tuple::vector(1.0, 0.0, 0.0)
+        );
+        assert_fuzzy_eq!(
+            c.ray_through(150.0, 50.0).unwrap().direction,
+            Tuple::vector(-1.0, 0.0, 0.0)
+        );
+        assert_fuzzy_eq!(
+            c.ray_through(0.0, 50.0).unwrap().direction,
+            Tuple::vector(0.0, 0.0, 1.0)
+        );
+        assert_fuzzy_eq!(
+            c.ray_through(100.0, 0.0).unwrap().direction,
+            Tuple::vector(0.0, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn left_is_left_in_every_projection() {
+        let perspective = Camera::new(200, 100, PI / 2.0);
+
+        for projection in [
+            Projection::Orthographic { size: 2.0 },
+            Projection::Fisheye,
+            Projection::Equirectangular,
+        ] {
+            let c = perspective.with_projection(projection);
+            let left = c.ray_through(60.0, 50.0).unwrap();
+            let right = c.ray_through(140.0, 50.0).unwrap();
+
+            assert!(left.origin.x + left.direction.x > right.origin.x + right.direction.x);
+        }
+    }
+}
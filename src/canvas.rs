@@ -3,6 +3,7 @@ pub mod to_ppm;
 pub mod to_rgba32;
 
 use crate::F;
+use std::fmt;
 use std::ops::{Add, Mul, Sub};
 use std::vec::Vec;
 
@@ -40,6 +41,11 @@ impl Color {
       self.blue.min(upper_bound).max(lower_bound),
     )
   }
+
+  /// Perceived brightness, weighting the channels like the sRGB / Rec. 709 primaries
+  pub fn luminance(&self) -> F {
+    0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+  }
 }
 
 impl Add for Color {
@@ -101,6 +107,16 @@ pub struct Canvas {
   pixels: Vec<Color>,
 }
 
+impl fmt::Debug for Canvas {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    // Like for textures, the pixels would drown out everything else
+    f.debug_struct("Canvas")
+      .field("width", &self.width)
+      .field("height", &self.height)
+      .finish()
+  }
+}
+
 impl Sized for Canvas {
   fn width(&self) -> usize {
     self.width
@@ -191,6 +207,12 @@ mod tests {
     assert_fuzzy_eq!(actual_result, expected_result);
   }
 
+  #[test]
+  fn luminance_of_colors() {
+    assert_fuzzy_eq!(Color::white().luminance(), 1.0);
+    assert_fuzzy_eq!(Color::new(0.0, 1.0, 0.0).luminance(), 0.7152);
+  }
+
   #[test]
   fn clamping_colors() {
     let c = Color::new(2.3, -6.7, 0.8);
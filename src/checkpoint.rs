@@ -0,0 +1,458 @@
+//! Snapshots of progressive renders, saved now and then while rendering, so a render which
+//! got killed can pick up where it stopped instead of starting over.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::{read, rename, write};
+use std::io;
+use std::path::Path;
+
+use crate::camera::Camera;
+use crate::canvas::Color;
+use crate::progressive::{Accumulator, ProgressiveRenderer};
+use crate::tile::{TileOrder, TileRenderer};
+use crate::F;
+
+const MAGIC: &[u8; 4] = b"RTCK";
+const VERSION: u32 = 1;
+/// Red, green, blue and weight as f64, and the number of samples as u64
+const PIXEL_BYTES: usize = 40;
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    /// The checkpoint could not be read or written
+    Io(io::Error),
+    /// The data does not start with the magic of a checkpoint
+    NotACheckpoint,
+    /// The checkpoint was written by another version of the format
+    UnsupportedVersion(u32),
+    /// The data ends before the whole checkpoint was read
+    UnexpectedEnd,
+    /// There is more data after the end of the checkpoint
+    TrailingData,
+    /// The checkpoint belongs to a render with other settings, named by the string
+    Mismatch(String),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckpointError::Io(error) => write!(f, "{error}"),
+            CheckpointError::NotACheckpoint => write!(f, "not a render checkpoint"),
+            CheckpointError::UnsupportedVersion(version) => {
+                write!(f, "unsupported checkpoint version {version}")
+            }
+            CheckpointError::UnexpectedEnd => write!(f, "unexpected end of data"),
+            CheckpointError::TrailingData => write!(f, "trailing data after the checkpoint"),
+            CheckpointError::Mismatch(setting) => {
+                write!(f, "checkpoint was saved by a render with another {setting}")
+            }
+        }
+    }
+}
+
+impl Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(error: io::Error) -> Self {
+        CheckpointError::Io(error)
+    }
+}
+
+/// Everything needed to carry on with a progressive render: the samples taken so far, the
+/// pass being rendered and which of its tiles are done.
+///
+/// The random numbers of a pixel only depend on the seed and the pass, so those two restore
+/// the random number generators just as they were.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    pub seed: u64,
+    pub samples_per_pass: usize,
+    pub tiles: TileRenderer,
+    /// The pass being rendered
+    pub pass: usize,
+    /// Which tiles of the pass are done, in the order the tile renderer lists them
+    pub completed_tiles: Vec<bool>,
+    pub accumulator: Accumulator,
+}
+
+impl Checkpoint {
+    /// Checkpoint of a render which has not started yet.
+    pub fn new(renderer: &ProgressiveRenderer, camera: &Camera) -> Self {
+        Checkpoint {
+            seed: renderer.seed,
+            samples_per_pass: renderer.samples_per_pass,
+            tiles: camera.tiles,
+            pass: 0,
+            completed_tiles: vec![false; camera.tiles.tiles(camera.hsize, camera.vsize).len()],
+            accumulator: Accumulator::new(camera.hsize, camera.vsize),
+        }
+    }
+
+    /// Whether the checkpoint can be resumed by the renderer and camera. The number of passes
+    /// may differ, to refine a finished render further or to stop earlier.
+    pub fn check_matches(
+        &self,
+        renderer: &ProgressiveRenderer,
+        camera: &Camera,
+    ) -> Result<(), CheckpointError> {
+        let mismatch = |setting: &str| Err(CheckpointError::Mismatch(setting.to_string()));
+
+        if (self.accumulator.width, self.accumulator.height) != (camera.hsize, camera.vsize) {
+            return mismatch("image size");
+        }
+        if self.tiles != camera.tiles {
+            return mismatch("tile size or order");
+        }
+        // Only a broken checkpoint gets here, the tiles follow from the size and the renderer
+        if self.completed_tiles.len() != camera.tiles.tiles(camera.hsize, camera.vsize).len() {
+            return mismatch("tile count");
+        }
+        if self.seed != renderer.seed {
+            return mismatch("seed");
+        }
+        if self.samples_per_pass != renderer.samples_per_pass {
+            return mismatch("number of samples per pass");
+        }
+
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, CheckpointError> {
+        Self::decode(&read(path)?)
+    }
+
+    /// Writes the checkpoint next to `path` first and moves it there afterwards, so a crash
+    /// while saving leaves the previous checkpoint in place.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+
+        write(&temporary, self.encode())?;
+        rename(&temporary, path)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.extend(VERSION.to_le_bytes());
+
+        let accumulator = &self.accumulator;
+        for value in [
+            accumulator.width,
+            accumulator.height,
+            self.samples_per_pass,
+            self.tiles.tile_size,
+        ] {
+            data.extend((value as u64).to_le_bytes());
+        }
+        data.extend(self.seed.to_le_bytes());
+        data.push(match self.tiles.order {
+            TileOrder::Scanline => 0,
+            TileOrder::Spiral => 1,
+            TileOrder::Hilbert => 2,
+        });
+
+        data.extend((self.pass as u64).to_le_bytes());
+        data.extend((self.completed_tiles.len() as u64).to_le_bytes());
+        data.extend(
+            self.completed_tiles
+                .iter()
+                .map(|&completed| u8::from(completed)),
+        );
+
+        for index in 0..accumulator.sums.len() {
+            let sum = accumulator.sums[index];
+            for value in [sum.red, sum.green, sum.blue, accumulator.weights[index]] {
+                data.extend(value.to_le_bytes());
+            }
+            data.extend((accumulator.samples[index] as u64).to_le_bytes());
+        }
+
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, CheckpointError> {
+        let mut reader = Reader { data, position: 0 };
+
+        if &reader.bytes::<4>()? != MAGIC {
+            return Err(CheckpointError::NotACheckpoint);
+        }
+        let version = u32::from_le_bytes(reader.bytes()?);
+        if version != VERSION {
+            return Err(CheckpointError::UnsupportedVersion(version));
+        }
+
+        let width = reader.usize()?;
+        let height = reader.usize()?;
+        let samples_per_pass = reader.usize()?;
+        let tile_size = reader.usize()?;
+        let seed = reader.u64()?;
+        let order = match reader.bytes::<1>()? {
+            [0] => TileOrder::Scanline,
+            [1] => TileOrder::Spiral,
+            [2] => TileOrder::Hilbert,
+            _ => return Err(CheckpointError::NotACheckpoint),
+        };
+
+        // The sizes in the header are checked against the data before allocating anything, a
+        // broken header could ask for any amount of memory
+        let pass = reader.usize()?;
+        let tile_count = reader.usize()?;
+        if tile_count > reader.remaining() {
+            return Err(CheckpointError::UnexpectedEnd);
+        }
+        let completed_tiles = (0..tile_count)
+            .map(|_| Ok(reader.bytes::<1>()? != [0]))
+            .collect::<Result<Vec<bool>, CheckpointError>>()?;
+
+        let pixel_bytes = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(PIXEL_BYTES))
+            .ok_or(CheckpointError::UnexpectedEnd)?;
+        if pixel_bytes > reader.remaining() {
+            return Err(CheckpointError::UnexpectedEnd);
+        }
+        if pixel_bytes < reader.remaining() {
+            return Err(CheckpointError::TrailingData);
+        }
+
+        let mut accumulator = Accumulator::new(width, height);
+        for index in 0..width * height {
+            let (red, green, blue) = (reader.f64()?, reader.f64()?, reader.f64()?);
+            accumulator.sums[index] = Color::new(red, green, blue);
+            accumulator.weights[index] = reader.f64()?;
+            accumulator.samples[index] = reader.usize()?;
+        }
+
+        Ok(Checkpoint {
+            seed,
+            samples_per_pass,
+            tiles: TileRenderer::new(tile_size).with_order(order),
+            pass,
+            completed_tiles,
+            accumulator,
+        })
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], CheckpointError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + N)
+            .ok_or(CheckpointError::UnexpectedEnd)?;
+        self.position += N;
+
+        Ok(bytes.try_into().expect("The slice has N bytes"))
+    }
+
+    fn u64(&mut self) -> Result<u64, CheckpointError> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn usize(&mut self) -> Result<usize, CheckpointError> {
+        Ok(self.u64()? as usize)
+    }
+
+    fn f64(&mut self) -> Result<F, CheckpointError> {
+        Ok(F::from_le_bytes(self.bytes()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use crate::material::{Material, Phong};
+    use crate::matrix::Matrix;
+    use crate::random::Rng;
+    use crate::ray::Ray;
+    use crate::sphere::Sphere;
+    use crate::tuple::Tuple;
+    use crate::world::World;
+    use std::f64::consts::PI;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn scene() -> (World, Camera) {
+        let sphere = Body::from(Sphere::with_material(
+            Material::from(Phong {
+                emissive: Color::new(1.0, 0.5, 0.25),
+                ..Phong::default()
+            }),
+            None,
+        ));
+        let w = World::new(vec![sphere], vec![]);
+        let c = Camera::new(12, 8, PI / 3.0)
+            .with_tiles(TileRenderer::new(4).with_order(TileOrder::Hilbert))
+            .with_transform(Matrix::view_transform(
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::point(0.0, 0.0, 0.0),
+                Tuple::vector(0.0, 1.0, 0.0),
+            ));
+
+        (w, c)
+    }
+
+    fn checkpoint_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("{name}_{}.checkpoint", std::process::id()))
+    }
+
+    #[test]
+    fn checkpoints_survive_a_round_trip() {
+        let (w, c) = scene();
+        let renderer = ProgressiveRenderer::new(2, 3);
+        let mut checkpoint = Checkpoint::new(&renderer, &c);
+        renderer.render_pass(
+            &c,
+            0,
+            &mut checkpoint.accumulator,
+            &|ray: Ray, _: &mut Rng| w.color_at(ray),
+        );
+        checkpoint.pass = 1;
+        checkpoint.completed_tiles[2] = true;
+
+        assert_eq!(
+            Checkpoint::decode(&checkpoint.encode()).unwrap(),
+            checkpoint
+        );
+    }
+
+    #[test]
+    fn broken_checkpoints_are_rejected() {
+        let (_, c) = scene();
+        let data = Checkpoint::new(&ProgressiveRenderer::default(), &c).encode();
+
+        assert!(matches!(
+            Checkpoint::decode(b"P6\n12 8\n"),
+            Err(CheckpointError::NotACheckpoint)
+        ));
+        assert!(matches!(
+            Checkpoint::decode(&data[..data.len() - 1]),
+            Err(CheckpointError::UnexpectedEnd)
+        ));
+        assert!(matches!(
+            Checkpoint::decode(&[data.as_slice(), &[0]].concat()),
+            Err(CheckpointError::TrailingData)
+        ));
+    }
+
+    #[test]
+    fn huge_sizes_in_the_header_are_rejected_before_allocating() {
+        let (_, c) = scene();
+        let data = Checkpoint::new(&ProgressiveRenderer::default(), &c).encode();
+        // The width follows the magic and the version
+        let with_width = |width: u64| {
+            let mut data = data.clone();
+            data[8..16].copy_from_slice(&width.to_le_bytes());
+            data
+        };
+
+        assert!(matches!(
+            Checkpoint::decode(&with_width(1 << 40)),
+            Err(CheckpointError::UnexpectedEnd)
+        ));
+        assert!(matches!(
+            Checkpoint::decode(&with_width(u64::MAX)),
+            Err(CheckpointError::UnexpectedEnd)
+        ));
+    }
+
+    #[test]
+    fn checkpoints_only_resume_the_same_render() {
+        let (_, c) = scene();
+        let renderer = ProgressiveRenderer::new(4, 2);
+        let checkpoint = Checkpoint::new(&renderer, &c);
+
+        assert!(checkpoint
+            .check_matches(&ProgressiveRenderer::new(8, 2), &c)
+            .is_ok());
+        assert!(matches!(
+            checkpoint.check_matches(&ProgressiveRenderer::new(4, 1), &c),
+            Err(CheckpointError::Mismatch(_))
+        ));
+        assert!(matches!(
+            checkpoint.check_matches(&renderer, &Camera::new(8, 12, PI / 3.0)),
+            Err(CheckpointError::Mismatch(_))
+        ));
+    }
+
+    #[test]
+    fn checkpoints_with_the_wrong_number_of_tiles_are_rejected() {
+        let (w, c) = scene();
+        let renderer = ProgressiveRenderer::new(2, 1);
+        let path = checkpoint_path("wrong_tile_count");
+        let mut checkpoint = Checkpoint::new(&renderer, &c);
+        checkpoint.completed_tiles.pop();
+        checkpoint.save(&path).unwrap();
+
+        let result = renderer.render_with_checkpoints(
+            &c,
+            |ray, _| w.color_at(ray),
+            &path,
+            Duration::ZERO,
+            |_, _| {},
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(CheckpointError::Mismatch(_))));
+    }
+
+    #[test]
+    fn an_interrupted_render_resumes_where_it_stopped() {
+        let (w, c) = scene();
+        let renderer = ProgressiveRenderer::new(3, 2);
+        let path = checkpoint_path("interrupted_render");
+
+        // Dies in the middle of the second pass, after saving after every batch of tiles
+        let traced = AtomicUsize::new(0);
+        let crashed = catch_unwind(AssertUnwindSafe(|| {
+            renderer.render_with_checkpoints(
+                &c,
+                |ray, _| {
+                    if traced.fetch_add(1, Ordering::Relaxed) == 12 * 8 * 2 + 50 {
+                        panic!("Killed");
+                    }
+                    w.color_at(ray)
+                },
+                &path,
+                Duration::ZERO,
+                |_, _| {},
+            )
+        }));
+        assert!(crashed.is_err());
+
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        assert!(checkpoint.pass >= 1);
+
+        let mut passes = vec![];
+        let resumed = renderer
+            .render_with_checkpoints(
+                &c,
+                |ray, _| w.color_at(ray),
+                &path,
+                Duration::ZERO,
+                |pass, _| passes.push(pass),
+            )
+            .unwrap();
+        let uninterrupted = renderer.render(&c, |ray, _| w.color_at(ray), |_, _| {});
+
+        assert_eq!(passes[0], checkpoint.pass);
+        assert!(!path.exists());
+        for y in 0..8 {
+            for x in 0..12 {
+                assert_eq!(resumed.pixel_at(x, y), uninterrupted.pixel_at(x, y));
+            }
+        }
+    }
+}
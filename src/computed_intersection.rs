@@ -0,0 +1,366 @@
+use crate::body::*;
+use crate::intersections::*;
+use crate::ray::Ray;
+use crate::tuple::Tuple;
+use crate::{EPSILON, F};
+
+/// Everything needed to shade an intersection, precomputed once per hit.
+#[derive(Debug, Clone)]
+pub struct ComputedIntersection {
+    pub t: F,
+    pub body: Body,
+    pub position: Tuple,
+    pub eye_vec: Tuple,
+    pub normal_vec: Tuple,
+    pub inside: bool,
+    /// The hit position moved slightly above the surface, so that rays spawned from it
+    /// do not intersect the body they are leaving again due to floating point inaccuracies.
+    pub over_point: Tuple,
+    /// The hit position moved slightly below the surface, where refracted rays start.
+    pub under_point: Tuple,
+    pub reflect_vec: Tuple,
+    /// Refractive index of the material the ray is leaving.
+    pub n1: F,
+    /// Refractive index of the material the ray is entering.
+    pub n2: F,
+    /// Time of the ray, which rays spawned from the hit inherit.
+    pub time: F,
+}
+
+impl Intersection {
+    /// Prepares the shading of this intersection. All intersections of the ray are needed
+    /// to find out which materials the ray is passing from and into.
+    pub fn get_computed(&self, ray: Ray, intersections: &Intersections) -> ComputedIntersection {
+        let position = ray.position(self.t);
+        let eye_vec = -ray.direction;
+        let mut normal_vec = self.body.normal_vector(position);
+
+        // Which side the eye is on is up to the geometry, a tilted normal could claim otherwise
+        let inside = normal_vec.dot(eye_vec) < 0.0;
+        if inside {
+            normal_vec = -normal_vec;
+        }
+
+        if let Some(normal_perturbation) = self.body.material().normal_perturbation() {
+            normal_vec = normal_perturbation.perturb(&self.body, position, normal_vec);
+        }
+
+        let over_point = position + normal_vec * EPSILON;
+        let under_point = position - normal_vec * EPSILON;
+        let reflect_vec = ray.direction.reflect(normal_vec);
+        let (n1, n2) = self.refractive_indices(intersections);
+
+        ComputedIntersection {
+            t: self.t,
+            body: self.body.clone(),
+            position,
+            eye_vec,
+            normal_vec,
+            inside,
+            over_point,
+            under_point,
+            reflect_vec,
+            n1,
+            n2,
+            time: ray.time,
+        }
+    }
+
+    fn refractive_indices(&self, intersections: &Intersections) -> (F, F) {
+        // Bodies the ray is currently inside of, the innermost one being the last.
+        let mut containers: Vec<&Intersection> = vec![];
+        let refractive_index = |containers: &Vec<&Intersection>| match containers.last() {
+            Some(container) => container.body.material().refractive_index(),
+            None => 1.0,
+        };
+
+        for intersection in intersections.data.iter() {
+            let is_hit = intersection == self;
+            let n1 = refractive_index(&containers);
+
+            // Two equal bodies of a world are still two containers, one inside of the other
+            match containers
+                .iter()
+                .position(|container| container.is_same_body(intersection))
+            {
+                Some(index) => {
+                    containers.remove(index);
+                }
+                None => containers.push(intersection),
+            }
+
+            if is_hit {
+                return (n1, refractive_index(&containers));
+            }
+        }
+
+        (1.0, 1.0)
+    }
+}
+
+impl ComputedIntersection {
+    /// Fraction of the light which is reflected at the hit, instead of being refracted,
+    /// using Schlick's approximation of the Fresnel equations.
+    pub fn schlick(&self) -> F {
+        let mut cos = self.eye_vec.dot(self.normal_vec);
+
+        if self.n1 > self.n2 {
+            let n_ratio = self.n1 / self.n2;
+            let sin2_t = n_ratio.powi(2) * (1.0 - cos.powi(2));
+
+            if sin2_t > 1.0 {
+                // Total internal reflection
+                return 1.0;
+            }
+
+            cos = (1.0 - sin2_t).sqrt();
+        }
+
+        let r0 = ((self.n1 - self.n2) / (self.n1 + self.n2)).powi(2);
+
+        r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzy_eq::*;
+    use crate::material::*;
+    use crate::normal_perturbation::NormalPerturbation;
+    use crate::matrix::Matrix;
+    use crate::sphere::Sphere;
+    use crate::triangle::Triangle;
+
+    #[test]
+    fn precomputing_the_state_of_an_intersection() {
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let body = Body::from(Sphere::new(None));
+        let i = Intersection::new(4.0, body);
+
+        let xs = Intersections::from(vec![i.clone()]);
+        let comps = i.get_computed(r, &xs);
+
+        assert_fuzzy_eq!(comps.t, i.t);
+        assert_eq!(comps.body, i.body);
+        assert_fuzzy_eq!(comps.position, Tuple::point(0.0, 0.0, -1.0));
+        assert_fuzzy_eq!(comps.eye_vec, Tuple::vector(0.0, 0.0, -1.0));
+        assert_fuzzy_eq!(comps.normal_vec, Tuple::vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn the_hit_when_an_intersection_occurs_on_the_outside() {
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let i = Intersection::new(4.0, Body::from(Sphere::new(None)));
+
+        let xs = Intersections::from(vec![i.clone()]);
+        let comps = i.get_computed(r, &xs);
+
+        assert!(!comps.inside);
+    }
+
+    #[test]
+    fn the_hit_when_an_intersection_occurs_on_the_inside() {
+        let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let i = Intersection::new(1.0, Body::from(Sphere::new(None)));
+
+        let xs = Intersections::from(vec![i.clone()]);
+        let comps = i.get_computed(r, &xs);
+
+        assert_fuzzy_eq!(comps.position, Tuple::point(0.0, 0.0, 1.0));
+        assert_fuzzy_eq!(comps.eye_vec, Tuple::vector(0.0, 0.0, -1.0));
+        assert!(comps.inside);
+        // normal would have been (0, 0, 1), but is inverted!
+        assert_fuzzy_eq!(comps.normal_vec, Tuple::vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn the_hit_should_offset_the_point() {
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let body = Body::from(Sphere::new(Some(Matrix::translation(0.0, 0.0, 1.0))));
+        let i = Intersection::new(5.0, body);
+
+        let xs = Intersections::from(vec![i.clone()]);
+        let comps = i.get_computed(r, &xs);
+
+        assert!(comps.over_point.z < -EPSILON / 2.0);
+        assert!(comps.position.z > comps.over_point.z);
+    }
+
+    #[test]
+    fn precomputing_the_reflection_vector() {
+        let sqrt2_over_2 = (2.0 as F).sqrt() / 2.0;
+        let r = Ray::new(
+            Tuple::point(0.0, 1.0, -1.0),
+            Tuple::vector(0.0, -sqrt2_over_2, sqrt2_over_2),
+        );
+        let body = Body::from(Triangle::new(
+            Tuple::point(-10.0, 0.0, -10.0),
+            Tuple::point(0.0, 0.0, 10.0),
+            Tuple::point(10.0, 0.0, -10.0),
+        ));
+        let i = Intersection::new((2.0 as F).sqrt(), body);
+
+        let xs = Intersections::from(vec![i.clone()]);
+        let comps = i.get_computed(r, &xs);
+
+        assert_fuzzy_eq!(comps.reflect_vec, Tuple::vector(0.0, sqrt2_over_2, sqrt2_over_2));
+    }
+
+    fn glass_sphere(transform: Option<Matrix<4>>, refractive_index: F) -> Body {
+        let material = Material::from(Phong {
+            transparency: 1.0,
+            refractive_index,
+            ..Phong::default()
+        });
+
+        Body::from(Sphere::with_material(material, transform))
+    }
+
+    #[test]
+    fn finding_n1_and_n2_at_various_intersections() {
+        let a = glass_sphere(Some(Matrix::scaling(2.0, 2.0, 2.0)), 1.5);
+        let b = glass_sphere(Some(Matrix::translation(0.0, 0.0, -0.25)), 2.0);
+        let c = glass_sphere(Some(Matrix::translation(0.0, 0.0, 0.25)), 2.5);
+        let r = Ray::new(Tuple::point(0.0, 0.0, -4.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = Intersections::from(vec![
+            Intersection::new(2.0, a.clone()),
+            Intersection::new(2.75, b.clone()),
+            Intersection::new(3.25, c.clone()),
+            Intersection::new(4.75, b),
+            Intersection::new(5.25, c),
+            Intersection::new(6.0, a),
+        ]);
+
+        let expected_results = [
+            (1.0, 1.5),
+            (1.5, 2.0),
+            (2.0, 2.5),
+            (2.5, 2.5),
+            (2.5, 1.5),
+            (1.5, 1.0),
+        ];
+
+        for (index, (n1, n2)) in expected_results.iter().enumerate() {
+            let comps = xs[index].get_computed(r, &xs);
+
+            assert_fuzzy_eq!(comps.n1, *n1);
+            assert_fuzzy_eq!(comps.n2, *n2);
+        }
+    }
+
+    #[test]
+    fn equal_bodies_of_a_world_are_different_containers() {
+        let glass = glass_sphere(None, 1.5);
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = Intersections::from(vec![
+            Intersection::new(4.0, glass.clone()).with_body_index(0),
+            Intersection::new(4.5, glass.clone()).with_body_index(1),
+            Intersection::new(5.5, glass.clone()).with_body_index(1),
+            Intersection::new(6.0, glass).with_body_index(0),
+        ]);
+
+        let comps = xs[1].get_computed(r, &xs);
+
+        // Entering the second body does not leave the first one
+        assert_fuzzy_eq!(comps.n1, 1.5);
+        assert_fuzzy_eq!(comps.n2, 1.5);
+    }
+
+    #[test]
+    fn the_under_point_is_offset_below_the_surface() {
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let body = glass_sphere(Some(Matrix::translation(0.0, 0.0, 1.0)), 1.5);
+        let i = Intersection::new(5.0, body);
+        let xs = Intersections::from(vec![i.clone()]);
+
+        let comps = i.get_computed(r, &xs);
+
+        assert!(comps.under_point.z > EPSILON / 2.0);
+        assert!(comps.position.z < comps.under_point.z);
+    }
+
+    #[test]
+    fn the_schlick_approximation_under_total_internal_reflection() {
+        let shape = glass_sphere(None, 1.5);
+        let sqrt2_over_2 = (2.0 as F).sqrt() / 2.0;
+        let r = Ray::new(Tuple::point(0.0, 0.0, sqrt2_over_2), Tuple::vector(0.0, 1.0, 0.0));
+        let xs = Intersections::from(vec![
+            Intersection::new(-sqrt2_over_2, shape.clone()),
+            Intersection::new(sqrt2_over_2, shape),
+        ]);
+
+        let comps = xs[1].get_computed(r, &xs);
+
+        assert_fuzzy_eq!(comps.schlick(), 1.0);
+    }
+
+    #[test]
+    fn the_schlick_approximation_with_a_perpendicular_viewing_angle() {
+        let shape = glass_sphere(None, 1.5);
+        let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        let xs = Intersections::from(vec![
+            Intersection::new(-1.0, shape.clone()),
+            Intersection::new(1.0, shape),
+        ]);
+
+        let comps = xs[1].get_computed(r, &xs);
+
+        assert_fuzzy_eq!(comps.schlick(), 0.04);
+    }
+
+    #[test]
+    fn the_schlick_approximation_with_small_angle_and_n2_greater_than_n1() {
+        let shape = glass_sphere(None, 1.5);
+        let r = Ray::new(Tuple::point(0.0, 0.99, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = Intersections::from(vec![Intersection::new(1.8589, shape)]);
+
+        let comps = xs[0].get_computed(r, &xs);
+
+        assert_fuzzy_eq!(comps.schlick(), 0.48873);
+    }
+
+    #[test]
+    fn the_normal_is_perturbed_by_the_material() {
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let perturbation = NormalPerturbation::sine(1.0, 0.5);
+        let material =
+            Material::from(Phong::default().with_normal_perturbation(perturbation.clone()));
+        let body = Body::from(Sphere::with_material(material, None));
+        let i = Intersection::new(4.0, body);
+
+        let xs = Intersections::from(vec![i.clone()]);
+        let comps = i.get_computed(r, &xs);
+
+        let expected_result = perturbation.perturb(
+            &i.body,
+            Tuple::point(0.0, 0.0, -1.0),
+            Tuple::vector(0.0, 0.0, -1.0),
+        );
+
+        assert_fuzzy_ne!(comps.normal_vec, Tuple::vector(0.0, 0.0, -1.0));
+        assert_fuzzy_eq!(comps.normal_vec, expected_result);
+    }
+
+    #[test]
+    fn the_normal_is_flipped_towards_the_eye_before_it_is_perturbed() {
+        let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let perturbation = NormalPerturbation::sine(1.0, 0.5);
+        let material =
+            Material::from(Phong::default().with_normal_perturbation(perturbation.clone()));
+        let body = Body::from(Sphere::with_material(material, None));
+        let i = Intersection::new(1.0, body);
+
+        let xs = Intersections::from(vec![i.clone()]);
+        let comps = i.get_computed(r, &xs);
+
+        let expected_result = perturbation.perturb(
+            &i.body,
+            Tuple::point(0.0, 0.0, 1.0),
+            Tuple::vector(0.0, 0.0, -1.0),
+        );
+
+        assert!(comps.inside);
+        assert_fuzzy_eq!(comps.normal_vec, expected_result);
+    }
+}
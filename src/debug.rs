@@ -0,0 +1,169 @@
+//! Renders which show what the tracer computes rather than what the scene looks like, for
+//! hunting down wrong normals, mangled texture coordinates or costly parts of a scene.
+
+use crate::body::Intersectable;
+use crate::camera::Camera;
+use crate::canvas::{Canvas, Color};
+use crate::world::{intersection_tests, World};
+use crate::F;
+
+/// What a debug render shades the first hit of every pixel by. Lights and materials are
+/// ignored, and rays which miss everything are black.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugMode {
+    /// Normal the body reports for the hit, with x, y and z mapped from `[-1, 1]` to red,
+    /// green and blue. Unlike during shading, normals are not turned towards the eye, so
+    /// normals pointing into a body show up in the complementary color.
+    Normal,
+    /// Texture coordinates u and v as red and green
+    Uv,
+    /// Distance to the hit along the ray, from black right at the camera to white at `max`
+    /// and beyond
+    Distance { max: F },
+    /// Heat map of the bodies tested against any ray needed to shade the pixel, shadow and
+    /// secondary rays included, from blue for the cheapest pixels to red for the costliest
+    IntersectionTests,
+}
+
+impl DebugMode {
+    pub fn render(&self, camera: &Camera, world: &World) -> Canvas {
+        match *self {
+            DebugMode::IntersectionTests => heat_map(camera, world),
+            mode => camera.render_samples(1, 0, |ray, _| {
+                let intersections = world.intersect(ray);
+                let hit = match intersections.hit() {
+                    Some(hit) => hit,
+                    None => return Color::black(),
+                };
+                let computed = hit.get_computed(ray, &intersections);
+
+                match mode {
+                    DebugMode::Normal => {
+                        let normal = computed.body.normal_vector(computed.position);
+                        Color::new(normal.x + 1.0, normal.y + 1.0, normal.z + 1.0) * 0.5
+                    }
+                    DebugMode::Uv => {
+                        let (u, v) = computed.body.uv(computed.position);
+                        Color::new(u, v, 0.0)
+                    }
+                    DebugMode::Distance { max } => {
+                        let distance = (computed.position - ray.origin).magnitude() / max;
+                        Color::new(distance, distance, distance).clamp(0.0, 1.0)
+                    }
+                    DebugMode::IntersectionTests => unreachable!(),
+                }
+            }),
+        }
+    }
+}
+
+fn heat_map(camera: &Camera, world: &World) -> Canvas {
+    let world = world.counting_intersection_tests();
+    let counts = camera.render_samples(1, 0, |ray, _| {
+        let before = intersection_tests();
+        world.color_at(ray);
+        let tests = (intersection_tests() - before) as F;
+
+        Color::new(tests, tests, tests)
+    });
+
+    let mut most: F = 1.0;
+    for y in 0..counts.height {
+        for x in 0..counts.width {
+            most = most.max(counts.pixel_at(x, y).red);
+        }
+    }
+
+    let mut canvas = Canvas::new(counts.width, counts.height);
+    for y in 0..counts.height {
+        for x in 0..counts.width {
+            canvas.write_pixel(x, y, heat(counts.pixel_at(x, y).red / most));
+        }
+    }
+
+    canvas
+}
+
+/// Color for a value between 0 and 1, running from blue through cyan, green and yellow to
+/// red.
+fn heat(value: F) -> Color {
+    #[rustfmt::skip]
+    const RAMP: [Color; 5] = [
+        Color { red: 0.0, green: 0.0, blue: 1.0 },
+        Color { red: 0.0, green: 1.0, blue: 1.0 },
+        Color { red: 0.0, green: 1.0, blue: 0.0 },
+        Color { red: 1.0, green: 1.0, blue: 0.0 },
+        Color { red: 1.0, green: 0.0, blue: 0.0 },
+    ];
+
+    let position = value.clamp(0.0, 1.0) * (RAMP.len() - 1) as F;
+    let index = (position as usize).min(RAMP.len() - 2);
+    let fraction = position - index as F;
+
+    RAMP[index] * (1.0 - fraction) + RAMP[index + 1] * fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzy_eq::*;
+    use crate::matrix::Matrix;
+    use crate::tuple::Tuple;
+    use crate::world::default_world;
+    use std::f64::consts::PI;
+
+    fn camera_at(from: Tuple) -> Camera {
+        Camera::new(11, 11, PI / 2.0).with_transform(Matrix::view_transform(
+            from,
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ))
+    }
+
+    #[test]
+    fn normals_are_mapped_to_colors() {
+        let canvas =
+            DebugMode::Normal.render(&camera_at(Tuple::point(0.0, 0.0, -5.0)), &default_world());
+
+        assert_fuzzy_eq!(canvas.pixel_at(5, 5), Color::new(0.5, 0.5, 0.0));
+        assert_fuzzy_eq!(canvas.pixel_at(0, 0), Color::black());
+    }
+
+    #[test]
+    fn texture_coordinates_are_mapped_to_red_and_green() {
+        let canvas =
+            DebugMode::Uv.render(&camera_at(Tuple::point(5.0, 0.0, 0.0)), &default_world());
+
+        assert_fuzzy_eq!(canvas.pixel_at(5, 5), Color::new(0.25, 0.5, 0.0));
+    }
+
+    #[test]
+    fn distances_fade_from_black_to_white() {
+        let camera = camera_at(Tuple::point(0.0, 0.0, -5.0));
+
+        let canvas = DebugMode::Distance { max: 8.0 }.render(&camera, &default_world());
+        assert_fuzzy_eq!(canvas.pixel_at(5, 5), Color::new(0.5, 0.5, 0.5));
+
+        let canvas = DebugMode::Distance { max: 2.0 }.render(&camera, &default_world());
+        assert_fuzzy_eq!(canvas.pixel_at(5, 5), Color::white());
+    }
+
+    #[test]
+    fn shadow_rays_make_hits_costlier_than_misses() {
+        let camera = camera_at(Tuple::point(0.0, 0.0, -5.0));
+
+        let canvas = DebugMode::IntersectionTests.render(&camera, &default_world());
+
+        // A miss tests both spheres once, a hit also tests them along the shadow ray
+        assert_fuzzy_eq!(canvas.pixel_at(5, 5), Color::new(1.0, 0.0, 0.0));
+        assert_fuzzy_eq!(canvas.pixel_at(0, 0), Color::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn the_heat_ramp_runs_from_blue_to_red() {
+        assert_fuzzy_eq!(heat(0.0), Color::new(0.0, 0.0, 1.0));
+        assert_fuzzy_eq!(heat(0.125), Color::new(0.0, 0.5, 1.0));
+        assert_fuzzy_eq!(heat(0.75), Color::new(1.0, 1.0, 0.0));
+        assert_fuzzy_eq!(heat(1.0), Color::new(1.0, 0.0, 0.0));
+    }
+}
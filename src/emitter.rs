@@ -0,0 +1,159 @@
+use crate::body::Intersectable;
+use crate::random::Rng;
+use crate::tuple::Tuple;
+use crate::{EPSILON, F};
+
+/// Point picked on a light emitting surface, as seen from the point being lit.
+#[derive(Debug, Clone, Copy)]
+pub struct EmitterSample {
+    pub point: Tuple,
+    /// Unit vector from the lit point towards the sampled point
+    pub direction: Tuple,
+    pub distance: F,
+    /// Probability density per solid angle, as seen from the lit point
+    pub pdf: F,
+}
+
+/// Surface which random points can be picked on, so glowing bodies can be sampled directly
+/// as light sources.
+pub trait Emitter: Intersectable {
+    /// Point on the surface, uniformly distributed by area.
+    fn sample_surface(&self, rng: &mut Rng) -> Tuple;
+
+    /// Probability density per unit area, with which `sample_surface` picks the given point.
+    fn area_pdf(&self, point: Tuple) -> F;
+
+    /// Picks a point on the surface to send a shadow ray from `from` to. Points seen exactly
+    /// edge on can not be reached and give `None`.
+    fn sample_toward(&self, from: Tuple, rng: &mut Rng) -> Option<EmitterSample> {
+        let point = self.sample_surface(rng);
+        let pdf = self.pdf(from, point);
+
+        if pdf == 0.0 {
+            return None;
+        }
+
+        let to_point = point - from;
+        let distance = to_point.magnitude();
+
+        Some(EmitterSample {
+            point,
+            direction: to_point / distance,
+            distance,
+            pdf,
+        })
+    }
+
+    /// Probability density per solid angle, with which `sample_toward` picks the given point
+    /// on the surface, when seen from `from`.
+    fn pdf(&self, from: Tuple, point: Tuple) -> F {
+        let to_point = point - from;
+        let distance_squared = to_point.dot(to_point);
+        let cosine = self.normal_vector(point).dot(to_point.normalize()).abs();
+
+        if cosine < EPSILON || distance_squared < EPSILON {
+            return 0.0;
+        }
+
+        // Patches far away or seen at a grazing angle cover less of the view
+        self.area_pdf(point) * distance_squared / cosine
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::Body;
+    use crate::fuzzy_eq::*;
+    use crate::matrix::Matrix;
+    use crate::sphere::Sphere;
+    use crate::triangle::Triangle;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn sampled_points_lie_on_the_surface_of_a_sphere() {
+        let s = Sphere::new(Some(
+            Matrix::translation(1.0, 2.0, 3.0) * Matrix::scaling(2.0, 2.0, 2.0),
+        ));
+        let mut rng = Rng::new(7);
+
+        for _ in 0..100 {
+            let point = s.sample_surface(&mut rng);
+
+            assert!(point.is_point());
+            assert_fuzzy_eq!((point - Tuple::point(1.0, 2.0, 3.0)).magnitude(), 2.0);
+        }
+    }
+
+    #[test]
+    fn area_pdf_of_a_scaled_sphere() {
+        let s = Sphere::new(Some(Matrix::scaling(2.0, 2.0, 2.0)));
+
+        assert_fuzzy_eq!(s.area_pdf(Tuple::point(0.0, 2.0, 0.0)), 1.0 / (16.0 * PI));
+    }
+
+    #[test]
+    fn area_pdf_of_a_stretched_sphere_varies_over_the_surface() {
+        let s = Sphere::new(Some(Matrix::scaling(1.0, 3.0, 1.0)));
+
+        // Near the poles the surface is barely stretched, at the equator it is 3 times wider
+        assert_fuzzy_eq!(s.area_pdf(Tuple::point(0.0, 3.0, 0.0)), 1.0 / (4.0 * PI));
+        assert_fuzzy_eq!(s.area_pdf(Tuple::point(1.0, 0.0, 0.0)), 1.0 / (12.0 * PI));
+    }
+
+    #[test]
+    fn sampled_points_lie_on_a_triangle() {
+        let t = Triangle::new(
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(-1.0, 0.0, 0.0),
+            Tuple::point(1.0, 0.0, 0.0),
+        );
+        let mut rng = Rng::new(7);
+
+        for _ in 0..100 {
+            let point = t.sample_surface(&mut rng);
+
+            assert_fuzzy_eq!(point.z, 0.0);
+            assert!(point.y >= 0.0 && point.y <= 1.0 - point.x.abs() + EPSILON);
+        }
+
+        assert_fuzzy_eq!(t.area_pdf(Tuple::point(0.0, 0.5, 0.0)), 1.0);
+    }
+
+    #[test]
+    fn solid_angle_pdf_grows_with_the_distance() {
+        let t = Body::from(Triangle::new(
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(-1.0, 0.0, 0.0),
+            Tuple::point(1.0, 0.0, 0.0),
+        ));
+        let point = Tuple::point(0.0, 0.5, 0.0);
+
+        assert_fuzzy_eq!(t.pdf(Tuple::point(0.0, 0.5, -2.0), point), 4.0);
+        assert_fuzzy_eq!(t.pdf(Tuple::point(0.0, 0.5, -4.0), point), 16.0);
+    }
+
+    #[test]
+    fn points_seen_edge_on_can_not_be_sampled() {
+        let t = Body::from(Triangle::new(
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(-1.0, 0.0, 0.0),
+            Tuple::point(1.0, 0.0, 0.0),
+        ));
+
+        assert_fuzzy_eq!(t.pdf(Tuple::point(-5.0, 0.5, 0.0), Tuple::point(0.0, 0.5, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn sampling_toward_a_point() {
+        let s = Sphere::new(Some(Matrix::translation(0.0, 0.0, 5.0)));
+        let from = Tuple::point(0.0, 0.0, 0.0);
+        let mut rng = Rng::new(3);
+
+        let sample = s.sample_toward(from, &mut rng).unwrap();
+
+        assert_fuzzy_eq!(sample.point, from + sample.direction * sample.distance);
+        assert_fuzzy_eq!(sample.direction.magnitude(), 1.0);
+        assert_fuzzy_eq!(sample.pdf, s.pdf(from, sample.point));
+    }
+}
@@ -0,0 +1,323 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::canvas::Color;
+use crate::matrix::Matrix;
+use crate::random::Rng;
+use crate::sampling::Distribution2D;
+use crate::sky::Sky;
+use crate::texture::Texture;
+use crate::tuple::Tuple;
+use crate::F;
+
+/// What is seen in the distance, where rays do not hit any body.
+#[derive(Debug, Clone)]
+pub enum Background {
+    Color(Color),
+    Environment(Box<EnvironmentMap>),
+    Sky(Sky),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Color(Color::black())
+    }
+}
+
+impl From<Color> for Background {
+    fn from(color: Color) -> Self {
+        Background::Color(color)
+    }
+}
+
+impl From<EnvironmentMap> for Background {
+    fn from(environment: EnvironmentMap) -> Self {
+        Background::Environment(Box::new(environment))
+    }
+}
+
+impl From<Sky> for Background {
+    fn from(sky: Sky) -> Self {
+        Background::Sky(sky)
+    }
+}
+
+impl Background {
+    /// Light arriving from far away, travelling against the given direction.
+    pub fn radiance(&self, direction: Tuple) -> Color {
+        match *self {
+            Background::Color(color) => color,
+            Background::Environment(ref environment) => environment.radiance(direction),
+            Background::Sky(ref sky) => sky.radiance(direction),
+        }
+    }
+}
+
+/// Direction picked towards the bright parts of an environment map.
+#[derive(Debug, Clone, Copy)]
+pub struct EnvironmentSample {
+    pub direction: Tuple,
+    pub radiance: Color,
+    /// Probability density per solid angle
+    pub pdf: F,
+}
+
+/// Panorama in equirectangular projection (usually an HDR image), which surrounds the world
+/// and lights it from all directions.
+///
+/// The center of the image lies towards -z, the top edge straight up. The map can be
+/// turned around the y axis, to move the bright parts around.
+#[derive(Debug, Clone)]
+pub struct EnvironmentMap {
+    /// Brightness multiplier for the whole map
+    pub intensity: F,
+    rotation: Matrix<4>,
+    inverse_rotation: Matrix<4>,
+    texture: Arc<Texture>,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    pub fn new(texture: Arc<Texture>) -> Self {
+        // Rows near the poles cover less solid angle, which the sine takes care of
+        let weights: Vec<F> = (0..texture.height)
+            .flat_map(|y| {
+                let sin_theta = (PI * (y as F + 0.5) / texture.height as F).sin();
+                let texture = &texture;
+
+                (0..texture.width).map(move |x| texture.pixel_at(x, y).luminance() * sin_theta)
+            })
+            .collect();
+
+        EnvironmentMap {
+            intensity: 1.0,
+            rotation: Matrix::identity(),
+            inverse_rotation: Matrix::identity(),
+            distribution: Distribution2D::new(texture.width, texture.height, &weights),
+            texture,
+        }
+    }
+
+    pub fn with_intensity(mut self, intensity: F) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Turns the map by the given angle around the y axis.
+    pub fn with_rotation(mut self, angle: F) -> Self {
+        self.rotation = Matrix::rotation_y(angle);
+        self.inverse_rotation = self.rotation.inverse();
+        self
+    }
+
+    pub fn radiance(&self, direction: Tuple) -> Color {
+        let (x, y) = self.direction_to_image(direction);
+
+        // Unfiltered, so the radiance is constant across each pixel, just like the density
+        // of the samples. Filtering would make a bright pixel's edges needlessly noisy.
+        let column = ((x * self.texture.width as F) as usize).min(self.texture.width - 1);
+        let row = ((y * self.texture.height as F) as usize).min(self.texture.height - 1);
+
+        self.texture.pixel_at(column, row) * self.intensity
+    }
+
+    /// Picks a direction towards the environment, brighter parts being more likely.
+    pub fn sample(&self, rng: &mut Rng) -> Option<EnvironmentSample> {
+        let ((x, y), image_pdf) = self.distribution.sample(rng.next_f64(), rng.next_f64());
+        let direction = self.image_to_direction(x, y);
+        let pdf = self.image_pdf_to_solid_angle(image_pdf, y);
+
+        if pdf == 0.0 {
+            return None;
+        }
+
+        Some(EnvironmentSample {
+            direction,
+            radiance: self.radiance(direction),
+            pdf,
+        })
+    }
+
+    /// Probability density per solid angle, with which `sample` picks the given direction.
+    pub fn pdf(&self, direction: Tuple) -> F {
+        let (x, y) = self.direction_to_image(direction);
+
+        self.image_pdf_to_solid_angle(self.distribution.pdf(x, y), y)
+    }
+
+    fn image_pdf_to_solid_angle(&self, image_pdf: F, y: F) -> F {
+        // The image spans 2 PI horizontally and PI vertically, squeezed towards the poles
+        let sin_theta = (PI * y).sin();
+
+        if sin_theta <= 0.0 {
+            0.0
+        } else {
+            image_pdf / (2.0 * PI * PI * sin_theta)
+        }
+    }
+
+    /// Position in the image, measured from its top left corner, both in `[0, 1]`.
+    fn direction_to_image(&self, direction: Tuple) -> (F, F) {
+        let direction = (self.inverse_rotation * direction).normalize();
+
+        let phi = direction.x.atan2(-direction.z);
+        let theta = direction.y.clamp(-1.0, 1.0).acos();
+
+        (0.5 + phi / (2.0 * PI), theta / PI)
+    }
+
+    fn image_to_direction(&self, x: F, y: F) -> Tuple {
+        let phi = (x - 0.5) * 2.0 * PI;
+        let theta = y * PI;
+
+        let direction = Tuple::vector(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        );
+
+        self.rotation * direction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzy_eq::*;
+
+    // Left half red, right half blue, a bright white spot in the upper right
+    fn panorama() -> Arc<Texture> {
+        let pixels = (0..8)
+            .flat_map(|y| {
+                (0..16).map(move |x| match (x, y) {
+                    (12, 2) => Color::new(100.0, 100.0, 100.0),
+                    (0..=7, _) => Color::new(1.0, 0.0, 0.0),
+                    _ => Color::new(0.0, 0.0, 1.0),
+                })
+            })
+            .collect();
+
+        Arc::new(Texture::new(16, 8, pixels))
+    }
+
+    #[test]
+    fn the_default_background_is_black() {
+        assert_fuzzy_eq!(
+            Background::default().radiance(Tuple::vector(0.0, 1.0, 0.0)),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn a_colored_background_looks_the_same_everywhere() {
+        let background = Background::from(Color::new(0.2, 0.4, 0.8));
+
+        assert_fuzzy_eq!(
+            background.radiance(Tuple::vector(1.0, 0.0, 0.0)),
+            Color::new(0.2, 0.4, 0.8)
+        );
+    }
+
+    #[test]
+    fn a_sky_background_shows_the_sky() {
+        let sky = Sky::new(Tuple::vector(0.0, 1.0, 1.0), 3.0);
+        let direction = Tuple::vector(1.0, 0.5, 0.0);
+
+        assert_fuzzy_eq!(
+            Background::from(sky).radiance(direction),
+            sky.radiance(direction)
+        );
+    }
+
+    #[test]
+    fn mapping_directions_onto_the_image() {
+        let environment = EnvironmentMap::new(panorama());
+
+        // Looking towards -x, left of the center of the image
+        assert_fuzzy_eq!(
+            environment.radiance(Tuple::vector(-1.0, 0.0, 0.0)),
+            Color::new(1.0, 0.0, 0.0)
+        );
+        assert_fuzzy_eq!(
+            environment.radiance(Tuple::vector(1.0, 0.0, 0.0)),
+            Color::new(0.0, 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn directions_survive_the_round_trip_through_the_image() {
+        let environment = EnvironmentMap::new(panorama()).with_rotation(0.7);
+
+        for direction in [
+            Tuple::vector(1.0, 2.0, 3.0).normalize(),
+            Tuple::vector(-0.5, -0.1, 0.2).normalize(),
+            Tuple::vector(0.0, 0.0, -1.0),
+        ] {
+            let (x, y) = environment.direction_to_image(direction);
+
+            assert_fuzzy_eq!(environment.image_to_direction(x, y), direction);
+        }
+    }
+
+    #[test]
+    fn rotating_the_map() {
+        let environment = EnvironmentMap::new(panorama()).with_rotation(PI);
+
+        assert_fuzzy_eq!(
+            environment.radiance(Tuple::vector(1.0, 0.0, 0.0)),
+            Color::new(1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn scaling_the_intensity() {
+        let environment = EnvironmentMap::new(panorama()).with_intensity(2.0);
+
+        assert_fuzzy_eq!(
+            environment.radiance(Tuple::vector(-1.0, 0.0, 0.0)),
+            Color::new(2.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn samples_favour_the_bright_spot() {
+        let environment = EnvironmentMap::new(panorama());
+        let mut rng = Rng::new(5);
+        let samples = 1000;
+
+        let bright = (0..samples)
+            .filter_map(|_| environment.sample(&mut rng))
+            .filter(|sample| sample.radiance.red > 10.0)
+            .count();
+
+        assert!(bright > samples / 2);
+    }
+
+    #[test]
+    fn sample_densities_match_the_pdf() {
+        let environment = EnvironmentMap::new(panorama());
+        let mut rng = Rng::new(6);
+
+        for _ in 0..100 {
+            let sample = environment.sample(&mut rng).unwrap();
+
+            assert!(sample.pdf > 0.0);
+            assert_fuzzy_eq!(environment.pdf(sample.direction), sample.pdf);
+        }
+    }
+
+    #[test]
+    fn the_pdf_integrates_to_one_over_the_sphere() {
+        let environment = EnvironmentMap::new(panorama());
+        let mut rng = Rng::new(8);
+        let samples = 200000;
+
+        // Monte Carlo integration with uniformly distributed directions
+        let sum: F = (0..samples)
+            .map(|_| environment.pdf(crate::sampling::on_unit_sphere(&mut rng)))
+            .sum();
+        let integral = sum * 4.0 * PI / samples as F;
+
+        assert!((integral - 1.0).abs() < 0.05);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::F;
+
+/// Reconstruction filter, which weights the samples taken around the center of a pixel.
+///
+/// All filters are separable: the weight of an offset is the product of the one dimensional
+/// filter applied to its x and y components. Offsets are given in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    /// All samples count the same, a radius of 0.5 covers exactly one pixel
+    Box { radius: F },
+    /// Weights fall off linearly towards the radius
+    Tent { radius: F },
+    /// Bell curve `exp(-alpha * x^2)`, shifted down to reach zero at the radius
+    Gaussian { radius: F, alpha: F },
+    /// Cubic of Mitchell and Netravali with parameters `b` and `c`, which sharpens a bit
+    /// thanks to its negative lobes
+    Mitchell { radius: F, b: F, c: F },
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+impl Filter {
+    /// Gaussian filter of radius 1.5, a good default for smooth results.
+    pub fn gaussian() -> Self {
+        Filter::Gaussian {
+            radius: 1.5,
+            alpha: 2.0,
+        }
+    }
+
+    /// Mitchell filter of radius 2 with the recommended `b = c = 1/3`.
+    pub fn mitchell() -> Self {
+        Filter::Mitchell {
+            radius: 2.0,
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        }
+    }
+
+    /// Distance from the center of a pixel, beyond which samples have no weight.
+    pub fn radius(&self) -> F {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. } => radius,
+        }
+    }
+
+    pub fn weight(&self, dx: F, dy: F) -> F {
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+
+    fn weight_1d(&self, x: F) -> F {
+        let x = x.abs();
+        if x > self.radius() {
+            return 0.0;
+        }
+
+        match *self {
+            Filter::Box { .. } => 1.0,
+            Filter::Tent { radius } => radius - x,
+            Filter::Gaussian { radius, alpha } => {
+                ((-alpha * x * x).exp() - (-alpha * radius * radius).exp()).max(0.0)
+            }
+            Filter::Mitchell { radius, b, c } => mitchell(2.0 * x / radius, b, c),
+        }
+    }
+}
+
+/// Mitchell-Netravali cubic, which spans `[-2, 2]`.
+fn mitchell(x: F, b: F, c: F) -> F {
+    let x = x.abs();
+
+    let weight = if x < 1.0 {
+        (12.0 - 9.0 * b - 6.0 * c) * x * x * x
+            + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+            + (6.0 - 2.0 * b)
+    } else if x < 2.0 {
+        (-b - 6.0 * c) * x * x * x
+            + (6.0 * b + 30.0 * c) * x * x
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c)
+    } else {
+        0.0
+    };
+
+    weight / 6.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzy_eq::*;
+
+    #[test]
+    fn the_box_filter_weights_all_samples_within_its_radius_the_same() {
+        let filter = Filter::default();
+
+        assert_fuzzy_eq!(filter.weight(0.0, 0.0), 1.0);
+        assert_fuzzy_eq!(filter.weight(0.4, -0.3), 1.0);
+        assert_fuzzy_eq!(filter.weight(0.6, 0.0), 0.0);
+    }
+
+    #[test]
+    fn the_tent_filter_falls_off_linearly() {
+        let filter = Filter::Tent { radius: 1.0 };
+
+        assert_fuzzy_eq!(filter.weight(0.0, 0.0), 1.0);
+        assert_fuzzy_eq!(filter.weight(0.5, 0.0), 0.5);
+        assert_fuzzy_eq!(filter.weight(0.5, 0.5), 0.25);
+        assert_fuzzy_eq!(filter.weight(1.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn the_gaussian_filter_reaches_zero_at_its_radius() {
+        let filter = Filter::gaussian();
+
+        assert!(filter.weight(0.0, 0.0) > filter.weight(0.5, 0.0));
+        assert!(filter.weight(1.4, 0.0) > 0.0);
+        assert_fuzzy_eq!(filter.weight(1.5, 0.0), 0.0);
+    }
+
+    #[test]
+    fn the_mitchell_filter_has_negative_lobes() {
+        let filter = Filter::mitchell();
+
+        assert_fuzzy_eq!(filter.weight(0.0, 0.0), (8.0 / 9.0) * (8.0 / 9.0));
+        assert!(filter.weight(1.5, 0.0) < 0.0);
+        assert_fuzzy_eq!(filter.weight(2.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn the_mitchell_filter_is_continuous() {
+        let filter = Filter::mitchell();
+
+        assert_fuzzy_eq!(filter.weight(0.999999, 0.0), filter.weight(1.000001, 0.0));
+    }
+}
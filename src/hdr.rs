@@ -0,0 +1,277 @@
+//! Decoder for Radiance `.hdr` (RGBE) images, the usual format of HDR environment maps.
+
+use std::error::Error;
+use std::fmt;
+
+use crate::canvas::Color;
+use crate::texture::Texture;
+use crate::F;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HdrError {
+    /// The data does not start with the `#?RADIANCE` or `#?RGBE` magic
+    NotAnHdrImage,
+    /// Pixels are stored in another format than 32 bit RGBE, e.g. XYZE
+    UnsupportedFormat(String),
+    /// The resolution line is missing, or describes a flipped or rotated image
+    UnsupportedResolution(String),
+    /// A run length encoded scanline does not add up to the width of the image
+    InvalidRunLength,
+    /// The data ends before all pixels were read
+    UnexpectedEnd,
+}
+
+impl fmt::Display for HdrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HdrError::NotAnHdrImage => write!(f, "not a Radiance HDR image"),
+            HdrError::UnsupportedFormat(format) => write!(f, "unsupported pixel format {format}"),
+            HdrError::UnsupportedResolution(line) => {
+                write!(f, "unsupported resolution line \"{line}\"")
+            }
+            HdrError::InvalidRunLength => write!(f, "invalid run length encoding"),
+            HdrError::UnexpectedEnd => write!(f, "unexpected end of data"),
+        }
+    }
+}
+
+impl Error for HdrError {}
+
+pub fn decode(data: &[u8]) -> Result<Texture, HdrError> {
+    let mut reader = Reader { data, position: 0 };
+
+    let magic = reader.line()?;
+    if magic != "#?RADIANCE" && magic != "#?RGBE" {
+        return Err(HdrError::NotAnHdrImage);
+    }
+
+    // Header variables, up to an empty line
+    loop {
+        let line = reader.line()?;
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(HdrError::UnsupportedFormat(format.to_string()));
+            }
+        }
+    }
+
+    let resolution = reader.line()?;
+    let (width, height) = parse_resolution(&resolution)
+        .ok_or_else(|| HdrError::UnsupportedResolution(resolution.clone()))?;
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for _ in 0..height {
+        for rgbe in reader.scanline(width)? {
+            pixels.push(rgbe_to_color(rgbe));
+        }
+    }
+
+    Ok(Texture::new(width, height, pixels))
+}
+
+/// Only the standard orientation is supported: rows from top to bottom, pixels from left to
+/// right, written as `-Y <height> +X <width>`.
+fn parse_resolution(line: &str) -> Option<(usize, usize)> {
+    match line.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", height, "+X", width] => {
+            let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+
+            if width > 0 && height > 0 {
+                Some((width, height))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Shared exponent encoding: each channel is a mantissa of `2 ^ (exponent - 128)`.
+fn rgbe_to_color([r, g, b, e]: [u8; 4]) -> Color {
+    if e == 0 {
+        return Color::black();
+    }
+
+    let factor = (2.0 as F).powi(e as i32 - (128 + 8));
+
+    Color::new(r as F * factor, g as F * factor, b as F * factor)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, HdrError> {
+        let byte = *self
+            .data
+            .get(self.position)
+            .ok_or(HdrError::UnexpectedEnd)?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], HdrError> {
+        let mut bytes = [0; N];
+        for byte in bytes.iter_mut() {
+            *byte = self.byte()?;
+        }
+        Ok(bytes)
+    }
+
+    fn line(&mut self) -> Result<String, HdrError> {
+        let mut line = vec![];
+
+        loop {
+            match self.byte()? {
+                b'\n' => break,
+                byte => line.push(byte),
+            }
+        }
+
+        Ok(String::from_utf8_lossy(&line).trim_end().to_string())
+    }
+
+    fn scanline(&mut self, width: usize) -> Result<Vec<[u8; 4]>, HdrError> {
+        // Run length encoded scanlines start with two 2s and their width
+        let is_encoded = (8..0x8000).contains(&width)
+            && self.data.get(self.position..self.position + 2) == Some(&[2, 2]);
+
+        if !is_encoded {
+            return (0..width).map(|_| self.bytes::<4>()).collect();
+        }
+
+        let [_, _, high, low] = self.bytes::<4>()?;
+        if ((high as usize) << 8 | low as usize) != width {
+            return Err(HdrError::InvalidRunLength);
+        }
+
+        // The four channels follow each other, each one compressed separately
+        let mut scanline = vec![[0; 4]; width];
+        for channel in 0..4 {
+            let mut x = 0;
+
+            while x < width {
+                let count = self.byte()?;
+
+                let (length, repeated) = if count > 128 {
+                    (count as usize - 128, Some(self.byte()?))
+                } else {
+                    (count as usize, None)
+                };
+
+                if length == 0 || x + length > width {
+                    return Err(HdrError::InvalidRunLength);
+                }
+
+                for pixel in &mut scanline[x..x + length] {
+                    pixel[channel] = match repeated {
+                        Some(value) => value,
+                        None => self.byte()?,
+                    };
+                }
+
+                x += length;
+            }
+        }
+
+        Ok(scanline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzy_eq::*;
+
+    fn header(resolution: &str) -> Vec<u8> {
+        format!("#?RADIANCE\n# made by hand\nFORMAT=32-bit_rle_rgbe\n\n{resolution}\n").into_bytes()
+    }
+
+    #[test]
+    fn decoding_uncompressed_pixels() {
+        let mut data = header("-Y 2 +X 1");
+        // 1.0 is stored as 128 * 2^(129 - 136), 0.5 one exponent lower
+        data.extend_from_slice(&[128, 64, 0, 129, 128, 128, 128, 128]);
+
+        let texture = decode(&data).unwrap();
+
+        assert_eq!((texture.width, texture.height), (1, 2));
+        assert_fuzzy_eq!(texture.pixel_at(0, 0), Color::new(1.0, 0.5, 0.0));
+        assert_fuzzy_eq!(texture.pixel_at(0, 1), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn decoding_run_length_encoded_scanlines() {
+        let mut data = header("-Y 1 +X 8");
+        data.extend_from_slice(&[2, 2, 0, 8]);
+        // red: a run of 8 times 128
+        data.extend_from_slice(&[136, 128]);
+        // green: 4 literal values, then a run of 4 zeros
+        data.extend_from_slice(&[4, 0, 64, 128, 255, 132, 0]);
+        // blue: all zero
+        data.extend_from_slice(&[136, 0]);
+        // exponent: all 1.0
+        data.extend_from_slice(&[136, 129]);
+
+        let texture = decode(&data).unwrap();
+
+        assert_eq!((texture.width, texture.height), (8, 1));
+        assert_fuzzy_eq!(texture.pixel_at(0, 0), Color::new(1.0, 0.0, 0.0));
+        assert_fuzzy_eq!(texture.pixel_at(2, 0), Color::new(1.0, 1.0, 0.0));
+        assert_fuzzy_eq!(texture.pixel_at(3, 0), Color::new(1.0, 255.0 / 128.0, 0.0));
+        assert_fuzzy_eq!(texture.pixel_at(7, 0), Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn a_zero_exponent_is_black() {
+        assert_fuzzy_eq!(rgbe_to_color([255, 255, 255, 0]), Color::black());
+    }
+
+    #[test]
+    fn decoding_other_data_fails() {
+        assert_eq!(decode(b"P3\n1 1\n255\n"), Err(HdrError::NotAnHdrImage));
+    }
+
+    #[test]
+    fn decoding_unsupported_pixel_formats_fails() {
+        let data = b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n\x80\x80\x80\x81";
+
+        assert_eq!(
+            decode(data),
+            Err(HdrError::UnsupportedFormat("32-bit_rle_xyze".to_string()))
+        );
+    }
+
+    #[test]
+    fn decoding_flipped_images_fails() {
+        let mut data = header("+Y 1 +X 1");
+        data.extend_from_slice(&[128, 128, 128, 129]);
+
+        assert_eq!(
+            decode(&data),
+            Err(HdrError::UnsupportedResolution("+Y 1 +X 1".to_string()))
+        );
+    }
+
+    #[test]
+    fn decoding_truncated_data_fails() {
+        let mut data = header("-Y 2 +X 2");
+        data.extend_from_slice(&[128, 128, 128, 129]);
+
+        assert_eq!(decode(&data), Err(HdrError::UnexpectedEnd));
+    }
+
+    #[test]
+    fn decoding_overlong_runs_fails() {
+        let mut data = header("-Y 1 +X 8");
+        data.extend_from_slice(&[2, 2, 0, 8, 137, 128]);
+
+        assert_eq!(decode(&data), Err(HdrError::InvalidRunLength));
+    }
+}
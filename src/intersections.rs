@@ -2,15 +2,36 @@ use core::ops::Index;
 use crate::body::*;
 use crate::F;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Intersection {
     pub t: F,
     pub body: Body,
+    /// Index of the body among the bodies of the world, if the intersection was found by
+    /// intersecting a world
+    pub body_index: Option<usize>,
 }
 
 impl Intersection {
     pub fn new(t: F, body: Body) -> Self {
-        Intersection { t, body }
+        Intersection {
+            t,
+            body,
+            body_index: None,
+        }
+    }
+
+    pub fn with_body_index(mut self, body_index: usize) -> Self {
+        self.body_index = Some(body_index);
+        self
+    }
+
+    /// Whether both intersections are with the same body of a world. Intersections which
+    /// do not know their body's index can only compare the bodies themselves.
+    pub fn is_same_body(&self, other: &Intersection) -> bool {
+        match (self.body_index, other.body_index) {
+            (Some(index), Some(other_index)) => index == other_index,
+            _ => self.body == other.body,
+        }
     }
 }
 
@@ -39,7 +60,7 @@ impl Intersections {
     pub fn hit(&self) -> Option<Intersection> {
         for intersection in self.data.iter() {
             if intersection.t > 0.0 {
-                return Some(*intersection);
+                return Some(intersection.clone());
             }
         }
         None
@@ -71,10 +92,10 @@ mod tests {
         let s = Sphere::new(None);
 
 
-        let i1 = Intersection::new(1.0, Body::from(s));
-        let i2 = Intersection::new(2.0, Body::from(s));
+        let i1 = Intersection::new(1.0, Body::from(s.clone()));
+        let i2 = Intersection::new(2.0, Body::from(s.clone()));
 
-        let xs = Intersections::new(vec![i2, i1]);
+        let xs = Intersections::new(vec![i2.clone(), i1.clone()]);
 
         assert_eq!(xs.hit(), Some(i1));
     }
@@ -83,10 +104,10 @@ mod tests {
     fn the_hit_when_some_intersections_have_negative_t() {
         let s = Sphere::new(None);
 
-        let i1 = Intersection::new(-1.0, Body::from(s));
-        let i2 = Intersection::new(1.0, Body::from(s));
+        let i1 = Intersection::new(-1.0, Body::from(s.clone()));
+        let i2 = Intersection::new(1.0, Body::from(s.clone()));
 
-        let xs = Intersections::new(vec![i2, i1]);
+        let xs = Intersections::new(vec![i2.clone(), i1.clone()]);
 
         assert_eq!(xs.hit(), Some(i2));
     }
@@ -96,10 +117,10 @@ mod tests {
         let s = Sphere::new(None);
 
 
-        let i1 = Intersection::new(-2.0, Body::from(s));
-        let i2 = Intersection::new(-1.0, Body::from(s));
+        let i1 = Intersection::new(-2.0, Body::from(s.clone()));
+        let i2 = Intersection::new(-1.0, Body::from(s.clone()));
 
-        let xs = Intersections::new(vec![i2, i1]);
+        let xs = Intersections::new(vec![i2.clone(), i1.clone()]);
 
         assert_eq!(xs.hit(), None);
     }
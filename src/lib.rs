@@ -8,8 +8,33 @@ pub mod tuple;
 pub mod canvas;
 pub mod matrix;
 pub mod ray;
+pub mod motion;
 pub mod sphere;
+pub mod triangle;
 pub mod body;
 pub mod intersections;
 pub mod light;
-pub mod material;
\ No newline at end of file
+pub mod material;
+pub mod noise;
+pub mod normal_perturbation;
+pub mod computed_intersection;
+pub mod texture;
+pub mod hdr;
+pub mod environment;
+pub mod sky;
+pub mod world;
+pub mod filter;
+pub mod aperture;
+pub mod aov;
+pub mod camera;
+pub mod tile;
+pub mod stereo;
+pub mod random;
+pub mod sampling;
+pub mod emitter;
+pub mod bsdf;
+pub mod path_tracer;
+pub mod ambient_occlusion;
+pub mod debug;
+pub mod progressive;
+pub mod checkpoint;
\ No newline at end of file
@@ -1,10 +1,165 @@
 use crate::canvas::Color;
+use crate::random::Rng;
+use crate::sampling::orthonormal_basis;
 use crate::tuple::*;
+use crate::{EPSILON, F};
+use std::f64::consts::PI;
+
+/// Light arriving at a lit point, from a single point on a light source.
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    /// Unit vector from the lit point towards the light
+    pub direction: Tuple,
+    /// How far away the light is, infinite for lights like the sun
+    pub distance: F,
+    /// Color and brightness of the light, as it arrives at the lit point
+    pub intensity: Color,
+}
+
+impl LightSample {
+    /// Light shining from `light_point` onto `point`.
+    pub fn between(point: Tuple, light_point: Tuple, intensity: Color) -> Self {
+        let to_light = light_point - point;
+        let distance = to_light.magnitude();
+
+        LightSample {
+            direction: to_light / distance,
+            distance,
+            intensity,
+        }
+    }
+}
+
+/// Anything shading can be computed for. Lights with an area are represented by a set of
+/// samples, shading and shadows get averaged over those.
+pub trait LightSource {
+    /// Color and brightness of the light, before any falloff.
+    fn intensity(&self) -> Color;
+
+    /// Light arriving at the given point. Lights with an area jitter the points sampled on
+    /// them randomly, seeded by the lit point, so renders stay reproducible.
+    fn samples(&self, point: Tuple) -> Vec<LightSample>;
+
+    /// Ambient light added at the given point. Lights which fade with the distance fade their
+    /// ambient light alike.
+    fn ambient_intensity(&self, _point: Tuple) -> Color {
+        self.intensity()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Light {
+    Point(PointLight),
+    Spot(SpotLight),
+    Directional(DirectionalLight),
+    Rectangle(RectangleLight),
+    Sphere(SphereLight),
+}
+
+impl From<PointLight> for Light {
+    fn from(point_light: PointLight) -> Self {
+        Light::Point(point_light)
+    }
+}
+
+impl From<SpotLight> for Light {
+    fn from(spot_light: SpotLight) -> Self {
+        Light::Spot(spot_light)
+    }
+}
+
+impl From<DirectionalLight> for Light {
+    fn from(directional_light: DirectionalLight) -> Self {
+        Light::Directional(directional_light)
+    }
+}
+
+impl From<RectangleLight> for Light {
+    fn from(rectangle_light: RectangleLight) -> Self {
+        Light::Rectangle(rectangle_light)
+    }
+}
+
+impl From<SphereLight> for Light {
+    fn from(sphere_light: SphereLight) -> Self {
+        Light::Sphere(sphere_light)
+    }
+}
+
+impl LightSource for Light {
+    fn intensity(&self) -> Color {
+        match *self {
+            Light::Point(ref point_light) => point_light.intensity(),
+            Light::Spot(ref spot_light) => spot_light.intensity(),
+            Light::Directional(ref directional_light) => directional_light.intensity(),
+            Light::Rectangle(ref rectangle_light) => rectangle_light.intensity(),
+            Light::Sphere(ref sphere_light) => sphere_light.intensity(),
+        }
+    }
+
+    fn samples(&self, point: Tuple) -> Vec<LightSample> {
+        match *self {
+            Light::Point(ref point_light) => point_light.samples(point),
+            Light::Spot(ref spot_light) => spot_light.samples(point),
+            Light::Directional(ref directional_light) => directional_light.samples(point),
+            Light::Rectangle(ref rectangle_light) => rectangle_light.samples(point),
+            Light::Sphere(ref sphere_light) => sphere_light.samples(point),
+        }
+    }
+
+    fn ambient_intensity(&self, point: Tuple) -> Color {
+        match *self {
+            Light::Point(ref point_light) => point_light.ambient_intensity(point),
+            Light::Spot(ref spot_light) => spot_light.ambient_intensity(point),
+            Light::Directional(ref directional_light) => directional_light.ambient_intensity(point),
+            Light::Rectangle(ref rectangle_light) => rectangle_light.ambient_intensity(point),
+            Light::Sphere(ref sphere_light) => sphere_light.ambient_intensity(point),
+        }
+    }
+}
+
+/// How the light of a point light fades with the distance.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Attenuation {
+    /// Equally bright at any distance
+    #[default]
+    None,
+    /// Divided by `constant + linear * d + quadratic * d^2`, as in classic real-time renderers
+    Polynomial {
+        constant: F,
+        linear: F,
+        quadratic: F,
+    },
+    /// Divided by the squared distance, like real light. The intensity is then the
+    /// brightness at a distance of one unit.
+    InverseSquare,
+}
+
+impl Attenuation {
+    /// Fraction of the light left after travelling the given distance.
+    pub fn factor(&self, distance: F) -> F {
+        match *self {
+            Attenuation::None => 1.0,
+            Attenuation::Polynomial {
+                constant,
+                linear,
+                quadratic,
+            } => {
+                // Coefficients which vanish or turn negative would blow the light up, keep it
+                // finite and positive
+                let denominator = constant + linear * distance + quadratic * distance.powi(2);
+                1.0 / denominator.max(EPSILON)
+            }
+            Attenuation::InverseSquare => 1.0 / distance.powi(2),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct PointLight {
     pub position: Tuple,
     pub intensity: Color,
+    pub attenuation: Attenuation,
 }
 
 impl PointLight {
@@ -12,10 +167,263 @@ impl PointLight {
         PointLight {
             position,
             intensity,
+            attenuation: Attenuation::default(),
+        }
+    }
+
+    pub fn with_attenuation(mut self, attenuation: Attenuation) -> Self {
+        self.attenuation = attenuation;
+        self
+    }
+}
+
+impl LightSource for PointLight {
+    fn intensity(&self) -> Color {
+        self.intensity
+    }
+
+    fn samples(&self, point: Tuple) -> Vec<LightSample> {
+        let mut sample = LightSample::between(point, self.position, self.intensity);
+        sample.intensity = sample.intensity * self.attenuation.factor(sample.distance);
+
+        vec![sample]
+    }
+
+    fn ambient_intensity(&self, point: Tuple) -> Color {
+        let distance = (self.position - point).magnitude();
+
+        self.intensity * self.attenuation.factor(distance)
+    }
+}
+
+/// Point light, which only shines into a cone around its direction. The light fades out
+/// smoothly between the inner and the outer angle, both measured from the cone's axis.
+#[derive(Debug, Clone, Copy)]
+pub struct SpotLight {
+    pub position: Tuple,
+    pub direction: Tuple,
+    pub inner_angle: F,
+    pub outer_angle: F,
+    pub intensity: Color,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Tuple,
+        direction: Tuple,
+        inner_angle: F,
+        outer_angle: F,
+        intensity: Color,
+    ) -> Self {
+        assert!(
+            inner_angle <= outer_angle,
+            "The inner cone of a spot light must not be wider than the outer one"
+        );
+
+        SpotLight {
+            position,
+            direction: direction.normalize(),
+            inner_angle,
+            outer_angle,
+            intensity,
+        }
+    }
+
+    /// How much of the light shines into the given direction, away from the light.
+    pub fn falloff(&self, direction: Tuple) -> F {
+        let cosine = direction.normalize().dot(self.direction);
+        let (inner, outer) = (self.inner_angle.cos(), self.outer_angle.cos());
+
+        if cosine >= inner {
+            1.0
+        } else if cosine <= outer {
+            0.0
+        } else {
+            // Smoothstep, so the edge of the cone has no visible seam
+            let x = (cosine - outer) / (inner - outer);
+            x * x * (3.0 - 2.0 * x)
+        }
+    }
+}
+
+impl LightSource for SpotLight {
+    fn intensity(&self) -> Color {
+        self.intensity
+    }
+
+    fn samples(&self, point: Tuple) -> Vec<LightSample> {
+        let falloff = self.falloff(point - self.position);
+
+        vec![LightSample::between(
+            point,
+            self.position,
+            self.intensity * falloff,
+        )]
+    }
+}
+
+/// Light from very far away, like the sun, which reaches everything from the same
+/// direction. `direction` is the way the light travels.
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionalLight {
+    pub direction: Tuple,
+    pub intensity: Color,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Tuple, intensity: Color) -> Self {
+        DirectionalLight {
+            direction: direction.normalize(),
+            intensity,
         }
     }
 }
 
+impl LightSource for DirectionalLight {
+    fn intensity(&self) -> Color {
+        self.intensity
+    }
+
+    fn samples(&self, _point: Tuple) -> Vec<LightSample> {
+        vec![LightSample {
+            direction: -self.direction,
+            distance: F::INFINITY,
+            intensity: self.intensity,
+        }]
+    }
+}
+
+/// Flat light in the shape of a parallelogram, spanned by two edges starting at a corner.
+/// It is sampled once per cell of a `usteps` by `vsteps` grid.
+#[derive(Debug, Clone, Copy)]
+pub struct RectangleLight {
+    pub corner: Tuple,
+    pub uvec: Tuple,
+    pub usteps: usize,
+    pub vvec: Tuple,
+    pub vsteps: usize,
+    pub intensity: Color,
+}
+
+impl RectangleLight {
+    pub fn new(
+        corner: Tuple,
+        uvec: Tuple,
+        usteps: usize,
+        vvec: Tuple,
+        vsteps: usize,
+        intensity: Color,
+    ) -> Self {
+        assert!(
+            usteps > 0 && vsteps > 0,
+            "Area lights need at least one sample per side"
+        );
+
+        RectangleLight {
+            corner,
+            uvec,
+            usteps,
+            vvec,
+            vsteps,
+            intensity,
+        }
+    }
+
+    pub fn center(&self) -> Tuple {
+        self.corner + self.uvec * 0.5 + self.vvec * 0.5
+    }
+
+    /// One jittered point per cell of the grid, seeded by the lit point.
+    pub fn sample_points(&self, point: Tuple) -> Vec<Tuple> {
+        let mut rng = Rng::for_point(point);
+        let mut points = Vec::with_capacity(self.usteps * self.vsteps);
+
+        for v in 0..self.vsteps {
+            for u in 0..self.usteps {
+                let u = (u as F + rng.next_f64()) / self.usteps as F;
+                let v = (v as F + rng.next_f64()) / self.vsteps as F;
+
+                points.push(self.corner + self.uvec * u + self.vvec * v);
+            }
+        }
+
+        points
+    }
+}
+
+impl LightSource for RectangleLight {
+    fn intensity(&self) -> Color {
+        self.intensity
+    }
+
+    fn samples(&self, point: Tuple) -> Vec<LightSample> {
+        self.sample_points(point)
+            .into_iter()
+            .map(|light_point| LightSample::between(point, light_point, self.intensity))
+            .collect()
+    }
+}
+
+/// Glowing ball. Seen from anywhere it looks like a disk facing the lit point, which is
+/// sampled once per cell of a grid of `steps` rings by `steps` sectors.
+#[derive(Debug, Clone, Copy)]
+pub struct SphereLight {
+    pub center: Tuple,
+    pub radius: F,
+    pub steps: usize,
+    pub intensity: Color,
+}
+
+impl SphereLight {
+    pub fn new(center: Tuple, radius: F, steps: usize, intensity: Color) -> Self {
+        assert!(steps > 0, "Area lights need at least one sample per side");
+
+        SphereLight {
+            center,
+            radius,
+            steps,
+            intensity,
+        }
+    }
+
+    /// One jittered point per cell of the disk facing the lit point, seeded by that point.
+    pub fn sample_points(&self, point: Tuple) -> Vec<Tuple> {
+        let mut rng = Rng::for_point(point);
+        let (tangent, bitangent) = orthonormal_basis((point - self.center).normalize());
+        let mut points = Vec::with_capacity(self.steps * self.steps);
+
+        for ring in 0..self.steps {
+            for sector in 0..self.steps {
+                // Rings of equal area, so all cells of the disk are equally large
+                let area = (ring as F + rng.next_f64()) / self.steps as F;
+                let angle = 2.0 * PI * (sector as F + rng.next_f64()) / self.steps as F;
+                let distance = self.radius * area.sqrt();
+
+                points.push(
+                    self.center
+                        + tangent * (distance * angle.cos())
+                        + bitangent * (distance * angle.sin()),
+                );
+            }
+        }
+
+        points
+    }
+}
+
+impl LightSource for SphereLight {
+    fn intensity(&self) -> Color {
+        self.intensity
+    }
+
+    fn samples(&self, point: Tuple) -> Vec<LightSample> {
+        self.sample_points(point)
+            .into_iter()
+            .map(|light_point| LightSample::between(point, light_point, self.intensity))
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -31,4 +439,222 @@ mod tests {
         assert_fuzzy_eq!(l.position, position);
         assert_fuzzy_eq!(l.intensity, intensity);
     }
+
+    #[test]
+    fn a_point_light_is_sampled_at_its_position() {
+        let l = Light::from(PointLight::new(Tuple::point(0.0, 3.0, 4.0), Color::white()));
+
+        let samples = l.samples(Tuple::point(0.0, 0.0, 0.0));
+
+        assert_eq!(samples.len(), 1);
+        assert_fuzzy_eq!(samples[0].direction, Tuple::vector(0.0, 0.6, 0.8));
+        assert_fuzzy_eq!(samples[0].distance, 5.0);
+        assert_fuzzy_eq!(samples[0].intensity, Color::white());
+        assert_fuzzy_eq!(l.intensity(), Color::white());
+    }
+
+    #[test]
+    fn point_lights_do_not_fade_by_default() {
+        let l = PointLight::new(Tuple::point(0.0, 0.0, 0.0), Color::white());
+
+        assert_eq!(l.attenuation, Attenuation::None);
+        assert_fuzzy_eq!(
+            l.samples(Tuple::point(0.0, 1000.0, 0.0))[0].intensity,
+            Color::white()
+        );
+    }
+
+    #[test]
+    fn polynomial_attenuation() {
+        let attenuation = Attenuation::Polynomial {
+            constant: 1.0,
+            linear: 0.5,
+            quadratic: 0.25,
+        };
+
+        assert_fuzzy_eq!(attenuation.factor(0.0), 1.0);
+        assert_fuzzy_eq!(attenuation.factor(2.0), 1.0 / 3.0);
+        assert_fuzzy_eq!(attenuation.factor(4.0), 1.0 / 7.0);
+    }
+
+    #[test]
+    fn polynomial_attenuation_stays_finite_for_vanishing_coefficients() {
+        let attenuation = Attenuation::Polynomial {
+            constant: 0.0,
+            linear: 0.0,
+            quadratic: 0.0,
+        };
+        let without_constant = Attenuation::Polynomial {
+            constant: 0.0,
+            linear: 1.0,
+            quadratic: 1.0,
+        };
+        let negative = Attenuation::Polynomial {
+            constant: -1.0,
+            linear: 0.0,
+            quadratic: 0.0,
+        };
+
+        assert!(attenuation.factor(1.0).is_finite());
+        assert!(without_constant.factor(0.0).is_finite());
+        assert!(negative.factor(1.0) > 0.0);
+        assert!(negative.factor(1.0).is_finite());
+    }
+
+    #[test]
+    fn attenuated_point_lights_fade_with_the_distance() {
+        let l = PointLight::new(Tuple::point(0.0, 0.0, 0.0), Color::new(1.0, 0.5, 0.0))
+            .with_attenuation(Attenuation::InverseSquare);
+
+        let near = l.samples(Tuple::point(0.0, 1.0, 0.0))[0];
+        let far = l.samples(Tuple::point(0.0, 4.0, 0.0))[0];
+
+        assert_fuzzy_eq!(near.intensity, Color::new(1.0, 0.5, 0.0));
+        assert_fuzzy_eq!(far.intensity, Color::new(0.0625, 0.03125, 0.0));
+        // Ambient light fades alike
+        assert_fuzzy_eq!(
+            l.ambient_intensity(Tuple::point(0.0, 1.0, 0.0)),
+            Color::new(1.0, 0.5, 0.0)
+        );
+        assert_fuzzy_eq!(
+            l.ambient_intensity(Tuple::point(0.0, 4.0, 0.0)),
+            Color::new(0.0625, 0.03125, 0.0)
+        );
+    }
+
+    fn spot_light() -> SpotLight {
+        SpotLight::new(
+            Tuple::point(0.0, 10.0, 0.0),
+            Tuple::vector(0.0, -1.0, 0.0),
+            PI / 8.0,
+            PI / 4.0,
+            Color::new(1.0, 0.5, 0.5),
+        )
+    }
+
+    #[test]
+    fn a_spot_light_fully_lights_points_inside_of_its_inner_cone() {
+        let l = Light::from(spot_light());
+
+        let samples = l.samples(Tuple::point(1.0, 0.0, 0.0));
+
+        assert_eq!(samples.len(), 1);
+        assert_fuzzy_eq!(
+            samples[0].direction,
+            Tuple::vector(-1.0, 10.0, 0.0).normalize()
+        );
+        assert_fuzzy_eq!(samples[0].intensity, Color::new(1.0, 0.5, 0.5));
+    }
+
+    #[test]
+    fn a_spot_light_does_not_light_points_outside_of_its_outer_cone() {
+        let l = Light::from(spot_light());
+
+        let samples = l.samples(Tuple::point(11.0, 0.0, 0.0));
+
+        assert_fuzzy_eq!(samples[0].intensity, Color::black());
+    }
+
+    #[test]
+    fn a_spot_light_fades_out_smoothly_between_its_cones() {
+        let l = spot_light();
+        let halfway = (3.0 * PI / 16.0).tan() * 10.0;
+
+        let at_the_edges = [
+            l.falloff(Tuple::vector((PI / 8.0).tan(), -1.0, 0.0)),
+            l.falloff(Tuple::vector((PI / 4.0).tan(), -1.0, 0.0)),
+        ];
+        let in_between = l.falloff(Tuple::point(halfway, 0.0, 0.0) - l.position);
+
+        assert_fuzzy_eq!(at_the_edges[0], 1.0);
+        assert_fuzzy_eq!(at_the_edges[1], 0.0);
+        assert!(in_between > 0.3 && in_between < 0.7);
+    }
+
+    #[test]
+    fn a_directional_light_shines_from_the_same_direction_everywhere() {
+        let l = Light::from(DirectionalLight::new(
+            Tuple::vector(0.0, -2.0, 0.0),
+            Color::white(),
+        ));
+
+        for point in [Tuple::point(0.0, 0.0, 0.0), Tuple::point(100.0, -5.0, 3.0)] {
+            let samples = l.samples(point);
+
+            assert_eq!(samples.len(), 1);
+            assert_fuzzy_eq!(samples[0].direction, Tuple::vector(0.0, 1.0, 0.0));
+            assert!(samples[0].distance.is_infinite());
+            assert_fuzzy_eq!(samples[0].intensity, Color::white());
+        }
+    }
+
+    #[test]
+    fn creating_a_rectangle_light() {
+        let corner = Tuple::point(0.0, 0.0, 0.0);
+        let v1 = Tuple::vector(2.0, 0.0, 0.0);
+        let v2 = Tuple::vector(0.0, 0.0, 1.0);
+
+        let l = RectangleLight::new(corner, v1, 4, v2, 2, Color::white());
+
+        assert_fuzzy_eq!(l.corner, corner);
+        assert_eq!(l.usteps, 4);
+        assert_eq!(l.vsteps, 2);
+        assert_fuzzy_eq!(l.center(), Tuple::point(1.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn a_rectangle_light_is_sampled_once_per_cell() {
+        let l = RectangleLight::new(
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(2.0, 0.0, 0.0),
+            4,
+            Tuple::vector(0.0, 0.0, 1.0),
+            2,
+            Color::white(),
+        );
+
+        let points = l.sample_points(Tuple::point(0.0, -5.0, 0.0));
+
+        assert_eq!(points.len(), 8);
+        for (index, point) in points.iter().enumerate() {
+            let (u, v) = (index % 4, index / 4);
+
+            assert_fuzzy_eq!(point.y, 0.0);
+            assert!(point.x >= u as F * 0.5 && point.x <= (u + 1) as F * 0.5);
+            assert!(point.z >= v as F * 0.5 && point.z <= (v + 1) as F * 0.5);
+        }
+    }
+
+    #[test]
+    fn area_light_samples_depend_on_the_lit_point_only() {
+        let l = RectangleLight::new(
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(1.0, 0.0, 0.0),
+            3,
+            Tuple::vector(0.0, 1.0, 0.0),
+            3,
+            Color::white(),
+        );
+        let point = Tuple::point(0.5, 0.5, -3.0);
+
+        assert_fuzzy_eq!(l.sample_points(point), l.sample_points(point));
+        assert_fuzzy_ne!(
+            l.sample_points(point),
+            l.sample_points(Tuple::point(0.0, 0.0, -3.0))
+        );
+    }
+
+    #[test]
+    fn a_sphere_light_is_sampled_on_the_disk_facing_the_point() {
+        let l = SphereLight::new(Tuple::point(0.0, 5.0, 0.0), 0.5, 3, Color::white());
+
+        let points = l.sample_points(Tuple::point(0.0, 0.0, 0.0));
+
+        assert_eq!(points.len(), 9);
+        assert_eq!(Light::from(l).samples(Tuple::point(0.0, 0.0, 0.0)).len(), 9);
+        for point in points {
+            assert_fuzzy_eq!(point.y, 5.0);
+            assert!((point - l.center).magnitude() <= 0.5);
+        }
+    }
 }
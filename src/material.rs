@@ -1,22 +1,67 @@
 use crate::canvas::Color;
 use crate::fuzzy_eq::*;
-use crate::light::PointLight;
+use crate::light::LightSource;
+use crate::normal_perturbation::NormalPerturbation;
 use crate::tuple::Tuple;
-use crate::F;
+use crate::{EPSILON, F};
+use std::f64::consts::PI;
 
 pub trait Illuminated {
-    fn lighting(
+    /// Light bounced around the scene, which reaches the surface no matter where the light
+    /// is placed or what blocks it.
+    fn ambient_light(&self, light_color: Color) -> Color;
+
+    /// Diffuse and specular light reflected towards the eye, for light of the given color
+    /// arriving from the direction of `light_vec`.
+    fn direct_light(
         &self,
-        light: PointLight,
-        position: Tuple,
+        light_color: Color,
+        light_vec: Tuple,
         eye_vec: Tuple,
         normal_vec: Tuple,
     ) -> Color;
+
+    /// Color of the surface at `position`, lit by `light`. `intensity` is the fraction of
+    /// the light reaching the point, 0 when fully in shadow and 1 when nothing is in the way.
+    fn lighting<L: LightSource>(
+        &self,
+        light: &L,
+        position: Tuple,
+        eye_vec: Tuple,
+        normal_vec: Tuple,
+        intensity: F,
+    ) -> Color {
+        self.occluded_lighting(light, position, eye_vec, normal_vec, intensity, 1.0)
+    }
+
+    /// Same as `lighting`, with the ambient light darkened where nearby bodies crowd the
+    /// surface. `ambient_visibility` is the unoccluded fraction of the hemisphere above it, as
+    /// estimated by `AmbientOcclusion`.
+    fn occluded_lighting<L: LightSource>(
+        &self,
+        light: &L,
+        position: Tuple,
+        eye_vec: Tuple,
+        normal_vec: Tuple,
+        intensity: F,
+        ambient_visibility: F,
+    ) -> Color {
+        let samples = light.samples(position);
+
+        let direct_light = samples.iter().fold(Color::black(), |sum, sample| {
+            sum + self.direct_light(sample.intensity, sample.direction, eye_vec, normal_vec)
+        });
+
+        self.ambient_light(light.ambient_intensity(position)) * ambient_visibility
+            + direct_light * (intensity / samples.len() as F)
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Material {
     Phong(Phong),
+    BlinnPhong(BlinnPhong),
+    CookTorrance(CookTorrance),
 }
 
 impl From<Phong> for Material {
@@ -25,44 +70,150 @@ impl From<Phong> for Material {
     }
 }
 
+impl From<BlinnPhong> for Material {
+    fn from(blinn_phong: BlinnPhong) -> Self {
+        Material::BlinnPhong(blinn_phong)
+    }
+}
+
+impl From<CookTorrance> for Material {
+    fn from(cook_torrance: CookTorrance) -> Self {
+        Material::CookTorrance(cook_torrance)
+    }
+}
+
 impl Default for Material {
     fn default() -> Self {
         Material::from(Phong::default())
     }
 }
 
+impl Material {
+    pub fn normal_perturbation(&self) -> Option<NormalPerturbation> {
+        match *self {
+            Material::Phong(Phong {
+                ref normal_perturbation,
+                ..
+            })
+            | Material::BlinnPhong(BlinnPhong {
+                ref normal_perturbation,
+                ..
+            })
+            | Material::CookTorrance(CookTorrance {
+                ref normal_perturbation,
+                ..
+            }) => normal_perturbation.clone(),
+        }
+    }
+
+    /// How much of the color seen in the mirror direction is added to the surface.
+    pub fn reflective(&self) -> F {
+        match *self {
+            Material::Phong(Phong { reflective, .. })
+            | Material::BlinnPhong(BlinnPhong { reflective, .. })
+            | Material::CookTorrance(CookTorrance { reflective, .. }) => reflective,
+        }
+    }
+
+    /// How much of the color seen through the surface is added to it.
+    pub fn transparency(&self) -> F {
+        match *self {
+            Material::Phong(Phong { transparency, .. })
+            | Material::BlinnPhong(BlinnPhong { transparency, .. })
+            | Material::CookTorrance(CookTorrance { transparency, .. }) => transparency,
+        }
+    }
+
+    pub fn refractive_index(&self) -> F {
+        match *self {
+            Material::Phong(Phong {
+                refractive_index, ..
+            })
+            | Material::BlinnPhong(BlinnPhong {
+                refractive_index, ..
+            })
+            | Material::CookTorrance(CookTorrance {
+                refractive_index, ..
+            }) => refractive_index,
+        }
+    }
+
+    /// Base color of the surface, before any light falls on it.
+    pub fn color(&self) -> Color {
+        match *self {
+            Material::Phong(Phong { color, .. })
+            | Material::BlinnPhong(BlinnPhong { color, .. })
+            | Material::CookTorrance(CookTorrance { color, .. }) => color,
+        }
+    }
+
+    pub fn emissive(&self) -> Color {
+        match *self {
+            Material::Phong(Phong { emissive, .. })
+            | Material::BlinnPhong(BlinnPhong { emissive, .. })
+            | Material::CookTorrance(CookTorrance { emissive, .. }) => emissive,
+        }
+    }
+}
+
 impl FuzzyEq<Material> for Material {
     fn fuzzy_eq(&self, other: Material) -> bool {
         match (self, other) {
             (Material::Phong(ref phong), Material::Phong(other)) => phong.fuzzy_eq(other),
-            // Add default case (different types) to return false, once more than one
-            // Material exists
-            // _ => false,
+            (Material::BlinnPhong(ref blinn_phong), Material::BlinnPhong(other)) => {
+                blinn_phong.fuzzy_eq(other)
+            }
+            (Material::CookTorrance(ref cook_torrance), Material::CookTorrance(other)) => {
+                cook_torrance.fuzzy_eq(other)
+            }
+            _ => false,
         }
     }
 }
 
 impl Illuminated for Material {
-    fn lighting(
+    fn ambient_light(&self, light_color: Color) -> Color {
+        match *self {
+            Material::Phong(ref phong) => phong.ambient_light(light_color),
+            Material::BlinnPhong(ref blinn_phong) => blinn_phong.ambient_light(light_color),
+            Material::CookTorrance(ref cook_torrance) => cook_torrance.ambient_light(light_color),
+        }
+    }
+
+    fn direct_light(
         &self,
-        light: PointLight,
-        position: Tuple,
+        light_color: Color,
+        light_vec: Tuple,
         eye_vec: Tuple,
         normal_vec: Tuple,
     ) -> Color {
         match *self {
-            Material::Phong(phong) => phong.lighting(light, position, eye_vec, normal_vec),
+            Material::Phong(ref phong) => {
+                phong.direct_light(light_color, light_vec, eye_vec, normal_vec)
+            }
+            Material::BlinnPhong(ref blinn_phong) => {
+                blinn_phong.direct_light(light_color, light_vec, eye_vec, normal_vec)
+            }
+            Material::CookTorrance(ref cook_torrance) => {
+                cook_torrance.direct_light(light_color, light_vec, eye_vec, normal_vec)
+            }
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Phong {
     pub color: Color,
     pub ambient: F,
     pub diffuse: F,
     pub specular: F,
     pub shininess: F,
+    pub reflective: F,
+    pub transparency: F,
+    pub refractive_index: F,
+    /// Light given off by the surface itself, independent of any light source
+    pub emissive: Color,
+    pub normal_perturbation: Option<NormalPerturbation>,
 }
 
 impl Default for Phong {
@@ -73,6 +224,11 @@ impl Default for Phong {
             diffuse: 0.9,
             specular: 0.9,
             shininess: 200.0,
+            reflective: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
+            emissive: Color::black(),
+            normal_perturbation: None,
         }
     }
 }
@@ -85,6 +241,11 @@ impl Phong {
             diffuse,
             specular,
             shininess,
+            reflective: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
+            emissive: Color::black(),
+            normal_perturbation: None,
         }
     }
 
@@ -94,6 +255,11 @@ impl Phong {
             ..Self::default()
         }
     }
+
+    pub fn with_normal_perturbation(mut self, normal_perturbation: NormalPerturbation) -> Self {
+        self.normal_perturbation = Some(normal_perturbation);
+        self
+    }
 }
 
 impl FuzzyEq<Phong> for Phong {
@@ -103,26 +269,30 @@ impl FuzzyEq<Phong> for Phong {
             && self.diffuse.fuzzy_eq(other.diffuse)
             && self.specular.fuzzy_eq(other.specular)
             && self.shininess.fuzzy_eq(other.shininess)
+            && self.reflective.fuzzy_eq(other.reflective)
+            && self.transparency.fuzzy_eq(other.transparency)
+            && self.refractive_index.fuzzy_eq(other.refractive_index)
+            && self.emissive.fuzzy_eq(other.emissive)
+            && self.normal_perturbation.fuzzy_eq(other.normal_perturbation)
     }
 }
 
 impl Illuminated for Phong {
-    fn lighting(
+    fn ambient_light(&self, light_color: Color) -> Color {
+        self.color * light_color * self.ambient
+    }
+
+    fn direct_light(
         &self,
-        light: PointLight,
-        position: Tuple,
+        light_color: Color,
+        light_vec: Tuple,
         eye_vec: Tuple,
         normal_vec: Tuple,
     ) -> Color {
-        let ambient_light: Color;
         let diffuse_light: Color;
         let specular_light: Color;
 
-        let effective_color = self.color * light.intensity;
-
-        let light_vec = (light.position - position).normalize();
-
-        ambient_light = effective_color * self.ambient;
+        let effective_color = self.color * light_color;
 
         let light_dot_normal = light_vec.dot(normal_vec);
 
@@ -142,17 +312,257 @@ impl Illuminated for Phong {
                 specular_light = Color::black();
             } else {
                 let factor = reflect_dot_eye.powf(self.shininess);
-                specular_light = light.intensity * self.specular * factor;
+                specular_light = light_color * self.specular * factor;
             }
         }
 
-        ambient_light + diffuse_light + specular_light
+        diffuse_light + specular_light
+    }
+}
+
+/// Like `Phong`, but the highlight is computed from the halfway vector between the light
+/// and the eye, which keeps highlights from being cut off at grazing angles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlinnPhong {
+    pub color: Color,
+    pub ambient: F,
+    pub diffuse: F,
+    pub specular: F,
+    pub shininess: F,
+    pub reflective: F,
+    pub transparency: F,
+    pub refractive_index: F,
+    /// Light given off by the surface itself, independent of any light source
+    pub emissive: Color,
+    pub normal_perturbation: Option<NormalPerturbation>,
+}
+
+impl Default for BlinnPhong {
+    fn default() -> Self {
+        BlinnPhong {
+            color: Color::new(1.0, 1.0, 1.0),
+            ambient: 0.1,
+            diffuse: 0.9,
+            specular: 0.9,
+            // Roughly four times the Phong shininess gives highlights of a similar size
+            shininess: 800.0,
+            reflective: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
+            emissive: Color::black(),
+            normal_perturbation: None,
+        }
+    }
+}
+
+impl BlinnPhong {
+    pub fn new(color: Color, ambient: F, diffuse: F, specular: F, shininess: F) -> Self {
+        BlinnPhong {
+            color,
+            ambient,
+            diffuse,
+            specular,
+            shininess,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_color(color: Color) -> Self {
+        BlinnPhong {
+            color,
+            ..Self::default()
+        }
+    }
+}
+
+impl FuzzyEq<BlinnPhong> for BlinnPhong {
+    fn fuzzy_eq(&self, other: BlinnPhong) -> bool {
+        self.color.fuzzy_eq(other.color)
+            && self.ambient.fuzzy_eq(other.ambient)
+            && self.diffuse.fuzzy_eq(other.diffuse)
+            && self.specular.fuzzy_eq(other.specular)
+            && self.shininess.fuzzy_eq(other.shininess)
+            && self.reflective.fuzzy_eq(other.reflective)
+            && self.transparency.fuzzy_eq(other.transparency)
+            && self.refractive_index.fuzzy_eq(other.refractive_index)
+            && self.emissive.fuzzy_eq(other.emissive)
+            && self.normal_perturbation.fuzzy_eq(other.normal_perturbation)
+    }
+}
+
+impl Illuminated for BlinnPhong {
+    fn ambient_light(&self, light_color: Color) -> Color {
+        self.color * light_color * self.ambient
+    }
+
+    fn direct_light(
+        &self,
+        light_color: Color,
+        light_vec: Tuple,
+        eye_vec: Tuple,
+        normal_vec: Tuple,
+    ) -> Color {
+        let effective_color = self.color * light_color;
+
+        let light_dot_normal = light_vec.dot(normal_vec);
+
+        if light_dot_normal < 0.0 {
+            // Light is on the other side of the surface
+            return Color::black();
+        }
+
+        let diffuse_light = effective_color * self.diffuse * light_dot_normal;
+
+        let halfway_vec = (light_vec + eye_vec).normalize();
+        let halfway_dot_normal = halfway_vec.dot(normal_vec);
+
+        let specular_light = if halfway_dot_normal <= 0.0 {
+            Color::black()
+        } else {
+            light_color * self.specular * halfway_dot_normal.powf(self.shininess)
+        };
+
+        diffuse_light + specular_light
+    }
+}
+
+/// Physically based microfacet material, using the GGX normal distribution, the Smith
+/// geometry term and Schlick's approximation of the Fresnel term.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CookTorrance {
+    pub color: Color,
+    pub ambient: F,
+    /// Perceptual roughness between 0.0 (polished) and 1.0 (completely rough)
+    pub roughness: F,
+    /// 0.0 for dielectrics, 1.0 for metals. Metals tint their reflections with the color
+    /// and have no diffuse part.
+    pub metallic: F,
+    /// Reflectance of dielectrics when looking straight at the surface
+    pub reflectance: F,
+    pub reflective: F,
+    pub transparency: F,
+    pub refractive_index: F,
+    /// Light given off by the surface itself, independent of any light source
+    pub emissive: Color,
+    pub normal_perturbation: Option<NormalPerturbation>,
+}
+
+impl Default for CookTorrance {
+    fn default() -> Self {
+        CookTorrance {
+            color: Color::new(1.0, 1.0, 1.0),
+            ambient: 0.1,
+            roughness: 0.5,
+            metallic: 0.0,
+            reflectance: 0.04,
+            reflective: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
+            emissive: Color::black(),
+            normal_perturbation: None,
+        }
+    }
+}
+
+impl CookTorrance {
+    pub fn new(color: Color, ambient: F, roughness: F, metallic: F) -> Self {
+        CookTorrance {
+            color,
+            ambient,
+            roughness,
+            metallic,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_color(color: Color) -> Self {
+        CookTorrance {
+            color,
+            ..Self::default()
+        }
+    }
+}
+
+impl FuzzyEq<CookTorrance> for CookTorrance {
+    fn fuzzy_eq(&self, other: CookTorrance) -> bool {
+        self.color.fuzzy_eq(other.color)
+            && self.ambient.fuzzy_eq(other.ambient)
+            && self.roughness.fuzzy_eq(other.roughness)
+            && self.metallic.fuzzy_eq(other.metallic)
+            && self.reflectance.fuzzy_eq(other.reflectance)
+            && self.reflective.fuzzy_eq(other.reflective)
+            && self.transparency.fuzzy_eq(other.transparency)
+            && self.refractive_index.fuzzy_eq(other.refractive_index)
+            && self.emissive.fuzzy_eq(other.emissive)
+            && self.normal_perturbation.fuzzy_eq(other.normal_perturbation)
+    }
+}
+
+/// Alpha below which surfaces are treated as equally smooth, keeping the highlights of
+/// polished surfaces bounded.
+const MIN_ALPHA: F = 1e-3;
+
+impl Illuminated for CookTorrance {
+    fn ambient_light(&self, light_color: Color) -> Color {
+        self.color * light_color * self.ambient
+    }
+
+    fn direct_light(
+        &self,
+        light_color: Color,
+        light_vec: Tuple,
+        eye_vec: Tuple,
+        normal_vec: Tuple,
+    ) -> Color {
+        let light_dot_normal = light_vec.dot(normal_vec);
+
+        if light_dot_normal <= 0.0 {
+            // Light is on the other side of the surface
+            return Color::black();
+        }
+
+        // Keep away from zero, where the specular term would blow up
+        let eye_dot_normal = eye_vec.dot(normal_vec).max(EPSILON);
+        let halfway_vec = (light_vec + eye_vec).normalize();
+        let halfway_dot_normal = halfway_vec.dot(normal_vec).max(0.0);
+        let eye_dot_halfway = eye_vec.dot(halfway_vec).max(0.0);
+
+        // A perfectly smooth surface would only reflect exactly along the halfway vector,
+        // where the distribution turns into 0 / 0
+        let alpha = self.roughness.powi(2).max(MIN_ALPHA);
+        let alpha2 = alpha.powi(2);
+
+        // GGX / Trowbridge-Reitz normal distribution
+        let distribution =
+            alpha2 / (PI * (halfway_dot_normal.powi(2) * (alpha2 - 1.0) + 1.0).powi(2));
+
+        // Smith geometry term with Schlick's approximation, remapped for direct lighting
+        let k = (self.roughness + 1.0).powi(2) / 8.0;
+        let geometry_1 = |dot: F| dot / (dot * (1.0 - k) + k);
+        let geometry = geometry_1(light_dot_normal) * geometry_1(eye_dot_normal);
+
+        // Schlick's Fresnel, metals tint their reflections with their color
+        let f0 = Color::white() * (self.reflectance * (1.0 - self.metallic))
+            + self.color * self.metallic;
+        let fresnel = f0 + (Color::white() - f0) * (1.0 - eye_dot_halfway).powi(5);
+
+        let specular =
+            fresnel * (distribution * geometry / (4.0 * light_dot_normal * eye_dot_normal));
+
+        // Whatever is not reflected at the surface enters it and gets scattered diffusely.
+        let diffuse = (Color::white() - fresnel) * self.color * (1.0 - self.metallic);
+
+        // The lambertian 1 / PI is folded into the light intensity throughout the renderer,
+        // so a white diffuse surface facing the light fully reflects it. Scale the specular
+        // part of the BRDF to match.
+        (diffuse + specular * PI) * light_color * light_dot_normal
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::light::*;
 
     #[test]
     fn default_phong_material() {
@@ -163,6 +573,11 @@ mod tests {
         assert_fuzzy_eq!(materail.diffuse, 0.9);
         assert_fuzzy_eq!(materail.specular, 0.9);
         assert_fuzzy_eq!(materail.shininess, 200.0);
+        assert_fuzzy_eq!(materail.reflective, 0.0);
+        assert_fuzzy_eq!(materail.transparency, 0.0);
+        assert_fuzzy_eq!(materail.refractive_index, 1.0);
+        assert_fuzzy_eq!(materail.emissive, Color::black());
+        assert_fuzzy_eq!(materail.normal_perturbation, None);
     }
 
     #[test]
@@ -191,7 +606,7 @@ mod tests {
         let normalv = Tuple::vector(0.0, 0.0, -1.0);
         let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
 
-        let actual_result = m.lighting(light, position, eyev, normalv);
+        let actual_result = m.lighting(&light, position, eyev, normalv, 1.0);
 
         let expected_result = Color::new(1.9, 1.9, 1.9);
 
@@ -208,7 +623,7 @@ mod tests {
         let normalv = Tuple::vector(0.0, 0.0, -1.0);
         let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
 
-        let actual_result = m.lighting(light, position, eyev, normalv);
+        let actual_result = m.lighting(&light, position, eyev, normalv, 1.0);
 
         let expected_result = Color::new(1.0, 1.0, 1.0);
 
@@ -224,7 +639,7 @@ mod tests {
         let normalv = Tuple::vector(0.0, 0.0, -1.0);
         let light = PointLight::new(Tuple::point(0.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
 
-        let actual_result = m.lighting(light, position, eyev, normalv);
+        let actual_result = m.lighting(&light, position, eyev, normalv, 1.0);
 
         let expected_result = Color::new(0.7364, 0.7364, 0.7364);
 
@@ -241,7 +656,7 @@ mod tests {
         let normalv = Tuple::vector(0.0, 0.0, -1.0);
         let light = PointLight::new(Tuple::point(0.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
 
-        let actual_result = m.lighting(light, position, eyev, normalv);
+        let actual_result = m.lighting(&light, position, eyev, normalv, 1.0);
 
         let expected_result = Color::new(1.6364, 1.6364, 1.6364);
 
@@ -257,10 +672,279 @@ mod tests {
         let normalv = Tuple::vector(0.0, 0.0, -1.0);
         let light = PointLight::new(Tuple::point(0.0, 0.0, 10.0), Color::new(1.0, 1.0, 1.0));
 
-        let actual_result = m.lighting(light, position, eyev, normalv);
+        let actual_result = m.lighting(&light, position, eyev, normalv, 1.0);
 
         let expected_result = Color::new(0.1, 0.1, 0.1);
 
         assert_fuzzy_eq!(actual_result, expected_result);
     }
+
+    #[test]
+    fn lighting_with_the_surface_in_shadow() {
+        let m = Phong::default();
+        let position = Tuple::point(0.0, 0.0, 0.0);
+
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+
+        let actual_result = m.lighting(&light, position, eyev, normalv, 0.0);
+
+        assert_fuzzy_eq!(actual_result, Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn ambient_occlusion_darkens_only_the_ambient_light() {
+        let m = Phong::default();
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
+
+        let in_shadow = m.occluded_lighting(&light, position, eyev, normalv, 0.0, 0.25);
+        let lit = m.occluded_lighting(&light, position, eyev, normalv, 1.0, 0.0);
+
+        assert_fuzzy_eq!(in_shadow, Color::new(0.025, 0.025, 0.025));
+        assert_fuzzy_eq!(lit, Color::new(1.8, 1.8, 1.8));
+    }
+
+    #[test]
+    fn lighting_uses_light_intensity_to_attenuate_color() {
+        let m = Phong {
+            ambient: 0.1,
+            diffuse: 0.9,
+            specular: 0.0,
+            ..Phong::default()
+        };
+        let position = Tuple::point(0.0, 0.0, -1.0);
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = Light::from(PointLight::new(
+            Tuple::point(0.0, 0.0, -10.0),
+            Color::white(),
+        ));
+
+        for (intensity, expected) in [(1.0, 1.0), (0.5, 0.55), (0.0, 0.1)] {
+            let actual_result = m.lighting(&light, position, eyev, normalv, intensity);
+
+            assert_fuzzy_eq!(actual_result, Color::new(expected, expected, expected));
+        }
+    }
+
+    #[test]
+    fn lighting_fades_the_ambient_light_of_attenuated_lights() {
+        let m = Phong::default();
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white() * 100.0)
+            .with_attenuation(Attenuation::InverseSquare);
+
+        let in_shadow = m.lighting(&light, position, eyev, normalv, 0.0);
+
+        assert_fuzzy_eq!(in_shadow, Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn lighting_averages_over_the_points_of_an_area_light() {
+        let m = Phong {
+            ambient: 0.0,
+            specular: 0.0,
+            ..Phong::default()
+        };
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let eyev = Tuple::vector(0.0, 1.0, 0.0);
+        let normalv = Tuple::vector(0.0, 1.0, 0.0);
+        let light = RectangleLight::new(
+            Tuple::point(-1.0, 1.0, -1.0),
+            Tuple::vector(2.0, 0.0, 0.0),
+            4,
+            Tuple::vector(0.0, 0.0, 2.0),
+            4,
+            Color::white(),
+        );
+
+        let expected = light
+            .sample_points(position)
+            .iter()
+            .map(|point| 0.9 * (*point - position).normalize().y)
+            .sum::<F>()
+            / 16.0;
+        let actual_result = m.lighting(&light, position, eyev, normalv, 1.0);
+
+        assert_fuzzy_eq!(actual_result, Color::new(expected, expected, expected));
+        // Most of the light arrives at an angle
+        assert!(expected < 0.9 * 0.8);
+    }
+
+    #[test]
+    fn materials_of_different_kinds_are_not_fuzzy_equal() {
+        let phong = Material::from(Phong::default());
+        let blinn_phong = Material::from(BlinnPhong::default());
+        let cook_torrance = Material::from(CookTorrance::default());
+
+        assert_fuzzy_ne!(phong, blinn_phong.clone());
+        assert_fuzzy_ne!(blinn_phong, cook_torrance.clone());
+        assert_fuzzy_ne!(cook_torrance, phong);
+    }
+
+    #[test]
+    fn shared_properties_are_available_for_every_kind_of_material() {
+        let blinn_phong = Material::from(BlinnPhong {
+            reflective: 0.3,
+            ..BlinnPhong::default()
+        });
+        let cook_torrance = Material::from(CookTorrance {
+            transparency: 0.8,
+            refractive_index: 1.5,
+            ..CookTorrance::default()
+        });
+
+        assert_fuzzy_eq!(blinn_phong.reflective(), 0.3);
+        assert_fuzzy_eq!(cook_torrance.transparency(), 0.8);
+        assert_fuzzy_eq!(cook_torrance.refractive_index(), 1.5);
+    }
+
+    #[test]
+    fn blinn_phong_lighting_with_the_eye_between_the_light_and_the_surface() {
+        let m = BlinnPhong::default();
+        let position = Tuple::point(0.0, 0.0, 0.0);
+
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+
+        let actual_result = m.lighting(&light, position, eyev, normalv, 1.0);
+
+        let expected_result = Color::new(1.9, 1.9, 1.9);
+
+        assert_fuzzy_eq!(actual_result, expected_result);
+    }
+
+    #[test]
+    fn blinn_phong_lighting_with_the_eye_offset_by_45_degrees() {
+        let m = BlinnPhong::new(Color::white(), 0.1, 0.9, 0.9, 10.0);
+        let position = Tuple::point(0.0, 0.0, 0.0);
+
+        let sqrt2_over_2 = (2.0 as F).sqrt() / 2.0;
+        let eyev = Tuple::vector(0.0, sqrt2_over_2, -sqrt2_over_2);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+
+        let actual_result = m.lighting(&light, position, eyev, normalv, 1.0);
+
+        // The halfway vector is 22.5 degrees off the normal
+        let specular = 0.9 * (PI / 8.0).cos().powf(10.0);
+        let expected_result = Color::white() * (1.0 + specular);
+
+        assert_fuzzy_eq!(actual_result, expected_result);
+    }
+
+    #[test]
+    fn blinn_phong_lighting_with_light_behind_the_surface() {
+        let m = BlinnPhong::default();
+        let position = Tuple::point(0.0, 0.0, 0.0);
+
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Tuple::point(0.0, 0.0, 10.0), Color::new(1.0, 1.0, 1.0));
+
+        let actual_result = m.lighting(&light, position, eyev, normalv, 1.0);
+
+        assert_fuzzy_eq!(actual_result, Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn cook_torrance_lighting_of_a_dielectric_facing_the_eye_and_light() {
+        let m = CookTorrance::default();
+        let position = Tuple::point(0.0, 0.0, 0.0);
+
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+
+        let actual_result = m.lighting(&light, position, eyev, normalv, 1.0);
+
+        // ambient 0.1 + diffuse (1.0 - 0.04) + specular 0.04 / (4 * 0.25^2)
+        let expected_result = Color::new(1.22, 1.22, 1.22);
+
+        assert_fuzzy_eq!(actual_result, expected_result);
+    }
+
+    #[test]
+    fn cook_torrance_lighting_of_a_metal_facing_the_eye_and_light() {
+        let m = CookTorrance::new(Color::new(1.0, 0.5, 0.0), 0.1, 0.5, 1.0);
+        let position = Tuple::point(0.0, 0.0, 0.0);
+
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+
+        let actual_result = m.lighting(&light, position, eyev, normalv, 1.0);
+
+        // No diffuse part, the reflection is tinted by the color of the metal
+        let expected_result = Color::new(4.1, 2.05, 0.0);
+
+        assert_fuzzy_eq!(actual_result, expected_result);
+    }
+
+    #[test]
+    fn cook_torrance_lighting_with_light_behind_the_surface() {
+        let m = CookTorrance::with_color(Color::new(1.0, 0.5, 0.0));
+        let position = Tuple::point(0.0, 0.0, 0.0);
+
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Tuple::point(0.0, 0.0, 10.0), Color::new(1.0, 1.0, 1.0));
+
+        let actual_result = m.lighting(&light, position, eyev, normalv, 1.0);
+
+        assert_fuzzy_eq!(actual_result, Color::new(0.1, 0.05, 0.0));
+    }
+
+    #[test]
+    fn rougher_cook_torrance_surfaces_have_dimmer_highlights() {
+        let position = Tuple::point(0.0, 0.0, 0.0);
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+
+        let smooth = CookTorrance::new(Color::white(), 0.0, 0.3, 1.0);
+        let rough = CookTorrance::new(Color::white(), 0.0, 0.8, 1.0);
+
+        let smooth_result = smooth.lighting(&light, position, eyev, normalv, 1.0);
+        let rough_result = rough.lighting(&light, position, eyev, normalv, 1.0);
+
+        assert!(smooth_result.red > rough_result.red);
+    }
+
+    #[test]
+    fn cook_torrance_lighting_of_a_perfectly_smooth_surface_is_finite() {
+        let m = CookTorrance::new(Color::white(), 0.0, 0.0, 1.0);
+        let position = Tuple::point(0.0, 0.0, 0.0);
+
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+
+        let actual_result = m.lighting(&light, position, eyev, normalv, 1.0);
+
+        assert!(actual_result.red.is_finite());
+        assert!(actual_result.red > 0.0);
+    }
+
+    #[test]
+    fn cook_torrance_highlight_of_a_perfectly_smooth_surface_is_bounded() {
+        let m = CookTorrance::new(Color::white(), 0.0, 0.0, 1.0);
+        let position = Tuple::point(0.0, 0.0, 0.0);
+
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+
+        let actual_result = m.lighting(&light, position, eyev, normalv, 1.0);
+
+        // The distribution peaks at 1 / (PI * MIN_ALPHA^2), which the specular part scales by PI / 4
+        assert!(actual_result.red <= 1.0 / (4.0 * MIN_ALPHA.powi(2)));
+    }
 }
@@ -140,6 +140,7 @@ impl Matrix<3> {
     self.submatrix(row, column).determinant()
   }
 
+  #[allow(clippy::manual_is_multiple_of)]
   pub fn cofactor(&self, row: usize, column: usize) -> F {
     let minor = self.minor(row, column);
     if (row + column) % 2 == 0 {
@@ -210,6 +211,7 @@ impl Matrix<4> {
     self.submatrix(row, column).determinant()
   }
 
+  #[allow(clippy::manual_is_multiple_of)]
   pub fn cofactor(&self, row: usize, column: usize) -> F {
     let minor = self.minor(row, column);
     if (row + column) % 2 == 0 {
@@ -406,6 +408,7 @@ mod tests {
   }
 
   #[test]
+  #[allow(clippy::excessive_precision)]
   fn matrix_equality_with_identical_3x3_matrices() {
     let m1 = Matrix::from([
       [0.123456789, 1.0, 2.0],
@@ -423,6 +426,7 @@ mod tests {
   }
 
   #[test]
+  #[allow(clippy::excessive_precision)]
   fn matrix_equality_with_almost_identical_3x3_matrices() {
     let m1 = Matrix::from([
       [0.123456789, 1.0, 2.0],
@@ -439,6 +443,7 @@ mod tests {
   }
 
   #[test]
+  #[allow(clippy::excessive_precision)]
   fn matrix_equality_with_identical_4x4_matrices() {
     let m1 = Matrix::from([
       [0.123456789, 1.0, 2.0, 42.0],
@@ -457,6 +462,7 @@ mod tests {
   }
 
   #[test]
+  #[allow(clippy::excessive_precision)]
   fn matrix_equality_with_almost_identical_4x4_matrices() {
     let m1 = Matrix::from([
       [0.123456789, 1.0, 2.0, 42.0],
@@ -475,6 +481,7 @@ mod tests {
   }
 
   #[test]
+  #[allow(clippy::excessive_precision)]
   fn matrix_inequality_with_non_identical_4x4_matrices() {
     let m1 = Matrix::from([
       [0.123456789, 1.0, 2.0, 42.0],
@@ -0,0 +1,313 @@
+//! Transformations changing over the time the shutter of the camera is open, which blur
+//! moving bodies.
+
+use crate::matrix::Matrix;
+use crate::F;
+
+/// Transformation moving from `start` at time 0 to `end` at time 1.
+///
+/// Interpolating matrices element by element would squash rotating bodies, so both matrices
+/// are split into translation, rotation and scaling first. Translation and scaling are
+/// interpolated linearly, rotations along the shortest arc (slerp).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimatedTransform {
+    start: Matrix<4>,
+    end: Matrix<4>,
+    start_parts: Decomposition,
+    end_parts: Decomposition,
+}
+
+impl AnimatedTransform {
+    pub fn new(start: Matrix<4>, end: Matrix<4>) -> Self {
+        AnimatedTransform {
+            start,
+            end,
+            start_parts: Decomposition::new(start),
+            end_parts: Decomposition::new(end),
+        }
+    }
+
+    pub fn start(&self) -> Matrix<4> {
+        self.start
+    }
+
+    pub fn end(&self) -> Matrix<4> {
+        self.end
+    }
+
+    /// The transformation at the given time, clamped to `[0, 1]`.
+    pub fn at(&self, time: F) -> Matrix<4> {
+        if time <= 0.0 || self.start == self.end {
+            return self.start;
+        }
+        if time >= 1.0 {
+            return self.end;
+        }
+
+        let (start, end) = (&self.start_parts, &self.end_parts);
+        let lerp = |a: F, b: F| a + (b - a) * time;
+
+        let translation = Matrix::translation(
+            lerp(start.translation[0], end.translation[0]),
+            lerp(start.translation[1], end.translation[1]),
+            lerp(start.translation[2], end.translation[2]),
+        );
+        let rotation = start.rotation.slerp(end.rotation, time).to_matrix();
+
+        let mut scaling = Matrix::identity();
+        for row in 0..3 {
+            for column in 0..3 {
+                scaling[row][column] = lerp(start.scaling[row][column], end.scaling[row][column]);
+            }
+        }
+
+        translation * rotation * scaling
+    }
+}
+
+/// Matrix split into `translation * rotation * scaling`, where the scaling may also shear.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Decomposition {
+    translation: [F; 3],
+    rotation: Quaternion,
+    scaling: Matrix<4>,
+}
+
+impl Decomposition {
+    fn new(matrix: Matrix<4>) -> Self {
+        let translation = [matrix[0][3], matrix[1][3], matrix[2][3]];
+
+        let mut linear = matrix;
+        for row in 0..3 {
+            linear[row][3] = 0.0;
+        }
+
+        // Polar decomposition: averaging a matrix with its inverse transpose converges to the
+        // rotation closest to it
+        let mut rotation = linear;
+        for _ in 0..100 {
+            let inverse_transpose = rotation.inverse().transpose();
+            let mut next = rotation;
+            let mut change: F = 0.0;
+
+            for row in 0..3 {
+                for column in 0..3 {
+                    next[row][column] =
+                        0.5 * (rotation[row][column] + inverse_transpose[row][column]);
+                    change = change.max((next[row][column] - rotation[row][column]).abs());
+                }
+            }
+
+            rotation = next;
+            if change < 1e-12 {
+                break;
+            }
+        }
+
+        // A mirroring matrix leaves an improper rotation behind, flip it into the scaling
+        if rotation.determinant() < 0.0 {
+            for row in 0..3 {
+                for column in 0..3 {
+                    rotation[row][column] = -rotation[row][column];
+                }
+            }
+        }
+
+        Decomposition {
+            translation,
+            rotation: Quaternion::from_matrix(rotation),
+            scaling: rotation.inverse() * linear,
+        }
+    }
+}
+
+/// Unit quaternion representing a rotation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Quaternion {
+    w: F,
+    x: F,
+    y: F,
+    z: F,
+}
+
+impl Quaternion {
+    /// Converts a pure rotation matrix.
+    fn from_matrix(m: Matrix<4>) -> Self {
+        let trace = m[0][0] + m[1][1] + m[2][2];
+
+        // Start from the largest component, to avoid dividing by something close to zero
+        let quaternion = if trace > 0.0 {
+            let s = 2.0 * (trace + 1.0).sqrt();
+            Quaternion {
+                w: s / 4.0,
+                x: (m[2][1] - m[1][2]) / s,
+                y: (m[0][2] - m[2][0]) / s,
+                z: (m[1][0] - m[0][1]) / s,
+            }
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = 2.0 * (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt();
+            Quaternion {
+                w: (m[2][1] - m[1][2]) / s,
+                x: s / 4.0,
+                y: (m[0][1] + m[1][0]) / s,
+                z: (m[0][2] + m[2][0]) / s,
+            }
+        } else if m[1][1] > m[2][2] {
+            let s = 2.0 * (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt();
+            Quaternion {
+                w: (m[0][2] - m[2][0]) / s,
+                x: (m[0][1] + m[1][0]) / s,
+                y: s / 4.0,
+                z: (m[1][2] + m[2][1]) / s,
+            }
+        } else {
+            let s = 2.0 * (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt();
+            Quaternion {
+                w: (m[1][0] - m[0][1]) / s,
+                x: (m[0][2] + m[2][0]) / s,
+                y: (m[1][2] + m[2][1]) / s,
+                z: s / 4.0,
+            }
+        };
+
+        quaternion.normalize()
+    }
+
+    #[rustfmt::skip]
+    fn to_matrix(self) -> Matrix<4> {
+        let Quaternion { w, x, y, z } = self;
+
+        Matrix::from([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z),       2.0 * (x * z + w * y),       0.0],
+            [2.0 * (x * y + w * z),       1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x),       0.0],
+            [2.0 * (x * z - w * y),       2.0 * (y * z + w * x),       1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0,                         0.0,                         0.0,                         1.0],
+        ])
+    }
+
+    fn dot(self, other: Quaternion) -> F {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    fn scale(self, factor: F) -> Quaternion {
+        Quaternion {
+            w: self.w * factor,
+            x: self.x * factor,
+            y: self.y * factor,
+            z: self.z * factor,
+        }
+    }
+
+    fn add(self, other: Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w + other.w,
+            x: self.x + other.x,
+            y: self.y + other.y,
+            z: self.z + other.z,
+        }
+    }
+
+    fn normalize(self) -> Quaternion {
+        self.scale(1.0 / self.dot(self).sqrt())
+    }
+
+    /// Spherical linear interpolation, turning at a constant speed along the shorter way.
+    fn slerp(self, other: Quaternion, t: F) -> Quaternion {
+        // q and -q are the same rotation, pick the one closer to the start
+        let (other, cos) = match self.dot(other) {
+            cos if cos < 0.0 => (other.scale(-1.0), -cos),
+            cos => (other, cos),
+        };
+
+        if cos > 0.9995 {
+            // Nearly the same rotation, where linear interpolation is accurate enough
+            return self.scale(1.0 - t).add(other.scale(t)).normalize();
+        }
+
+        let angle = cos.acos();
+        let sin = angle.sin();
+
+        self.scale(((1.0 - t) * angle).sin() / sin)
+            .add(other.scale((t * angle).sin() / sin))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzy_eq::*;
+    use crate::tuple::Tuple;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn a_static_transform_stays_the_same() {
+        let m = Matrix::translation(1.0, 2.0, 3.0) * Matrix::rotation_x(0.5);
+        let animated = AnimatedTransform::new(m, m);
+
+        assert_fuzzy_eq!(animated.at(0.3), m);
+    }
+
+    #[test]
+    fn the_ends_are_the_start_and_end_transforms() {
+        let start = Matrix::translation(1.0, 0.0, 0.0);
+        let end = Matrix::rotation_z(PI / 2.0) * Matrix::scaling(2.0, 1.0, 1.0);
+        let animated = AnimatedTransform::new(start, end);
+
+        assert_fuzzy_eq!(animated.at(0.0), start);
+        assert_fuzzy_eq!(animated.at(1.0), end);
+        assert_fuzzy_eq!(animated.at(1.5), end);
+    }
+
+    #[test]
+    fn translations_are_interpolated_linearly() {
+        let animated = AnimatedTransform::new(
+            Matrix::translation(0.0, 0.0, 0.0),
+            Matrix::translation(4.0, -2.0, 0.0),
+        );
+
+        assert_fuzzy_eq!(animated.at(0.25), Matrix::translation(1.0, -0.5, 0.0));
+    }
+
+    #[test]
+    fn rotations_turn_at_a_constant_speed() {
+        let animated = AnimatedTransform::new(Matrix::identity(), Matrix::rotation_y(PI / 2.0));
+
+        assert_fuzzy_eq!(animated.at(0.5), Matrix::rotation_y(PI / 4.0));
+        assert_fuzzy_eq!(animated.at(0.2), Matrix::rotation_y(PI / 10.0));
+    }
+
+    #[test]
+    fn rotating_bodies_keep_their_size() {
+        let animated = AnimatedTransform::new(
+            Matrix::scaling(2.0, 2.0, 2.0),
+            Matrix::rotation_z(PI * 0.9) * Matrix::scaling(2.0, 2.0, 2.0),
+        );
+
+        let point = animated.at(0.5) * Tuple::point(1.0, 0.0, 0.0);
+
+        assert_fuzzy_eq!((point - Tuple::point(0.0, 0.0, 0.0)).magnitude(), 2.0);
+    }
+
+    #[test]
+    fn translation_rotation_and_scaling_are_interpolated_together() {
+        let start = Matrix::translation(1.0, 2.0, 3.0) * Matrix::scaling(1.0, 2.0, 3.0);
+        let end = Matrix::translation(3.0, 2.0, 1.0)
+            * Matrix::rotation_x(PI / 3.0)
+            * Matrix::scaling(3.0, 2.0, 1.0);
+        let animated = AnimatedTransform::new(start, end);
+
+        let expected = Matrix::translation(2.0, 2.0, 2.0)
+            * Matrix::rotation_x(PI / 6.0)
+            * Matrix::scaling(2.0, 2.0, 2.0);
+
+        assert_fuzzy_eq!(animated.at(0.5), expected);
+    }
+
+    #[test]
+    fn mirrored_transforms_survive_the_decomposition() {
+        let m = Matrix::rotation_y(0.4) * Matrix::scaling(-1.0, 2.0, 1.0);
+        let animated = AnimatedTransform::new(Matrix::identity(), m);
+
+        assert_fuzzy_eq!(animated.at(1.0 - 1e-12), m);
+    }
+}
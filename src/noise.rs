@@ -0,0 +1,141 @@
+use crate::tuple::Tuple;
+use crate::F;
+
+// Ken Perlin's reference permutation, repeated once so lookups never need to wrap.
+#[rustfmt::skip]
+const PERMUTATION: [usize; 256] = [
+    151, 160, 137,  91,  90,  15, 131,  13, 201,  95,  96,  53, 194, 233,   7, 225,
+    140,  36, 103,  30,  69, 142,   8,  99,  37, 240,  21,  10,  23, 190,   6, 148,
+    247, 120, 234,  75,   0,  26, 197,  62,  94, 252, 219, 203, 117,  35,  11,  32,
+     57, 177,  33,  88, 237, 149,  56,  87, 174,  20, 125, 136, 171, 168,  68, 175,
+     74, 165,  71, 134, 139,  48,  27, 166,  77, 146, 158, 231,  83, 111, 229, 122,
+     60, 211, 133, 230, 220, 105,  92,  41,  55,  46, 245,  40, 244, 102, 143,  54,
+     65,  25,  63, 161,   1, 216,  80,  73, 209,  76, 132, 187, 208,  89,  18, 169,
+    200, 196, 135, 130, 116, 188, 159,  86, 164, 100, 109, 198, 173, 186,   3,  64,
+     52, 217, 226, 250, 124, 123,   5, 202,  38, 147, 118, 126, 255,  82,  85, 212,
+    207, 206,  59, 227,  47,  16,  58,  17, 182, 189,  28,  42, 223, 183, 170, 213,
+    119, 248, 152,   2,  44, 154, 163,  70, 221, 153, 101, 155, 167,  43, 172,   9,
+    129,  22,  39, 253,  19,  98, 108, 110,  79, 113, 224, 232, 178, 185, 112, 104,
+    218, 246,  97, 228, 251,  34, 242, 193, 238, 210, 144,  12, 191, 179, 162, 241,
+     81,  51, 145, 235, 249,  14, 239, 107,  49, 192, 214,  31, 181, 199, 106, 157,
+    184,  84, 204, 176, 115, 121,  50,  45, 127,   4, 150, 254, 138, 236, 205,  93,
+    222, 114,  67,  29,  24,  72, 243, 141, 128, 195,  78,  66, 215,  61, 156, 180,
+];
+
+fn hash(index: usize) -> usize {
+    PERMUTATION[index & 255]
+}
+
+fn fade(t: F) -> F {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: F, a: F, b: F) -> F {
+    a + t * (b - a)
+}
+
+fn gradient(hash: usize, x: F, y: F, z: F) -> F {
+    // Pick one of the 12 edge directions of a cube from the lower 4 bits of the hash.
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+
+    let u = if h & 1 == 0 { u } else { -u };
+    let v = if h & 2 == 0 { v } else { -v };
+
+    u + v
+}
+
+/// Improved Perlin noise evaluated at the given point.
+///
+/// Returns values roughly in `[-1, 1]`, and exactly `0` on integer lattice points.
+pub fn perlin(point: Tuple) -> F {
+    let (floor_x, floor_y, floor_z) = (point.x.floor(), point.y.floor(), point.z.floor());
+
+    // Lattice cell containing the point, wrapped to the permutation table size.
+    let xi = (floor_x as i64 & 255) as usize;
+    let yi = (floor_y as i64 & 255) as usize;
+    let zi = (floor_z as i64 & 255) as usize;
+
+    // Position of the point inside of its cell
+    let x = point.x - floor_x;
+    let y = point.y - floor_y;
+    let z = point.z - floor_z;
+
+    let u = fade(x);
+    let v = fade(y);
+    let w = fade(z);
+
+    let a = hash(xi) + yi;
+    let aa = hash(a) + zi;
+    let ab = hash(a + 1) + zi;
+    let b = hash(xi + 1) + yi;
+    let ba = hash(b) + zi;
+    let bb = hash(b + 1) + zi;
+
+    lerp(
+        w,
+        lerp(
+            v,
+            lerp(u, gradient(hash(aa), x, y, z), gradient(hash(ba), x - 1.0, y, z)),
+            lerp(u, gradient(hash(ab), x, y - 1.0, z), gradient(hash(bb), x - 1.0, y - 1.0, z)),
+        ),
+        lerp(
+            v,
+            lerp(
+                u,
+                gradient(hash(aa + 1), x, y, z - 1.0),
+                gradient(hash(ba + 1), x - 1.0, y, z - 1.0),
+            ),
+            lerp(
+                u,
+                gradient(hash(ab + 1), x, y - 1.0, z - 1.0),
+                gradient(hash(bb + 1), x - 1.0, y - 1.0, z - 1.0),
+            ),
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzy_eq::*;
+
+    #[test]
+    fn perlin_noise_is_zero_on_lattice_points() {
+        assert_fuzzy_eq!(perlin(Tuple::point(0.0, 0.0, 0.0)), 0.0);
+        assert_fuzzy_eq!(perlin(Tuple::point(1.0, 2.0, 3.0)), 0.0);
+        assert_fuzzy_eq!(perlin(Tuple::point(-4.0, 7.0, -2.0)), 0.0);
+    }
+
+    #[test]
+    fn perlin_noise_is_deterministic() {
+        let p = Tuple::point(0.3, 1.7, -2.2);
+
+        assert_fuzzy_eq!(perlin(p), perlin(p));
+    }
+
+    #[test]
+    fn perlin_noise_stays_within_bounds() {
+        for i in 0..1000 {
+            let t = i as F * 0.173;
+            let n = perlin(Tuple::point(t, t * 0.7 - 3.0, t * 1.3 + 5.0));
+
+            assert!((-1.0..=1.0).contains(&n));
+        }
+    }
+
+    #[test]
+    fn perlin_noise_varies_between_lattice_points() {
+        let n1 = perlin(Tuple::point(0.5, 0.5, 0.5));
+        let n2 = perlin(Tuple::point(1.5, 0.5, 0.5));
+
+        assert_fuzzy_ne!(n1, n2);
+    }
+}
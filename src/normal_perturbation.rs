@@ -0,0 +1,305 @@
+use std::sync::Arc;
+
+use crate::body::*;
+use crate::fuzzy_eq::*;
+use crate::noise::perlin;
+use crate::texture::Texture;
+use crate::tuple::Tuple;
+use crate::F;
+
+// Step used for the central differences when estimating the height field gradient.
+const GRADIENT_DELTA: F = 0.0001;
+
+/// Functions which are used to tilt the surface normal of a body, without touching
+/// its geometry. This allows rough or wavy looking surfaces on otherwise smooth bodies.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NormalPerturbation {
+    /// Overlapping sine waves along every axis, e.g. for ripples on water surfaces.
+    Sine { frequency: F, amplitude: F },
+    /// Perlin noise based bumps, e.g. for rough stone.
+    Noise { scale: F, amplitude: F },
+    /// Tangent space normals encoded as RGB colors, looked up through the texture
+    /// coordinates of the body.
+    NormalMap(Arc<Texture>),
+    /// Heights stored as the brightness of a texture, looked up through the texture
+    /// coordinates of the body. The normal tilts away from where the heights rise, the more
+    /// so the higher the amplitude.
+    HeightMap { texture: Arc<Texture>, amplitude: F },
+}
+
+impl NormalPerturbation {
+    pub fn sine(frequency: F, amplitude: F) -> Self {
+        NormalPerturbation::Sine {
+            frequency,
+            amplitude,
+        }
+    }
+
+    pub fn noise(scale: F, amplitude: F) -> Self {
+        NormalPerturbation::Noise { scale, amplitude }
+    }
+
+    pub fn normal_map(texture: Arc<Texture>) -> Self {
+        NormalPerturbation::NormalMap(texture)
+    }
+
+    pub fn height_map(texture: Arc<Texture>, amplitude: F) -> Self {
+        NormalPerturbation::HeightMap { texture, amplitude }
+    }
+
+    /// Tilts the normal of the given body at the given point.
+    pub fn perturb(&self, body: &Body, point: Tuple, normal: Tuple) -> Tuple {
+        match *self {
+            NormalPerturbation::NormalMap(ref texture) => {
+                let (u, v) = body.uv(point);
+                let encoded = texture.sample(u, v);
+                // Colors in [0, 1] encode normal components in [-1, 1]
+                let tangent_space_normal = Tuple::vector(
+                    encoded.red * 2.0 - 1.0,
+                    encoded.green * 2.0 - 1.0,
+                    encoded.blue * 2.0 - 1.0,
+                );
+
+                let (tangent, bitangent) = tangent_frame(body, point, normal);
+
+                (tangent * tangent_space_normal.x
+                    + bitangent * tangent_space_normal.y
+                    + normal * tangent_space_normal.z)
+                    .normalize()
+            }
+            NormalPerturbation::HeightMap {
+                ref texture,
+                amplitude,
+            } => {
+                let (u, v) = body.uv(point);
+                let height = |u: F, v: F| {
+                    let color = texture.sample(u, v);
+                    (color.red + color.green + color.blue) / 3.0
+                };
+
+                // Central differences one texel apart, the finest detail the texture has
+                let du = 1.0 / texture.width as F;
+                let dv = 1.0 / texture.height as F;
+                let slope_u = (height(u + du, v) - height(u - du, v)) / (2.0 * du);
+                let slope_v = (height(u, v + dv) - height(u, v - dv)) / (2.0 * dv);
+
+                let (tangent, bitangent) = tangent_frame(body, point, normal);
+
+                (normal - (tangent * slope_u + bitangent * slope_v) * amplitude).normalize()
+            }
+            NormalPerturbation::Sine { .. } | NormalPerturbation::Noise { .. } => {
+                let gradient = self.gradient(point);
+                // Only the part of the slope, which lies in the tangent plane, tilts the normal.
+                let tangential_gradient = gradient - normal * gradient.dot(normal);
+
+                (normal - tangential_gradient).normalize()
+            }
+        }
+    }
+
+    /// Slope of the height field described by the procedural perturbations.
+    fn gradient(&self, point: Tuple) -> Tuple {
+        match *self {
+            NormalPerturbation::Sine {
+                frequency,
+                amplitude,
+            } => {
+                Tuple::vector(
+                    (point.x * frequency).cos(),
+                    (point.y * frequency).cos(),
+                    (point.z * frequency).cos(),
+                ) * frequency
+                    * amplitude
+            }
+            NormalPerturbation::Noise { scale, amplitude } => {
+                let height = |p: Tuple| perlin(p * scale);
+
+                let dx = Tuple::vector(GRADIENT_DELTA, 0.0, 0.0);
+                let dy = Tuple::vector(0.0, GRADIENT_DELTA, 0.0);
+                let dz = Tuple::vector(0.0, 0.0, GRADIENT_DELTA);
+
+                Tuple::vector(
+                    height(point + dx) - height(point - dx),
+                    height(point + dy) - height(point - dy),
+                    height(point + dz) - height(point - dz),
+                ) * (amplitude / (2.0 * GRADIENT_DELTA))
+            }
+            // Normal maps store the tilted normals directly, and height maps are looked up
+            // through the texture coordinates rather than the point
+            NormalPerturbation::NormalMap(_) | NormalPerturbation::HeightMap { .. } => {
+                Tuple::vector(0.0, 0.0, 0.0)
+            }
+        }
+    }
+}
+
+/// Tangent and bitangent of the body at the given point, made perpendicular to the normal and
+/// to each other. The tangents follow the texture coordinates and are not necessarily
+/// perpendicular to the given normal on their own.
+fn tangent_frame(body: &Body, point: Tuple, normal: Tuple) -> (Tuple, Tuple) {
+    let (tangent, bitangent) = body.tangents(point);
+    let tangent = (tangent - normal * tangent.dot(normal)).normalize();
+    let bitangent =
+        (bitangent - normal * bitangent.dot(normal) - tangent * bitangent.dot(tangent)).normalize();
+
+    (tangent, bitangent)
+}
+
+impl FuzzyEq<NormalPerturbation> for NormalPerturbation {
+    fn fuzzy_eq(&self, other: NormalPerturbation) -> bool {
+        match (self, other) {
+            (
+                NormalPerturbation::Sine {
+                    frequency,
+                    amplitude,
+                },
+                NormalPerturbation::Sine {
+                    frequency: other_frequency,
+                    amplitude: other_amplitude,
+                },
+            ) => frequency.fuzzy_eq(other_frequency) && amplitude.fuzzy_eq(other_amplitude),
+            (
+                NormalPerturbation::Noise { scale, amplitude },
+                NormalPerturbation::Noise {
+                    scale: other_scale,
+                    amplitude: other_amplitude,
+                },
+            ) => scale.fuzzy_eq(other_scale) && amplitude.fuzzy_eq(other_amplitude),
+            (NormalPerturbation::NormalMap(texture), NormalPerturbation::NormalMap(other)) => {
+                *texture == other
+            }
+            (
+                NormalPerturbation::HeightMap { texture, amplitude },
+                NormalPerturbation::HeightMap {
+                    texture: other_texture,
+                    amplitude: other_amplitude,
+                },
+            ) => *texture == other_texture && amplitude.fuzzy_eq(other_amplitude),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Color;
+    use crate::sphere::Sphere;
+    use std::f64::consts::PI;
+
+    fn sphere() -> Body {
+        Body::from(Sphere::new(None))
+    }
+
+    fn uniform_normal_map(color: Color) -> NormalPerturbation {
+        NormalPerturbation::normal_map(Arc::new(Texture::new(1, 1, vec![color])))
+    }
+
+    #[test]
+    fn sine_perturbation_keeps_normal_at_a_wave_crest() {
+        let perturbation = NormalPerturbation::sine(1.0, 0.5);
+        // cos(PI / 2) == 0 along each axis, so the height field is flat here
+        let point = Tuple::point(PI / 2.0, PI / 2.0, PI / 2.0);
+        let normal = Tuple::vector(0.0, 1.0, 0.0);
+
+        assert_fuzzy_eq!(perturbation.perturb(&sphere(), point, normal), normal);
+    }
+
+    #[test]
+    fn sine_perturbation_tilts_normal_on_a_slope() {
+        let perturbation = NormalPerturbation::sine(1.0, 0.5);
+        let point = Tuple::point(0.0, 0.0, 0.0);
+        let normal = Tuple::vector(0.0, 1.0, 0.0);
+
+        let expected_result = Tuple::vector(-0.5, 1.0, -0.5).normalize();
+
+        assert_fuzzy_eq!(perturbation.perturb(&sphere(), point, normal), expected_result);
+    }
+
+    #[test]
+    fn noise_perturbation_tilts_the_normal() {
+        let perturbation = NormalPerturbation::noise(4.0, 0.2);
+        let point = Tuple::point(0.31, 1.0, 0.47);
+        let normal = Tuple::vector(0.0, 1.0, 0.0);
+
+        assert_fuzzy_ne!(perturbation.perturb(&sphere(), point, normal), normal);
+    }
+
+    #[test]
+    fn perturbed_normal_is_normalized() {
+        let perturbation = NormalPerturbation::noise(4.0, 0.2);
+        let point = Tuple::point(0.31, 1.0, 0.47);
+        let normal = Tuple::vector(0.0, 1.0, 0.0);
+
+        let n = perturbation.perturb(&sphere(), point, normal);
+
+        assert_fuzzy_eq!(n.magnitude(), 1.0);
+        assert!(n.is_vector());
+    }
+
+    #[test]
+    fn flat_normal_map_keeps_the_normal() {
+        let perturbation = uniform_normal_map(Color::new(0.5, 0.5, 1.0));
+        let point = Tuple::point(0.0, 0.0, -1.0);
+        let normal = Tuple::vector(0.0, 0.0, -1.0);
+
+        assert_fuzzy_eq!(perturbation.perturb(&sphere(), point, normal), normal);
+    }
+
+    #[test]
+    fn normal_map_tilts_the_normal_along_the_tangent() {
+        let perturbation = uniform_normal_map(Color::new(1.0, 0.5, 0.5));
+        let point = Tuple::point(0.0, 0.0, -1.0);
+        let normal = Tuple::vector(0.0, 0.0, -1.0);
+
+        // u grows towards +x on the front side of the sphere
+        let expected_result = Tuple::vector(1.0, 0.0, 0.0);
+
+        assert_fuzzy_eq!(perturbation.perturb(&sphere(), point, normal), expected_result);
+    }
+
+    #[test]
+    fn normal_map_tilts_the_normal_along_the_bitangent() {
+        let perturbation = uniform_normal_map(Color::new(0.5, 1.0, 1.0));
+        let point = Tuple::point(0.0, 0.0, -1.0);
+        let normal = Tuple::vector(0.0, 0.0, -1.0);
+
+        // v grows towards the north pole
+        let expected_result = Tuple::vector(0.0, 1.0, -1.0).normalize();
+
+        assert_fuzzy_eq!(perturbation.perturb(&sphere(), point, normal), expected_result);
+    }
+
+    #[test]
+    fn flat_height_map_keeps_the_normal() {
+        let texture = Arc::new(Texture::new(1, 1, vec![Color::new(0.5, 0.5, 0.5)]));
+        let perturbation = NormalPerturbation::height_map(texture, 1.0);
+        let point = Tuple::point(0.0, 0.0, -1.0);
+        let normal = Tuple::vector(0.0, 0.0, -1.0);
+
+        assert_fuzzy_eq!(perturbation.perturb(&sphere(), point, normal), normal);
+    }
+
+    #[test]
+    fn height_map_tilts_the_normal_away_from_rising_heights() {
+        // Heights rise from the bottom to the top row, i.e. towards the north pole
+        let texture = Arc::new(Texture::new(
+            1,
+            3,
+            vec![Color::white(), Color::new(0.5, 0.5, 0.5), Color::black()],
+        ));
+        let perturbation = NormalPerturbation::height_map(texture, 2.0 / 3.0);
+        let point = Tuple::point(0.0, 0.0, -1.0);
+        let normal = Tuple::vector(0.0, 0.0, -1.0);
+
+        // The height grows by 1.5 per unit of v, which the amplitude scales down to 1.0
+        let expected_result = Tuple::vector(0.0, -1.0, -1.0).normalize();
+
+        assert_fuzzy_eq!(perturbation.perturb(&sphere(), point, normal), expected_result);
+    }
+
+    #[test]
+    fn different_kinds_of_perturbations_are_not_fuzzy_equal() {
+        assert_fuzzy_ne!(NormalPerturbation::sine(1.0, 0.5), NormalPerturbation::noise(1.0, 0.5));
+    }
+}
@@ -0,0 +1,505 @@
+use crate::aov::RenderBuffers;
+use crate::body::*;
+use crate::bsdf::*;
+use crate::camera::Camera;
+use crate::canvas::{Canvas, Color};
+use crate::computed_intersection::ComputedIntersection;
+use crate::emitter::Emitter;
+use crate::environment::Background;
+use crate::random::Rng;
+use crate::ray::Ray;
+use crate::sampling::power_heuristic;
+use crate::world::World;
+use crate::F;
+
+/// Monte Carlo path tracer, which simulates global illumination by following random light
+/// paths through the world.
+///
+/// Only emissive bodies and the background light the world here, the `lights` of the world
+/// are ignored, as they are not part of the geometry random paths could hit. Unless turned
+/// off, glowing bodies and environment maps are also aimed at directly (next event
+/// estimation), which makes small or bright lights far less noisy.
+#[derive(Debug, Clone, Copy)]
+pub struct PathTracer {
+    /// Paths per pixel, used instead of the camera's samples per pixel. They are spread
+    /// across the camera's filter just the same.
+    pub samples_per_pixel: usize,
+    /// Bounces after which a path is cut off, no matter what
+    pub max_depth: usize,
+    /// Bounces after which paths may be terminated early by Russian roulette
+    pub russian_roulette_depth: usize,
+    /// Whether to send shadow rays towards glowing bodies at every bounce, combining them
+    /// with the scattered rays by multiple importance sampling
+    pub next_event_estimation: bool,
+    pub seed: u64,
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        PathTracer {
+            samples_per_pixel: 64,
+            max_depth: 16,
+            russian_roulette_depth: 3,
+            next_event_estimation: true,
+            seed: 0,
+        }
+    }
+}
+
+impl PathTracer {
+    pub fn new(samples_per_pixel: usize) -> Self {
+        PathTracer {
+            samples_per_pixel,
+            ..Self::default()
+        }
+    }
+
+    pub fn render(&self, camera: &Camera, world: &World) -> Canvas {
+        let emitters = PathTracer::emitters(world);
+
+        camera.render_samples(self.samples_per_pixel, self.seed, |ray, rng| {
+            self.radiance(world, &emitters, ray, rng)
+        })
+    }
+
+    /// The glowing bodies of the world, which next event estimation aims at. They are the
+    /// same for every path, so collect them once per render and hand them to `radiance`.
+    pub fn emitters(world: &World) -> Vec<&Body> {
+        world
+            .bodies
+            .iter()
+            .filter(|body| is_glowing(body.material().emissive()))
+            .collect()
+    }
+
+    /// Renders the world together with the extra buffers compositors and denoisers need.
+    pub fn render_with_aovs(&self, camera: &Camera, world: &World) -> RenderBuffers {
+        let emitters = PathTracer::emitters(world);
+
+        RenderBuffers::render(camera, world, self.samples_per_pixel, self.seed, |ray, rng| {
+            self.radiance(world, &emitters, ray, rng)
+        })
+    }
+
+    /// Estimates the light arriving along the given ray, following a single random path.
+    /// `emitters` are the glowing bodies of the world, as collected by `emitters`.
+    pub fn radiance(&self, world: &World, emitters: &[&Body], ray: Ray, rng: &mut Rng) -> Color {
+        let mut radiance = Color::black();
+        // How much of the light found further along the path reaches the camera
+        let mut throughput = Color::white();
+        let mut ray = ray;
+        // Density of the direction the path continued in, in case it hits a glowing body or
+        // escapes into the environment. `None` when light sampling could not have found the
+        // same path.
+        let mut scatter_pdf: Option<F> = None;
+
+        for depth in 0..self.max_depth {
+            let intersections = world.intersect(ray);
+            let hit = match intersections.hit() {
+                Some(hit) => hit,
+                None => {
+                    radiance = radiance + throughput * self.background(world, ray, scatter_pdf);
+                    break;
+                }
+            };
+
+            let computed = hit.get_computed(ray, &intersections);
+            let material = computed.body.material();
+
+            let emissive = material.emissive();
+            if is_glowing(emissive) {
+                let weight = match scatter_pdf {
+                    Some(pdf) if self.next_event_estimation => {
+                        let light_pdf = computed.body.pdf(ray.origin, computed.position)
+                            / emitters.len() as F;
+
+                        power_heuristic(pdf, light_pdf)
+                    }
+                    _ => 1.0,
+                };
+
+                radiance = radiance + throughput * emissive * weight;
+            }
+
+            let bsdf = Bsdf::choose(&material, rng);
+
+            // Light arriving directly from a light source would be the next vertex
+            if self.next_event_estimation && !bsdf.is_specular() && depth + 1 < self.max_depth {
+                let direct = self.sample_emitters(world, emitters, &computed, &bsdf, rng)
+                    + self.sample_environment(world, &computed, &bsdf, rng);
+
+                radiance = radiance + throughput * direct;
+            }
+
+            let sample = match bsdf.sample(&computed, rng) {
+                Some(sample) => sample,
+                None => break,
+            };
+
+            throughput = throughput * sample.weight;
+            ray = sample.ray;
+            scatter_pdf = if bsdf.is_specular() {
+                None
+            } else {
+                Some(sample.pdf)
+            };
+
+            if depth >= self.russian_roulette_depth {
+                // Randomly stop paths which carry little light anymore, and boost the
+                // surviving ones to make up for it.
+                let survival = throughput
+                    .red
+                    .max(throughput.green)
+                    .max(throughput.blue)
+                    .clamp(0.05, 0.95);
+
+                if rng.next_f64() >= survival {
+                    break;
+                }
+
+                throughput = throughput * (1.0 / survival);
+            }
+        }
+
+        radiance
+    }
+
+    /// Light scattered towards the eye from a random point on a random glowing body, weighted
+    /// against finding the same light by scattering.
+    fn sample_emitters(
+        &self,
+        world: &World,
+        emitters: &[&Body],
+        computed: &ComputedIntersection,
+        bsdf: &Bsdf,
+        rng: &mut Rng,
+    ) -> Color {
+        if emitters.is_empty() {
+            return Color::black();
+        }
+
+        let index = ((rng.next_f64() * emitters.len() as F) as usize).min(emitters.len() - 1);
+        // Moving emitters are aimed at where they are at the time of the path
+        let moved;
+        let emitter = if emitters[index].is_moving() {
+            moved = emitters[index].at_time(computed.time);
+            &moved
+        } else {
+            emitters[index]
+        };
+
+        let sample = match emitter.sample_toward(computed.over_point, rng) {
+            Some(sample) => sample,
+            None => return Color::black(),
+        };
+
+        let scattered = bsdf.eval(computed, sample.direction);
+        if !is_glowing(scattered)
+            || world.is_occluded(computed.over_point, sample.point, computed.time)
+        {
+            return Color::black();
+        }
+
+        let light_pdf = sample.pdf / emitters.len() as F;
+        let weight = power_heuristic(light_pdf, bsdf.pdf(computed, sample.direction));
+
+        scattered * emitter.material().emissive() * (weight / light_pdf)
+    }
+
+    /// Light scattered towards the eye from a bright direction of the environment map,
+    /// weighted against finding the same light by scattering.
+    fn sample_environment(
+        &self,
+        world: &World,
+        computed: &ComputedIntersection,
+        bsdf: &Bsdf,
+        rng: &mut Rng,
+    ) -> Color {
+        let environment = match world.background {
+            Background::Environment(ref environment) => environment,
+            Background::Color(_) | Background::Sky(_) => return Color::black(),
+        };
+
+        let sample = match environment.sample(rng) {
+            Some(sample) => sample,
+            None => return Color::black(),
+        };
+
+        let scattered = bsdf.eval(computed, sample.direction);
+        if !is_glowing(scattered)
+            || world.is_occluded_along(
+                computed.over_point,
+                sample.direction,
+                F::INFINITY,
+                computed.time,
+            )
+        {
+            return Color::black();
+        }
+
+        let weight = power_heuristic(sample.pdf, bsdf.pdf(computed, sample.direction));
+
+        scattered * sample.radiance * (weight / sample.pdf)
+    }
+
+    /// Light from the background, for a path which escaped the world along `ray`.
+    fn background(&self, world: &World, ray: Ray, scatter_pdf: Option<F>) -> Color {
+        let radiance = world.background.radiance(ray.direction);
+
+        let weight = match (&world.background, scatter_pdf) {
+            (Background::Environment(ref environment), Some(pdf)) if self.next_event_estimation => {
+                power_heuristic(pdf, environment.pdf(ray.direction))
+            }
+            _ => 1.0,
+        };
+
+        radiance * weight
+    }
+}
+
+fn is_glowing(color: Color) -> bool {
+    color.red > 0.0 || color.green > 0.0 || color.blue > 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::EnvironmentMap;
+    use crate::fuzzy_eq::*;
+    use crate::material::*;
+    use crate::matrix::Matrix;
+    use crate::sphere::Sphere;
+    use crate::texture::Texture;
+    use crate::triangle::Triangle;
+    use crate::tuple::Tuple;
+    use std::f64::consts::PI;
+    use std::sync::Arc;
+
+    fn glowing_sphere(emissive: Color, diffuse: F) -> Body {
+        Body::from(Sphere::with_material(
+            Material::from(Phong {
+                diffuse,
+                emissive,
+                ..Phong::default()
+            }),
+            None,
+        ))
+    }
+
+    #[test]
+    fn radiance_is_black_when_the_ray_misses() {
+        let w = World::new(vec![glowing_sphere(Color::white(), 0.9)], vec![]);
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 1.0, 0.0));
+        let mut rng = Rng::new(0);
+
+        assert_fuzzy_eq!(
+            PathTracer::default().radiance(&w, &PathTracer::emitters(&w), r, &mut rng),
+            Color::black()
+        );
+    }
+
+    #[test]
+    fn radiance_of_an_emissive_body_seen_directly() {
+        let emissive = Color::new(0.8, 0.4, 0.2);
+        let w = World::new(vec![glowing_sphere(emissive, 0.9)], vec![]);
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut rng = Rng::new(0);
+
+        // Every bounce off the convex sphere escapes into the black void
+        assert_fuzzy_eq!(
+            PathTracer::default().radiance(&w, &PathTracer::emitters(&w), r, &mut rng),
+            emissive
+        );
+    }
+
+    #[test]
+    fn radiance_inside_of_a_glowing_diffuse_sphere_converges() {
+        // Each bounce adds the emission and halves the throughput: 0.5 + 0.25 + ... = 1.0
+        let w = World::new(vec![glowing_sphere(Color::new(0.5, 0.5, 0.5), 0.5)], vec![]);
+        let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let path_tracer = PathTracer {
+            max_depth: 64,
+            ..PathTracer::default()
+        };
+        let emitters = PathTracer::emitters(&w);
+        let mut rng = Rng::new(0);
+        let samples = 4000;
+
+        let sum = (0..samples).fold(Color::black(), |sum, _| {
+            sum + path_tracer.radiance(&w, &emitters, r, &mut rng)
+        });
+        let mean = sum * (1.0 / samples as F);
+
+        assert!((mean.red - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn paths_are_cut_off_at_the_maximum_depth() {
+        let w = World::new(vec![glowing_sphere(Color::new(0.5, 0.5, 0.5), 0.5)], vec![]);
+        let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let path_tracer = PathTracer {
+            max_depth: 1,
+            ..PathTracer::default()
+        };
+        let mut rng = Rng::new(0);
+
+        assert_fuzzy_eq!(
+            path_tracer.radiance(&w, &PathTracer::emitters(&w), r, &mut rng),
+            Color::new(0.5, 0.5, 0.5)
+        );
+    }
+
+    #[test]
+    fn rendering_is_reproducible() {
+        let w = World::new(
+            vec![
+                glowing_sphere(Color::new(1.0, 1.0, 1.0), 0.9),
+                Body::from(Sphere::new(Some(Matrix::translation(1.0, 0.0, 1.0)))),
+            ],
+            vec![],
+        );
+        let c = Camera::new(6, 4, PI / 2.0).with_transform(Matrix::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ));
+        let path_tracer = PathTracer::new(4);
+
+        let first = path_tracer.render(&c, &w);
+        let second = path_tracer.render(&c, &w);
+
+        for y in 0..4 {
+            for x in 0..6 {
+                assert_fuzzy_eq!(first.pixel_at(x, y), second.pixel_at(x, y));
+            }
+        }
+    }
+
+    // Diffuse floor at y = 0, lit by a small glowing sphere hovering above it
+    fn small_light_scene() -> World {
+        let floor = Body::from(Triangle::with_material(
+            Material::from(Phong::with_color(Color::new(0.8, 0.8, 0.8))),
+            Tuple::point(-100.0, 0.0, -100.0),
+            Tuple::point(0.0, 0.0, 100.0),
+            Tuple::point(100.0, 0.0, -100.0),
+        ));
+        let lamp = Body::from(Sphere::with_material(
+            Material::from(Phong {
+                emissive: Color::new(8.0, 6.0, 4.0),
+                ..Phong::default()
+            }),
+            Some(Matrix::translation(0.5, 2.0, 0.0) * Matrix::scaling(0.4, 0.4, 0.4)),
+        ));
+
+        World::new(vec![floor, lamp], vec![])
+    }
+
+    // Mean and variance of the red channel
+    fn estimate(path_tracer: PathTracer, world: &World, ray: Ray, samples: usize) -> (F, F) {
+        let emitters = PathTracer::emitters(world);
+        let mut rng = Rng::new(17);
+
+        let values: Vec<F> = (0..samples)
+            .map(|_| path_tracer.radiance(world, &emitters, ray, &mut rng).red)
+            .collect();
+        let mean = values.iter().sum::<F>() / samples as F;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<F>() / samples as F;
+
+        (mean, variance)
+    }
+
+    #[test]
+    fn next_event_estimation_converges_to_the_same_mean_with_less_noise() {
+        let w = small_light_scene();
+        let r = Ray::new(Tuple::point(0.0, 1.0, -3.0), Tuple::vector(0.0, -1.0, 3.0).normalize());
+        let naive = PathTracer {
+            next_event_estimation: false,
+            ..PathTracer::default()
+        };
+        let samples = 40000;
+
+        let (naive_mean, naive_variance) = estimate(naive, &w, r, samples);
+        let (mean, variance) = estimate(PathTracer::default(), &w, r, samples);
+
+        assert!(naive_mean > 0.1);
+        assert!((mean - naive_mean).abs() < 0.03 * naive_mean.max(1.0));
+        assert!(variance * 10.0 < naive_variance);
+    }
+
+    #[test]
+    fn glowing_bodies_are_not_counted_twice_with_next_event_estimation() {
+        let w = small_light_scene();
+        let r = Ray::new(Tuple::point(0.0, 1.0, -3.0), Tuple::vector(0.0, -1.0, 3.0).normalize());
+        let direct_only = |next_event_estimation| PathTracer {
+            max_depth: 2,
+            next_event_estimation,
+            ..PathTracer::default()
+        };
+
+        let (naive_mean, _) = estimate(direct_only(false), &w, r, 40000);
+        let (mean, _) = estimate(direct_only(true), &w, r, 40000);
+
+        assert!((mean - naive_mean).abs() < 0.03 * naive_mean.max(1.0));
+    }
+
+    fn environment(bright_spot: F) -> Background {
+        // Dim sky above, a small sun towards +x and dark ground below
+        let pixels = (0..16)
+            .flat_map(|y| {
+                (0..32).map(move |x| match (x, y) {
+                    (24, 5) => Color::new(bright_spot, bright_spot, bright_spot),
+                    (_, 0..=7) => Color::new(0.2, 0.3, 0.5),
+                    _ => Color::new(0.1, 0.1, 0.1),
+                })
+            })
+            .collect();
+
+        Background::from(EnvironmentMap::new(Arc::new(Texture::new(32, 16, pixels))))
+    }
+
+    #[test]
+    fn a_diffuse_sphere_in_a_uniform_environment_reflects_its_albedo() {
+        let uniform = Texture::new(1, 1, vec![Color::white()]);
+        let w = World::new(vec![glowing_sphere(Color::black(), 0.5)], vec![])
+            .with_background(Background::from(EnvironmentMap::new(Arc::new(uniform))));
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let (mean, _) = estimate(PathTracer::default(), &w, r, 2000);
+
+        assert!((mean - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn rays_escaping_the_world_see_the_background() {
+        let w = World::default().with_background(Background::from(Color::new(0.1, 0.2, 0.3)));
+        let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let mut rng = Rng::new(0);
+
+        assert_fuzzy_eq!(
+            PathTracer::default().radiance(&w, &PathTracer::emitters(&w), r, &mut rng),
+            Color::new(0.1, 0.2, 0.3)
+        );
+    }
+
+    #[test]
+    fn importance_sampling_the_environment_converges_to_the_same_mean_with_less_noise() {
+        let w = small_light_scene().with_background(environment(500.0));
+        let r = Ray::new(
+            Tuple::point(0.0, 1.0, -3.0),
+            Tuple::vector(0.0, -1.0, 3.0).normalize(),
+        );
+        let naive = PathTracer {
+            next_event_estimation: false,
+            ..PathTracer::default()
+        };
+        let samples = 40000;
+
+        let (naive_mean, naive_variance) = estimate(naive, &w, r, samples);
+        let (mean, variance) = estimate(PathTracer::default(), &w, r, samples);
+
+        // The naive estimate is still noisy, allow for four standard errors
+        let standard_error = ((naive_variance + variance) / samples as F).sqrt();
+        assert!((mean - naive_mean).abs() < 4.0 * standard_error);
+        assert!(variance * 10.0 < naive_variance);
+    }
+}
@@ -0,0 +1,323 @@
+//! Progressive rendering: the image is refined pass by pass, so a noisy preview is ready
+//! right away and gets cleaner for as long as one cares to wait.
+
+use std::fs::{remove_file, write};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::camera::Camera;
+use crate::canvas::to_png::ToPNG;
+use crate::canvas::{Canvas, Color};
+use crate::checkpoint::{Checkpoint, CheckpointError};
+use crate::random::Rng;
+use crate::ray::Ray;
+use crate::tile::Tile;
+use crate::F;
+
+/// Running sums of the samples taken for every pixel, which more samples can be added to at
+/// any time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accumulator {
+    pub width: usize,
+    pub height: usize,
+    /// Colors of the samples, weighted by the filter of the camera
+    pub(crate) sums: Vec<Color>,
+    pub(crate) weights: Vec<F>,
+    pub(crate) samples: Vec<usize>,
+}
+
+impl Accumulator {
+    pub fn new(width: usize, height: usize) -> Self {
+        Accumulator {
+            width,
+            height,
+            sums: vec![Color::black(); width * height],
+            weights: vec![0.0; width * height],
+            samples: vec![0; width * height],
+        }
+    }
+
+    /// Adds samples to the pixel, given as the sum of their weighted colors and the sum of
+    /// their weights.
+    pub fn add(&mut self, x: usize, y: usize, sum: Color, weight: F, samples: usize) {
+        let index = y * self.width + x;
+
+        self.sums[index] = self.sums[index] + sum;
+        self.weights[index] += weight;
+        self.samples[index] += samples;
+    }
+
+    /// Number of samples taken for the pixel so far.
+    pub fn samples_at(&self, x: usize, y: usize) -> usize {
+        self.samples[y * self.width + x]
+    }
+
+    /// The image as far as it got, black where no samples have been taken yet.
+    pub fn canvas(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.width, self.height);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let index = y * self.width + x;
+                if self.weights[index] != 0.0 {
+                    canvas.write_pixel(x, y, self.sums[index] * (1.0 / self.weights[index]));
+                }
+            }
+        }
+
+        canvas
+    }
+}
+
+/// Renders images in passes of a few samples per pixel, adding them up in an `Accumulator`.
+///
+/// Every pass samples the pixels anew, spread across the filter, lens and shutter interval of
+/// the camera like a regular render, so the passes together look the same as one render with
+/// all of their samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressiveRenderer {
+    pub passes: usize,
+    pub samples_per_pass: usize,
+    pub seed: u64,
+}
+
+impl Default for ProgressiveRenderer {
+    fn default() -> Self {
+        ProgressiveRenderer {
+            passes: 64,
+            samples_per_pass: 1,
+            seed: 0,
+        }
+    }
+}
+
+impl ProgressiveRenderer {
+    pub fn new(passes: usize, samples_per_pass: usize) -> Self {
+        ProgressiveRenderer {
+            passes,
+            samples_per_pass,
+            ..Self::default()
+        }
+    }
+
+    /// Renders all passes with the given function, e.g. a path tracer's `radiance`, calling
+    /// `after_pass` with the number of the pass finished and the samples taken so far.
+    pub fn render<R, A>(&self, camera: &Camera, radiance: R, mut after_pass: A) -> Canvas
+    where
+        R: Fn(Ray, &mut Rng) -> Color + Sync,
+        A: FnMut(usize, &Accumulator),
+    {
+        let mut accumulator = Accumulator::new(camera.hsize, camera.vsize);
+
+        for pass in 0..self.passes {
+            self.render_pass(camera, pass, &mut accumulator, &radiance);
+            after_pass(pass, &accumulator);
+        }
+
+        accumulator.canvas()
+    }
+
+    /// Renders all passes, overwriting the PNG at `path` with the image so far after each.
+    pub fn render_to_png<R>(&self, camera: &Camera, radiance: R, path: &Path) -> io::Result<Canvas>
+    where
+        R: Fn(Ray, &mut Rng) -> Color + Sync,
+    {
+        let mut result = Ok(());
+        let canvas = self.render(camera, radiance, |_, accumulator| {
+            if result.is_ok() {
+                result = write(path, accumulator.canvas().to_png());
+            }
+        });
+
+        result.map(|_| canvas)
+    }
+
+    /// Renders the passes left, picking up from the checkpoint at `path` if there is one.
+    /// Every `interval` the state of the render is saved there, so it can be resumed after a
+    /// crash without losing more than that. The checkpoint is removed once the render is done.
+    ///
+    /// Resuming requires the same renderer, camera and radiance function, and ends up with
+    /// exactly the same image as an uninterrupted render.
+    pub fn render_with_checkpoints<R, A>(
+        &self,
+        camera: &Camera,
+        radiance: R,
+        path: &Path,
+        interval: Duration,
+        mut after_pass: A,
+    ) -> Result<Canvas, CheckpointError>
+    where
+        R: Fn(Ray, &mut Rng) -> Color + Sync,
+        A: FnMut(usize, &Accumulator),
+    {
+        let mut checkpoint = if path.exists() {
+            let checkpoint = Checkpoint::load(path)?;
+            checkpoint.check_matches(self, camera)?;
+            checkpoint
+        } else {
+            Checkpoint::new(self, camera)
+        };
+
+        let tiles = camera.tiles.tiles(camera.hsize, camera.vsize);
+        let mut last_saved = Instant::now();
+
+        while checkpoint.pass < self.passes {
+            let remaining: Vec<usize> = (0..tiles.len())
+                .filter(|&index| !checkpoint.completed_tiles[index])
+                .collect();
+
+            // A few tiles per thread at a time, often enough to save in between
+            for batch in remaining.chunks(rayon::current_num_threads() * 2) {
+                let batch_tiles: Vec<Tile> = batch.iter().map(|&index| tiles[index]).collect();
+                self.render_tiles(
+                    camera,
+                    checkpoint.pass,
+                    &batch_tiles,
+                    &mut checkpoint.accumulator,
+                    &radiance,
+                );
+                for &index in batch {
+                    checkpoint.completed_tiles[index] = true;
+                }
+
+                if last_saved.elapsed() >= interval {
+                    checkpoint.save(path)?;
+                    last_saved = Instant::now();
+                }
+            }
+
+            after_pass(checkpoint.pass, &checkpoint.accumulator);
+            checkpoint.pass += 1;
+            checkpoint.completed_tiles = vec![false; tiles.len()];
+
+            if last_saved.elapsed() >= interval {
+                checkpoint.save(path)?;
+                last_saved = Instant::now();
+            }
+        }
+
+        if path.exists() {
+            remove_file(path)?;
+        }
+
+        Ok(checkpoint.accumulator.canvas())
+    }
+
+    /// Adds the samples of the given pass to the accumulator.
+    pub fn render_pass<R>(
+        &self,
+        camera: &Camera,
+        pass: usize,
+        accumulator: &mut Accumulator,
+        radiance: &R,
+    ) where
+        R: Fn(Ray, &mut Rng) -> Color + Sync,
+    {
+        let tiles = camera.tiles.tiles(camera.hsize, camera.vsize);
+
+        self.render_tiles(camera, pass, &tiles, accumulator, radiance);
+    }
+
+    fn render_tiles<R>(
+        &self,
+        camera: &Camera,
+        pass: usize,
+        tiles: &[Tile],
+        accumulator: &mut Accumulator,
+        radiance: &R,
+    ) where
+        R: Fn(Ray, &mut Rng) -> Color + Sync,
+    {
+        // Every pass needs random numbers of its own, or it would repeat the first one. They
+        // only depend on the seed, the pass and the pixel, so a pass can be rendered again
+        // exactly the same.
+        let seed = Rng::new(self.seed ^ pass as u64).next_u64();
+
+        let rendered = camera.tiles.map_tiles(tiles, |x, y| {
+            let mut rng = Rng::for_pixel(x, y, seed);
+            camera.sample_pixel(x, y, self.samples_per_pass, &mut rng, radiance)
+        });
+
+        for (tile, sums) in rendered {
+            for (index, (sum, weight)) in sums.into_iter().enumerate() {
+                let (x, y) = (tile.x + index % tile.width, tile.y + index / tile.width);
+                accumulator.add(x, y, sum, weight, self.samples_per_pass);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::edge_of_a_triangle;
+    use crate::fuzzy_eq::*;
+
+    #[test]
+    fn accumulated_samples_are_averaged_by_weight() {
+        let mut accumulator = Accumulator::new(2, 1);
+
+        accumulator.add(0, 0, Color::new(1.0, 0.0, 0.0), 1.0, 1);
+        accumulator.add(0, 0, Color::new(0.0, 1.5, 0.0), 2.0, 2);
+
+        assert_fuzzy_eq!(
+            accumulator.canvas().pixel_at(0, 0),
+            Color::new(1.0 / 3.0, 0.5, 0.0)
+        );
+        assert_eq!(accumulator.samples_at(0, 0), 3);
+        assert_fuzzy_eq!(accumulator.canvas().pixel_at(1, 0), Color::black());
+        assert_eq!(accumulator.samples_at(1, 0), 0);
+    }
+
+    #[test]
+    fn every_pass_adds_samples_to_every_pixel() {
+        let (w, c) = edge_of_a_triangle();
+        let renderer = ProgressiveRenderer::new(3, 2);
+        let mut samples = vec![];
+
+        renderer.render(
+            &c,
+            |ray, _| w.color_at(ray),
+            |pass, accumulator| {
+                samples.push((pass, accumulator.samples_at(1, 1)));
+            },
+        );
+
+        assert_eq!(samples, vec![(0, 2), (1, 4), (2, 6)]);
+    }
+
+    #[test]
+    fn passes_converge_on_the_antialiased_image() {
+        let (w, c) = edge_of_a_triangle();
+        let mut previews = vec![];
+
+        let canvas = ProgressiveRenderer::new(64, 1).render(
+            &c,
+            |ray, _| w.color_at(ray),
+            |_, accumulator| previews.push(accumulator.canvas().pixel_at(1, 1).red),
+        );
+
+        // A single sample lands on one side of the edge or the other
+        assert!(previews[0] == 0.0 || previews[0] == 1.0);
+        assert!((canvas.pixel_at(1, 1).red - 0.5).abs() < 0.1);
+        assert_fuzzy_eq!(canvas.pixel_at(0, 1), Color::black());
+    }
+
+    #[test]
+    fn the_image_so_far_is_written_after_every_pass() {
+        let (w, c) = edge_of_a_triangle();
+        // Tests of several checkouts may run at the same time
+        let path = std::env::temp_dir().join(format!(
+            "progressive_rendering_test_{}.png",
+            std::process::id()
+        ));
+
+        let canvas = ProgressiveRenderer::new(2, 1)
+            .render_to_png(&c, |ray, _| w.color_at(ray), &path)
+            .unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), canvas.to_png());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
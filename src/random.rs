@@ -0,0 +1,110 @@
+use crate::tuple::Tuple;
+use crate::F;
+
+/// Small and fast pseudo random number generator (SplitMix64).
+///
+/// Renders need lots of random numbers, but nothing of cryptographic quality. Seeding it
+/// per pixel keeps renders reproducible, no matter how the work is split across threads.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    /// Seeds a generator for the given pixel, so neighbouring pixels get unrelated sequences.
+    pub fn for_pixel(x: usize, y: usize, seed: u64) -> Self {
+        let mut rng = Rng::new(seed ^ (((x as u64) << 32) | y as u64));
+        // Throw away the first value, which is still closely related to the seed
+        rng.next_u64();
+        rng
+    }
+
+    /// Seeds a generator from a position in space, so everything computed for the same
+    /// point uses the same random numbers.
+    pub fn for_point(point: Tuple) -> Self {
+        let seed = point.x.to_bits()
+            ^ point.y.to_bits().rotate_left(21)
+            ^ point.z.to_bits().rotate_left(42);
+
+        let mut rng = Rng::new(seed);
+        rng.next_u64();
+        rng
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed number in `[0, 1)`
+    pub fn next_f64(&mut self) -> F {
+        // The upper 53 bits fill the whole mantissa of a double
+        (self.next_u64() >> 11) as F / (1u64 << 53) as F
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generators_with_the_same_seed_produce_the_same_sequence() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn generators_with_different_seeds_produce_different_sequences() {
+        let mut a = Rng::new(1);
+        let mut b = Rng::new(2);
+
+        assert_ne!(a.next_u64(), b.next_u64());
+    }
+
+    #[test]
+    fn pixels_get_different_sequences() {
+        let mut a = Rng::for_pixel(0, 1, 0);
+        let mut b = Rng::for_pixel(1, 0, 0);
+
+        assert_ne!(a.next_u64(), b.next_u64());
+    }
+
+    #[test]
+    fn floats_are_uniformly_distributed_in_the_unit_interval() {
+        let mut rng = Rng::new(7);
+        let samples = 10000;
+        let mut sum = 0.0;
+
+        for _ in 0..samples {
+            let value = rng.next_f64();
+            assert!((0.0..1.0).contains(&value));
+            sum += value;
+        }
+
+        let mean = sum / samples as F;
+        assert!((mean - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn generators_for_the_same_point_agree() {
+        let mut a = Rng::for_point(Tuple::point(1.0, 2.0, 3.0));
+        let mut b = Rng::for_point(Tuple::point(1.0, 2.0, 3.0));
+        let mut c = Rng::for_point(Tuple::point(3.0, 2.0, 1.0));
+
+        let first = a.next_u64();
+        assert_eq!(first, b.next_u64());
+        assert_ne!(first, c.next_u64());
+    }
+}
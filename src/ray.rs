@@ -6,6 +6,9 @@ use crate::matrix::*;
 pub struct Ray {
     pub origin: Tuple,
     pub direction: Tuple,
+    /// Moment within the shutter interval `[0, 1]` the ray travels at, which decides where
+    /// moving bodies are
+    pub time: F,
 }
 
 impl Ray {
@@ -14,7 +17,16 @@ impl Ray {
             panic!("Origin argument should be point and Direction argument should be vector");
         }
 
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    pub fn with_time(mut self, time: F) -> Self {
+        self.time = time;
+        self
     }
 
     pub fn position(&self, time: F) -> Tuple {
@@ -28,6 +40,7 @@ impl Ray {
         Ray {
             origin: m * self.origin,
             direction: m * self.direction,
+            time: self.time,
         }
     }
 }
@@ -80,5 +93,12 @@ mod tests {
         assert_fuzzy_eq!(Tuple::vector(0.0, 3.0, 0.0), r2.direction);
     }
 
+    #[test]
+    fn rays_start_at_time_zero_and_keep_their_time_when_transformed() {
+        let r = Ray::new(Tuple::point(1.0, 2.0, 3.0), Tuple::vector(0.0, 1.0, 0.0));
+        assert_fuzzy_eq!(r.time, 0.0);
 
-}
\ No newline at end of file
+        let r2 = r.with_time(0.5).transform(Matrix::translation(3.0, 4.0, 5.0));
+        assert_fuzzy_eq!(r2.time, 0.5);
+    }
+}
@@ -0,0 +1,377 @@
+use std::f64::consts::PI;
+
+use crate::random::Rng;
+use crate::tuple::Tuple;
+use crate::F;
+
+/// Two unit vectors, which form an orthonormal basis together with the given normal.
+pub fn orthonormal_basis(normal: Tuple) -> (Tuple, Tuple) {
+    // Start from the axis least aligned with the normal, to stay numerically stable
+    let helper = if normal.x.abs() > 0.9 {
+        Tuple::vector(0.0, 1.0, 0.0)
+    } else {
+        Tuple::vector(1.0, 0.0, 0.0)
+    };
+
+    let tangent = helper.cross(normal).normalize();
+    let bitangent = normal.cross(tangent);
+
+    (tangent, bitangent)
+}
+
+/// Direction in the hemisphere around the normal, more likely close to the normal, with a
+/// probability density of `cos(theta) / PI`.
+pub fn cosine_weighted_hemisphere(normal: Tuple, rng: &mut Rng) -> Tuple {
+    let u1 = rng.next_f64();
+    let u2 = rng.next_f64();
+
+    // Uniform point on the unit disk, projected up onto the hemisphere
+    let radius = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    let z = (1.0 - u1).max(0.0).sqrt();
+
+    let (tangent, bitangent) = orthonormal_basis(normal);
+
+    (tangent * (radius * phi.cos()) + bitangent * (radius * phi.sin()) + normal * z).normalize()
+}
+
+/// Uniformly distributed point inside of the unit sphere, returned as vector.
+pub fn in_unit_sphere(rng: &mut Rng) -> Tuple {
+    loop {
+        let v = Tuple::vector(
+            rng.next_f64() * 2.0 - 1.0,
+            rng.next_f64() * 2.0 - 1.0,
+            rng.next_f64() * 2.0 - 1.0,
+        );
+
+        if v.magnitude() < 1.0 {
+            return v;
+        }
+    }
+}
+
+/// Uniformly distributed direction, returned as unit vector.
+pub fn on_unit_sphere(rng: &mut Rng) -> Tuple {
+    let z = 1.0 - 2.0 * rng.next_f64();
+    let radius = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.next_f64();
+
+    Tuple::vector(radius * phi.cos(), radius * phi.sin(), z)
+}
+
+/// Jittered points in the unit square, stratified along both axes: each of the `n` rows and
+/// each of the `n` columns of equal width holds exactly one point (Latin hypercube).
+pub fn stratified_2d(n: usize, rng: &mut Rng) -> Vec<(F, F)> {
+    // Shuffle the rows, so they are not simply paired with the same column
+    let mut rows: Vec<usize> = (0..n).collect();
+    shuffle(&mut rows, rng);
+
+    rows.into_iter()
+        .enumerate()
+        .map(|(column, row)| {
+            (
+                (column as F + rng.next_f64()) / n as F,
+                (row as F + rng.next_f64()) / n as F,
+            )
+        })
+        .collect()
+}
+
+/// Puts the items into a random order, each order being equally likely (Fisher-Yates).
+pub fn shuffle<T>(items: &mut [T], rng: &mut Rng) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, (rng.next_u64() % (i as u64 + 1)) as usize);
+    }
+}
+
+/// Weight of a sample, when combining two sampling strategies with multiple importance
+/// sampling. Squaring the densities favours whichever strategy is clearly better suited.
+pub fn power_heuristic(pdf: F, other_pdf: F) -> F {
+    let (pdf, other_pdf) = (pdf * pdf, other_pdf * other_pdf);
+
+    if pdf + other_pdf == 0.0 {
+        0.0
+    } else {
+        pdf / (pdf + other_pdf)
+    }
+}
+
+/// Piecewise constant probability distribution over `[0, 1)`, with one step per value of
+/// the function it was built from. Sampling it picks steps in proportion to their values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution1D {
+    function: Vec<F>,
+    cdf: Vec<F>,
+    integral: F,
+}
+
+impl Distribution1D {
+    pub fn new(function: Vec<F>) -> Self {
+        assert!(
+            !function.is_empty(),
+            "Distributions need at least one value"
+        );
+
+        let count = function.len() as F;
+        let mut cdf = Vec::with_capacity(function.len() + 1);
+        cdf.push(0.0);
+        for value in function.iter() {
+            cdf.push(cdf[cdf.len() - 1] + value.abs() / count);
+        }
+
+        let integral = cdf[cdf.len() - 1];
+        if integral == 0.0 {
+            // Nothing to prefer, fall back to uniform sampling
+            for (index, step) in cdf.iter_mut().enumerate() {
+                *step = index as F / count;
+            }
+        } else {
+            for step in cdf.iter_mut() {
+                *step /= integral;
+            }
+        }
+
+        Distribution1D {
+            function,
+            cdf,
+            integral,
+        }
+    }
+
+    /// Average of the function over `[0, 1)`.
+    pub fn integral(&self) -> F {
+        self.integral
+    }
+
+    pub fn len(&self) -> usize {
+        self.function.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.function.is_empty()
+    }
+
+    /// Maps a uniformly distributed `u` onto the distribution. Returns the sampled position,
+    /// its probability density and the index of the step it lies in.
+    pub fn sample(&self, u: F) -> (F, F, usize) {
+        // The last step with a cdf not above u
+        let index = self
+            .cdf
+            .partition_point(|step| *step <= u)
+            .clamp(1, self.len())
+            - 1;
+
+        let step_width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if step_width > 0.0 {
+            (u - self.cdf[index]) / step_width
+        } else {
+            0.0
+        };
+
+        let position = (index as F + offset) / self.len() as F;
+
+        (position, self.pdf(position), index)
+    }
+
+    /// Probability density of sampling the given position.
+    pub fn pdf(&self, position: F) -> F {
+        let index = ((position * self.len() as F) as usize).min(self.len() - 1);
+
+        if self.integral == 0.0 {
+            1.0
+        } else {
+            self.function[index].abs() / self.integral
+        }
+    }
+}
+
+/// Piecewise constant probability distribution over the unit square, built from a grid of
+/// values given row by row. Picks a row first, then a column within that row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(width: usize, height: usize, function: &[F]) -> Self {
+        assert_eq!(
+            function.len(),
+            width * height,
+            "Expected width * height values"
+        );
+
+        let rows: Vec<Distribution1D> = function
+            .chunks_exact(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(|row| row.integral()).collect());
+
+        Distribution2D { rows, marginal }
+    }
+
+    /// Maps two uniformly distributed numbers onto a point `(x, y)` in the unit square,
+    /// returned with its probability density.
+    pub fn sample(&self, u1: F, u2: F) -> ((F, F), F) {
+        let (y, y_pdf, row) = self.marginal.sample(u2);
+        let (x, x_pdf, _) = self.rows[row].sample(u1);
+
+        ((x, y), x_pdf * y_pdf)
+    }
+
+    pub fn pdf(&self, x: F, y: F) -> F {
+        let row = ((y * self.rows.len() as F) as usize).min(self.rows.len() - 1);
+
+        self.marginal.pdf(y) * self.rows[row].pdf(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzy_eq::*;
+
+    #[test]
+    fn orthonormal_basis_is_perpendicular_to_the_normal() {
+        for normal in [
+            Tuple::vector(0.0, 1.0, 0.0),
+            Tuple::vector(1.0, 0.0, 0.0),
+            Tuple::vector(1.0, 2.0, -3.0).normalize(),
+        ] {
+            let (tangent, bitangent) = orthonormal_basis(normal);
+
+            assert_fuzzy_eq!(tangent.magnitude(), 1.0);
+            assert_fuzzy_eq!(bitangent.magnitude(), 1.0);
+            assert_fuzzy_eq!(tangent.dot(normal), 0.0);
+            assert_fuzzy_eq!(bitangent.dot(normal), 0.0);
+            assert_fuzzy_eq!(tangent.dot(bitangent), 0.0);
+        }
+    }
+
+    #[test]
+    fn cosine_weighted_directions_lie_in_the_hemisphere() {
+        let mut rng = Rng::new(3);
+        let normal = Tuple::vector(0.0, 0.0, -1.0);
+
+        for _ in 0..1000 {
+            let direction = cosine_weighted_hemisphere(normal, &mut rng);
+
+            assert!(direction.is_vector());
+            assert_fuzzy_eq!(direction.magnitude(), 1.0);
+            assert!(direction.dot(normal) >= 0.0);
+        }
+    }
+
+    #[test]
+    fn cosine_weighted_directions_have_the_expected_mean_cosine() {
+        let mut rng = Rng::new(5);
+        let normal = Tuple::vector(0.0, 1.0, 0.0);
+        let samples = 20000;
+
+        let sum: F = (0..samples)
+            .map(|_| cosine_weighted_hemisphere(normal, &mut rng).dot(normal))
+            .sum();
+
+        // Expected value of cos(theta) for a density of cos(theta) / PI is 2 / 3
+        assert!((sum / samples as F - 2.0 / 3.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn stratified_points_fill_every_row_and_column() {
+        let mut rng = Rng::new(7);
+        let n = 9;
+
+        let points = stratified_2d(n, &mut rng);
+
+        for stratum in 0..n {
+            let in_stratum = |v: F| (v * n as F) as usize == stratum;
+
+            assert_eq!(points.iter().filter(|(x, _)| in_stratum(*x)).count(), 1);
+            assert_eq!(points.iter().filter(|(_, y)| in_stratum(*y)).count(), 1);
+        }
+    }
+
+    #[test]
+    fn points_in_the_unit_sphere() {
+        let mut rng = Rng::new(11);
+
+        for _ in 0..1000 {
+            assert!(in_unit_sphere(&mut rng).magnitude() < 1.0);
+        }
+    }
+
+    #[test]
+    fn directions_on_the_unit_sphere_are_balanced() {
+        let mut rng = Rng::new(13);
+        let samples = 20000;
+
+        let sum = (0..samples).fold(Tuple::vector(0.0, 0.0, 0.0), |sum, _| {
+            let direction = on_unit_sphere(&mut rng);
+            assert_fuzzy_eq!(direction.magnitude(), 1.0);
+
+            sum + direction
+        });
+
+        assert!((sum / samples as F).magnitude() < 0.02);
+    }
+
+    #[test]
+    fn power_heuristic_weights_sum_up_to_one() {
+        assert_fuzzy_eq!(power_heuristic(1.0, 3.0), 0.1);
+        assert_fuzzy_eq!(power_heuristic(3.0, 1.0), 0.9);
+        assert_fuzzy_eq!(power_heuristic(2.0, 0.0), 1.0);
+        assert_fuzzy_eq!(power_heuristic(0.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn a_1d_distribution_samples_steps_in_proportion_to_their_values() {
+        let distribution = Distribution1D::new(vec![1.0, 3.0]);
+
+        assert_fuzzy_eq!(distribution.integral(), 2.0);
+        assert_eq!(distribution.sample(0.2).2, 0);
+        assert_eq!(distribution.sample(0.3).2, 1);
+
+        let (position, pdf, _) = distribution.sample(0.625);
+        assert_fuzzy_eq!(position, 0.75);
+        assert_fuzzy_eq!(pdf, 1.5);
+        assert_fuzzy_eq!(distribution.pdf(0.1), 0.5);
+    }
+
+    #[test]
+    fn a_1d_distribution_never_samples_empty_steps() {
+        let distribution = Distribution1D::new(vec![0.0, 2.0, 0.0]);
+        let mut rng = Rng::new(9);
+
+        for _ in 0..100 {
+            let (position, pdf, index) = distribution.sample(rng.next_f64());
+
+            assert_eq!(index, 1);
+            assert!((1.0 / 3.0..2.0 / 3.0).contains(&position));
+            assert_fuzzy_eq!(pdf, 3.0);
+        }
+    }
+
+    #[test]
+    fn an_all_zero_1d_distribution_is_uniform() {
+        let distribution = Distribution1D::new(vec![0.0, 0.0]);
+
+        assert_fuzzy_eq!(distribution.sample(0.3).0, 0.3);
+        assert_fuzzy_eq!(distribution.pdf(0.7), 1.0);
+    }
+
+    #[test]
+    fn a_2d_distribution_picks_the_bright_cell() {
+        let distribution = Distribution2D::new(2, 2, &[0.0, 0.0, 0.0, 4.0]);
+        let mut rng = Rng::new(4);
+
+        for _ in 0..100 {
+            let ((x, y), pdf) = distribution.sample(rng.next_f64(), rng.next_f64());
+
+            assert!(x >= 0.5 && y >= 0.5);
+            assert_fuzzy_eq!(pdf, 4.0);
+            assert_fuzzy_eq!(distribution.pdf(x, y), pdf);
+        }
+
+        assert_fuzzy_eq!(distribution.pdf(0.25, 0.25), 0.0);
+    }
+}
@@ -0,0 +1,266 @@
+//! Analytic daylight sky after Preetham, Shirley and Smits, "A Practical Analytic Model for
+//! Daylight" (1999).
+
+use crate::canvas::Color;
+use crate::light::DirectionalLight;
+use crate::tuple::Tuple;
+use crate::F;
+
+/// Clear or hazy sky lit by the sun, usable as the background of a world.
+///
+/// Luminance, x and y chromaticity of every direction follow the Perez distribution, relative
+/// to their values at the zenith. Directions below the horizon keep the color of the horizon,
+/// the ground itself is not modelled.
+#[derive(Debug, Clone, Copy)]
+pub struct Sky {
+    /// Converts the model's luminance in kcd/m² into the renderer's radiance
+    pub intensity: F,
+    sun_direction: Tuple,
+    turbidity: F,
+    /// Perez coefficients A to E for luminance, x and y chromaticity
+    perez: [[F; 5]; 3],
+    /// Luminance, x and y chromaticity at the zenith
+    zenith: [F; 3],
+}
+
+impl Sky {
+    /// The sun direction points from the world towards the sun. Turbidity describes the haze,
+    /// from 2 for a very clear sky to about 10 for a hazy one.
+    pub fn new(sun_direction: Tuple, turbidity: F) -> Self {
+        let sun_direction = sun_direction.normalize();
+        // The model is only fitted for the sun above the horizon
+        let theta = sun_direction.y.clamp(0.0, 1.0).acos();
+        let t = turbidity;
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (std::f64::consts::PI - 2.0 * theta);
+        let luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+
+        let chromaticity = |coefficients: [[F; 4]; 3]| {
+            let [t2, t1, t0] =
+                coefficients.map(|[c3, c2, c1, c0]| ((c3 * theta + c2) * theta + c1) * theta + c0);
+            (t2 * t + t1) * t + t0
+        };
+        let x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        Sky {
+            intensity: 0.05,
+            sun_direction,
+            turbidity,
+            perez,
+            zenith: [luminance, x, y],
+        }
+    }
+
+    pub fn with_intensity(mut self, intensity: F) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn sun_direction(&self) -> Tuple {
+        self.sun_direction
+    }
+
+    pub fn turbidity(&self) -> F {
+        self.turbidity
+    }
+
+    pub fn radiance(&self, direction: Tuple) -> Color {
+        // Below the horizon, the sky right above is used
+        let lifted = Tuple::vector(direction.x, direction.y.max(0.0), direction.z);
+        let direction = if lifted.magnitude() > 0.0 {
+            lifted.normalize()
+        } else {
+            // Straight down lies below every point of the horizon alike, so just pick one
+            Tuple::vector(0.0, 0.0, 1.0)
+        };
+        // Keeps the Perez function finite at the horizon
+        let cos_theta = direction.y.max(0.001);
+        let gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0).acos();
+        let sun_theta = self.sun_direction.y.clamp(0.0, 1.0).acos();
+
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            self.zenith[i] * perez(self.perez[i], cos_theta, gamma)
+                / perez(self.perez[i], 1.0, sun_theta)
+        });
+
+        xyy_to_rgb(x, y, luminance) * self.intensity
+    }
+
+    /// Directional light shining from the sun, reddened by the atmosphere it passes through.
+    /// Below the horizon the sun does not shine at all.
+    pub fn sun(&self, intensity: F) -> DirectionalLight {
+        let color = if self.sun_direction.y > 0.0 {
+            self.sun_transmittance() * intensity
+        } else {
+            Color::black()
+        };
+
+        DirectionalLight::new(-self.sun_direction, color)
+    }
+
+    /// Fraction of sunlight reaching the ground, for wavelengths standing in for red, green
+    /// and blue. Rayleigh and aerosol scattering only, without absorption by ozone or water.
+    fn sun_transmittance(&self) -> Color {
+        let theta = self.sun_direction.y.clamp(0.0, 1.0).acos();
+        // Relative optical air mass after Kasten
+        let air_mass = 1.0 / (theta.cos() + 0.15 * (93.885 - theta.to_degrees()).powf(-1.253));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+
+        let transmittance = |wavelength: F| {
+            let rayleigh = 0.008735 * wavelength.powf(-4.08);
+            let aerosol = beta * wavelength.powf(-1.3);
+            (-air_mass * (rayleigh + aerosol)).exp()
+        };
+
+        Color::new(
+            transmittance(0.65),
+            transmittance(0.55),
+            transmittance(0.45),
+        )
+    }
+}
+
+fn perez([a, b, c, d, e]: [F; 5], cos_theta: F, gamma: F) -> F {
+    (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
+
+/// CIE xyY to linear sRGB, negative channels of colors outside the gamut are dropped.
+fn xyy_to_rgb(x: F, y: F, luminance: F) -> Color {
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+
+    Color::new(
+        3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+    )
+    .clamp(0.0, F::INFINITY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzy_eq::*;
+
+    fn afternoon() -> Sky {
+        Sky::new(Tuple::vector(1.0, 1.0, 0.0), 3.0)
+    }
+
+    #[test]
+    fn the_zenith_has_the_zenith_luminance() {
+        let sky = afternoon().with_intensity(1.0);
+
+        let luminance = sky.radiance(Tuple::vector(0.0, 1.0, 0.0)).luminance();
+
+        assert!((luminance - sky.zenith[0]).abs() < 1e-3 * sky.zenith[0]);
+    }
+
+    #[test]
+    fn a_clear_sky_is_blue() {
+        let color = afternoon().radiance(Tuple::vector(-1.0, 1.0, 0.0));
+
+        assert!(color.blue > color.green && color.green > color.red);
+    }
+
+    #[test]
+    fn the_sky_is_brighter_around_the_sun() {
+        let sky = afternoon();
+
+        let towards_sun = sky.radiance(Tuple::vector(1.0, 1.2, 0.0)).luminance();
+        let away_from_sun = sky.radiance(Tuple::vector(-1.0, 1.2, 0.0)).luminance();
+
+        assert!(towards_sun > 2.0 * away_from_sun);
+    }
+
+    #[test]
+    fn below_the_horizon_the_sky_keeps_the_horizon_color() {
+        let sky = afternoon();
+
+        assert_fuzzy_eq!(
+            sky.radiance(Tuple::vector(0.0, -1.0, 1.0)),
+            sky.radiance(Tuple::vector(0.0, 0.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn straight_down_the_sky_keeps_a_horizon_color() {
+        let sky = afternoon();
+
+        assert_fuzzy_eq!(
+            sky.radiance(Tuple::vector(0.0, -1.0, 0.0)),
+            sky.radiance(Tuple::vector(0.0, 0.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn the_sun_shines_from_the_sun_direction() {
+        let sun = afternoon().sun(1.0);
+
+        assert_fuzzy_eq!(sun.direction, Tuple::vector(-1.0, -1.0, 0.0).normalize());
+        assert!(sun.intensity.red > 0.5 && sun.intensity.red < 1.0);
+    }
+
+    #[test]
+    fn a_low_sun_is_redder() {
+        let high = Sky::new(Tuple::vector(0.0, 1.0, 0.2), 3.0)
+            .sun(1.0)
+            .intensity;
+        let low = Sky::new(Tuple::vector(0.0, 0.05, 1.0), 3.0)
+            .sun(1.0)
+            .intensity;
+
+        assert!(low.red / low.blue > 2.0 * high.red / high.blue);
+    }
+
+    #[test]
+    fn haze_dims_the_sun() {
+        let clear = Sky::new(Tuple::vector(0.0, 1.0, 1.0), 2.0)
+            .sun(1.0)
+            .intensity;
+        let hazy = Sky::new(Tuple::vector(0.0, 1.0, 1.0), 8.0)
+            .sun(1.0)
+            .intensity;
+
+        assert!(hazy.luminance() < clear.luminance());
+    }
+
+    #[test]
+    fn the_sun_does_not_shine_at_night() {
+        let sky = Sky::new(Tuple::vector(0.0, -1.0, 1.0), 3.0);
+
+        assert_fuzzy_eq!(sky.sun(1.0).intensity, Color::black());
+    }
+}
@@ -3,13 +3,20 @@ use crate::material::Material;
 use crate::ray::*;
 use crate::tuple::*;
 use crate::matrix::*;
+use crate::motion::AnimatedTransform;
 use crate::body::*;
+use crate::emitter::Emitter;
+use crate::random::Rng;
+use crate::sampling::on_unit_sphere;
+use crate::{EPSILON, F};
+use std::f64::consts::PI;
 
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Sphere {
     transform: Matrix<4>,
     material: Material,
+    motion: Option<AnimatedTransform>,
 }
 
 impl Sphere {
@@ -18,8 +25,9 @@ impl Sphere {
             None => Sphere {
                 transform: Matrix::identity(),
                 material: Material::default(),
+                motion: None,
             },
-            Some(transform) => Sphere { transform , material: Material::default() }
+            Some(transform) => Sphere { transform , material: Material::default(), motion: None }
         }
         
     }
@@ -29,10 +37,38 @@ impl Sphere {
         s.material = material;
         s
     }
+
+    /// Moves the sphere while the shutter is open, from its transformation at time 0 to
+    /// `end_transform` at time 1.
+    pub fn with_motion(mut self, end_transform: Matrix<4>) -> Self {
+        self.motion = Some(AnimatedTransform::new(self.transform, end_transform));
+        self
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.motion.is_some()
+    }
+
+    /// The sphere frozen where it is at the given time.
+    pub fn at_time(&self, time: F) -> Self {
+        match self.motion {
+            Some(motion) => Sphere {
+                transform: motion.at(time),
+                material: self.material.clone(),
+                motion: None,
+            },
+            None => self.clone(),
+        }
+    }
 }
 
 impl Intersectable for Sphere {
     fn intersect(&self, ray: Ray) -> Intersections {
+        // The hits belong to the sphere as it was at the time, which then shades them
+        if self.motion.is_some() {
+            return self.at_time(ray.time).intersect(ray);
+        }
+
         let object_space_ray = ray.transform(self.transform.inverse());
 
         let sphere_to_ray = object_space_ray.origin - Tuple::point(0.0, 0.0, 0.0);
@@ -56,8 +92,8 @@ impl Intersectable for Sphere {
             // ].into()
 
             Intersections::new(vec![
-                Intersection::new(t1, Body::from(*self)), 
-                Intersection::new(t2, Body::from(*self))
+                Intersection::new(t1, Body::from(self.clone())), 
+                Intersection::new(t2, Body::from(self.clone()))
             ])
         }
     }
@@ -75,7 +111,70 @@ impl Intersectable for Sphere {
     }
 
     fn material(&self) -> Material {
-        self.material
+        self.material.clone()
+    }
+
+    fn uv(&self, point: Tuple) -> (F, F) {
+        let object_point = self.transform.inverse() * point;
+
+        // azimuthal angle around the y axis, -PI < theta <= PI
+        let theta = object_point.x.atan2(object_point.z);
+        let radius = Tuple::vector(object_point.x, object_point.y, object_point.z).magnitude();
+        // polar angle from the north pole, 0 <= phi <= PI
+        let phi = (object_point.y / radius).clamp(-1.0, 1.0).acos();
+
+        let u = 1.0 - (theta / (2.0 * PI) + 0.5);
+        let v = 1.0 - phi / PI;
+
+        (u, v)
+    }
+
+    fn tangents(&self, point: Tuple) -> (Tuple, Tuple) {
+        let object_point = self.transform.inverse() * point;
+        let (x, y, z) = (object_point.x, object_point.y, object_point.z);
+
+        // u grows against theta, so the tangent runs clockwise around the y axis.
+        // At the poles this direction degenerates, any horizontal direction will do.
+        let mut object_tangent = Tuple::vector(-z, 0.0, x);
+        if object_tangent.magnitude() < EPSILON {
+            object_tangent = Tuple::vector(1.0, 0.0, 0.0);
+        }
+
+        // v grows towards the north pole, along the meridian through the point.
+        let horizontal = Tuple::vector(x, 0.0, z);
+        let object_bitangent = Tuple::vector(0.0, horizontal.dot(horizontal), 0.0) - horizontal * y;
+        let object_bitangent = if object_bitangent.magnitude() < EPSILON {
+            Tuple::vector(0.0, 0.0, 1.0)
+        } else {
+            object_bitangent
+        };
+
+        let mut tangent = self.transform * object_tangent.normalize();
+        let mut bitangent = self.transform * object_bitangent.normalize();
+        tangent.w = 0.0;
+        bitangent.w = 0.0;
+
+        (tangent.normalize(), bitangent.normalize())
+    }
+}
+
+impl Emitter for Sphere {
+    fn sample_surface(&self, rng: &mut Rng) -> Tuple {
+        let object_point = Tuple::point(0.0, 0.0, 0.0) + on_unit_sphere(rng);
+
+        self.transform * object_point
+    }
+
+    fn area_pdf(&self, point: Tuple) -> F {
+        let inverse = self.transform.inverse();
+        let object_normal = (inverse * point - Tuple::point(0.0, 0.0, 0.0)).normalize();
+        let mut scaled_normal = inverse.transpose() * object_normal;
+        scaled_normal.w = 0.0;
+
+        // How much the transformation stretches a small patch around the point
+        let stretch = self.transform.determinant().abs() * scaled_normal.magnitude();
+
+        1.0 / (4.0 * PI * stretch)
     }
 }
 
@@ -217,6 +316,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn computing_the_normal_on_a_translated_sphere() {
         let s = Sphere::new(Some(Matrix::translation(0.0, 1.0, 0.0)));
         
@@ -262,6 +362,53 @@ mod tests {
         assert_fuzzy_eq!(n.normalize(), n);
     }
 
+    #[test]
+    fn texture_coordinates_on_a_sphere() {
+        let s = Sphere::new(None);
+        let sqrt2_over_2 = (2.0 as F).sqrt() / 2.0;
+
+        let cases = [
+            (Tuple::point(0.0, 0.0, -1.0), (0.0, 0.5)),
+            (Tuple::point(1.0, 0.0, 0.0), (0.25, 0.5)),
+            (Tuple::point(0.0, 0.0, 1.0), (0.5, 0.5)),
+            (Tuple::point(-1.0, 0.0, 0.0), (0.75, 0.5)),
+            (Tuple::point(0.0, 1.0, 0.0), (0.5, 1.0)),
+            (Tuple::point(0.0, -1.0, 0.0), (0.5, 0.0)),
+            (Tuple::point(sqrt2_over_2, sqrt2_over_2, 0.0), (0.25, 0.75)),
+        ];
+
+        for (point, (expected_u, expected_v)) in cases {
+            let (u, v) = s.uv(point);
+
+            assert_fuzzy_eq!(u, expected_u);
+            assert_fuzzy_eq!(v, expected_v);
+        }
+    }
+
+    #[test]
+    fn tangents_on_the_front_of_a_sphere() {
+        let s = Sphere::new(None);
+
+        let (tangent, bitangent) = s.tangents(Tuple::point(0.0, 0.0, -1.0));
+
+        assert_fuzzy_eq!(tangent, Tuple::vector(1.0, 0.0, 0.0));
+        assert_fuzzy_eq!(bitangent, Tuple::vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn tangents_on_a_transformed_sphere_are_normalized_surface_directions() {
+        let s = Sphere::new(Some(Matrix::scaling(2.0, 1.0, 1.0) * Matrix::rotation_y(PI / 3.0)));
+        let p = Tuple::point(0.5, 0.5, -0.5);
+        let n = s.normal_vector(p);
+
+        let (tangent, bitangent) = s.tangents(p);
+
+        assert_fuzzy_eq!(tangent.magnitude(), 1.0);
+        assert_fuzzy_eq!(bitangent.magnitude(), 1.0);
+        assert_fuzzy_eq!(tangent.dot(n), 0.0);
+        assert_fuzzy_eq!(bitangent.dot(n), 0.0);
+    }
+
     #[test]
     fn sphere_has_default_phong_material() {
         let s = Sphere::new(None);
@@ -273,8 +420,32 @@ mod tests {
     #[test]
     fn sphere_may_be_assigned_a_material() {
         let m = Material::from(Phong::new(Color::new(1.0, 1.0, 0.0), 0.05, 0.7, 0.95, 400.0));
-        let s = Sphere::with_material(m, None);
+        let s = Sphere::with_material(m.clone(), None);
 
         assert_fuzzy_eq!(s.material, m);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn a_moving_sphere_is_hit_where_it_is_at_the_time_of_the_ray() {
+        let s = Sphere::new(None).with_motion(Matrix::translation(0.0, 0.0, 2.0));
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert_fuzzy_eq!(s.intersect(r).data[0].t, 4.0);
+        assert_fuzzy_eq!(s.intersect(r.with_time(0.5)).data[0].t, 5.0);
+        assert_fuzzy_eq!(s.intersect(r.with_time(1.0)).data[0].t, 6.0);
+    }
+
+    #[test]
+    fn hits_on_a_moving_sphere_are_shaded_as_it_was_at_the_time() {
+        let s = Sphere::new(None).with_motion(Matrix::translation(2.0, 0.0, 0.0));
+        let r = Ray::new(Tuple::point(1.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let xs = s.intersect(r.with_time(0.5));
+
+        assert_fuzzy_eq!(xs.data[0].t, 4.0);
+        assert_fuzzy_eq!(
+            xs.data[0].body.normal_vector(Tuple::point(1.0, 0.0, -1.0)),
+            Tuple::vector(0.0, 0.0, -1.0)
+        );
+    }
+}
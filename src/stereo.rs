@@ -0,0 +1,219 @@
+use crate::camera::Camera;
+use crate::canvas::{Canvas, Color};
+use crate::matrix::Matrix;
+use crate::tuple::Tuple;
+use crate::world::World;
+use crate::F;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+/// Pair of cameras, set apart like a pair of eyes, for pictures which look three dimensional
+/// when each eye gets to see its own half.
+///
+/// Both eyes look at the same point at the convergence distance (toe-in). Things at that
+/// distance appear on the screen, nearer ones in front of it and farther ones behind it.
+#[derive(Debug, Clone, Copy)]
+pub struct StereoCamera {
+    /// Settings shared by both eyes, its transform is replaced by the eye's
+    pub camera: Camera,
+    pub from: Tuple,
+    pub to: Tuple,
+    pub up: Tuple,
+    /// Distance between the eyes, about 0.065 for humans measured in meters
+    pub interocular_distance: F,
+    pub convergence_distance: F,
+}
+
+impl StereoCamera {
+    /// Both eyes converge on `to`, until told otherwise.
+    pub fn new(camera: Camera, from: Tuple, to: Tuple, up: Tuple, interocular_distance: F) -> Self {
+        StereoCamera {
+            camera,
+            from,
+            to,
+            up,
+            interocular_distance,
+            convergence_distance: (to - from).magnitude(),
+        }
+    }
+
+    pub fn with_convergence_distance(mut self, convergence_distance: F) -> Self {
+        self.convergence_distance = convergence_distance;
+        self
+    }
+
+    /// Camera seeing the world through the given eye.
+    pub fn eye(&self, eye: Eye) -> Camera {
+        let forward = (self.to - self.from).normalize();
+        // The same direction the view transformation calls left
+        let left = forward.cross(self.up.normalize()).normalize();
+
+        let offset = match eye {
+            Eye::Left => self.interocular_distance / 2.0,
+            Eye::Right => -self.interocular_distance / 2.0,
+        };
+        let position = self.from + left * offset;
+        let focus = self.from + forward * self.convergence_distance;
+
+        let transform = Matrix::view_transform(position, focus, self.up);
+
+        self.camera.with_transform(transform)
+    }
+
+    /// Renders the left and the right eye with the given function, e.g. a path tracer.
+    pub fn render_eyes<R>(&self, render: R) -> (Canvas, Canvas)
+    where
+        R: Fn(&Camera) -> Canvas,
+    {
+        (render(&self.eye(Eye::Left)), render(&self.eye(Eye::Right)))
+    }
+
+    pub fn render_side_by_side(&self, world: &World) -> Canvas {
+        let (left, right) = self.render_eyes(|camera| camera.render(world));
+
+        side_by_side(&left, &right)
+    }
+
+    pub fn render_anaglyph(&self, world: &World) -> Canvas {
+        let (left, right) = self.render_eyes(|camera| camera.render(world));
+
+        anaglyph(&left, &right)
+    }
+}
+
+/// Both images next to each other, the left one on the left, for viewing with a headset or
+/// by crossing the eyes.
+pub fn side_by_side(left: &Canvas, right: &Canvas) -> Canvas {
+    assert_eq!(
+        (left.width, left.height),
+        (right.width, right.height),
+        "Both eyes need images of the same size"
+    );
+
+    let mut canvas = Canvas::new(left.width * 2, left.height);
+    for y in 0..left.height {
+        for x in 0..left.width {
+            canvas.write_pixel(x, y, left.pixel_at(x, y));
+            canvas.write_pixel(left.width + x, y, right.pixel_at(x, y));
+        }
+    }
+
+    canvas
+}
+
+/// Red/cyan anaglyph, to be viewed through glasses with a red filter over the left eye: red
+/// comes from the left image, green and blue from the right one.
+pub fn anaglyph(left: &Canvas, right: &Canvas) -> Canvas {
+    assert_eq!(
+        (left.width, left.height),
+        (right.width, right.height),
+        "Both eyes need images of the same size"
+    );
+
+    let mut canvas = Canvas::new(left.width, left.height);
+    for y in 0..left.height {
+        for x in 0..left.width {
+            let (l, r) = (left.pixel_at(x, y), right.pixel_at(x, y));
+            canvas.write_pixel(x, y, Color::new(l.red, r.green, r.blue));
+        }
+    }
+
+    canvas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzy_eq::*;
+    use std::f64::consts::PI;
+
+    fn rig() -> StereoCamera {
+        StereoCamera::new(
+            Camera::new(11, 11, PI / 2.0),
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+            0.5,
+        )
+    }
+
+    #[test]
+    fn the_eyes_sit_apart_by_the_interocular_distance() {
+        let stereo = rig();
+
+        let left = stereo.eye(Eye::Left).ray_for_pixel(5, 5).unwrap();
+        let right = stereo.eye(Eye::Right).ray_for_pixel(5, 5).unwrap();
+
+        assert_fuzzy_eq!(left.origin, Tuple::point(-0.25, 0.0, -5.0));
+        assert_fuzzy_eq!(right.origin, Tuple::point(0.25, 0.0, -5.0));
+    }
+
+    #[test]
+    fn the_eyes_converge_at_the_convergence_distance() {
+        let stereo = rig().with_convergence_distance(2.0);
+
+        for eye in [Eye::Left, Eye::Right] {
+            let ray = stereo.eye(eye).ray_for_pixel(5, 5).unwrap();
+            let t = 2.0 / ray.direction.z;
+
+            assert_fuzzy_eq!(ray.position(t), Tuple::point(0.0, 0.0, -3.0));
+        }
+    }
+
+    #[test]
+    fn the_eyes_keep_the_settings_of_the_camera() {
+        let stereo = rig();
+        let eye = stereo.eye(Eye::Right);
+
+        assert_eq!((eye.hsize, eye.vsize), (11, 11));
+        assert_fuzzy_eq!(eye.field_of_view, PI / 2.0);
+    }
+
+    fn reddish_and_bluish_eyes() -> (Canvas, Canvas) {
+        let mut left = Canvas::new(2, 1);
+        let mut right = Canvas::new(2, 1);
+        for x in 0..2 {
+            left.write_pixel(x, 0, Color::new(1.0, 0.2, 0.0));
+            right.write_pixel(x, 0, Color::new(0.0, 0.4, 1.0));
+        }
+
+        (left, right)
+    }
+
+    #[test]
+    fn side_by_side_images_put_the_left_eye_on_the_left() {
+        let (left, right) = reddish_and_bluish_eyes();
+
+        let canvas = side_by_side(&left, &right);
+
+        assert_eq!((canvas.width, canvas.height), (4, 1));
+        assert_fuzzy_eq!(canvas.pixel_at(1, 0), Color::new(1.0, 0.2, 0.0));
+        assert_fuzzy_eq!(canvas.pixel_at(2, 0), Color::new(0.0, 0.4, 1.0));
+    }
+
+    #[test]
+    fn anaglyphs_take_red_from_the_left_eye_and_cyan_from_the_right() {
+        let (left, right) = reddish_and_bluish_eyes();
+
+        let canvas = anaglyph(&left, &right);
+
+        assert_eq!((canvas.width, canvas.height), (2, 1));
+        assert_fuzzy_eq!(canvas.pixel_at(0, 0), Color::new(1.0, 0.4, 1.0));
+    }
+
+    #[test]
+    fn rendering_a_stereo_pair() {
+        let w = crate::world::default_world();
+
+        let canvas = rig().render_side_by_side(&w);
+
+        assert_eq!((canvas.width, canvas.height), (22, 11));
+        // The sphere straight ahead is seen by both eyes
+        assert!(canvas.pixel_at(5, 5).red > 0.1);
+        assert!(canvas.pixel_at(16, 5).red > 0.1);
+    }
+}
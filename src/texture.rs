@@ -0,0 +1,165 @@
+use std::fmt;
+
+use crate::canvas::Color;
+use crate::hdr::{self, HdrError};
+use crate::F;
+
+/// An image which can be sampled through `(u, v)` texture coordinates.
+///
+/// `u` runs from the left (0.0) to the right (1.0) edge of the image and wraps around,
+/// `v` runs from the bottom (0.0) to the top (1.0) edge and is clamped.
+#[derive(Clone, PartialEq)]
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+
+    pixels: Vec<Color>,
+}
+
+impl Texture {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        if width == 0 || height == 0 || pixels.len() != width * height {
+            panic!("Texture needs at least one pixel and exactly width * height pixels.");
+        }
+
+        Texture {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Decodes a PNG image. The color values are taken as they are stored, without any
+    /// gamma correction, which is what data textures like normal maps expect.
+    pub fn from_png(data: &[u8]) -> Result<Self, png::DecodingError> {
+        let mut decoder = png::Decoder::new(data);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+
+        let channels = info.color_type.samples();
+        let to_float = |byte: u8| byte as F / 255.0;
+
+        let pixels = buffer[..info.buffer_size()]
+            .chunks_exact(channels)
+            .map(|pixel| match info.color_type {
+                png::ColorType::Grayscale | png::ColorType::GrayscaleAlpha => {
+                    let value = to_float(pixel[0]);
+                    Color::new(value, value, value)
+                }
+                _ => Color::new(to_float(pixel[0]), to_float(pixel[1]), to_float(pixel[2])),
+            })
+            .collect();
+
+        Ok(Texture::new(
+            info.width as usize,
+            info.height as usize,
+            pixels,
+        ))
+    }
+
+    /// Decodes a Radiance HDR image, keeping colors brighter than white as they are.
+    pub fn from_hdr(data: &[u8]) -> Result<Self, HdrError> {
+        hdr::decode(data)
+    }
+
+    pub fn pixel_at(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Bilinearly filtered lookup of the color at the given texture coordinates.
+    pub fn sample(&self, u: F, v: F) -> Color {
+        // Map onto pixel centers, with the first row of the image being the top edge.
+        let x = (u - u.floor()) * self.width as F - 0.5;
+        let y = (1.0 - v.clamp(0.0, 1.0)) * self.height as F - 0.5;
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+
+        let wrap_x = |x: F| (x as i64).rem_euclid(self.width as i64) as usize;
+        let clamp_y = |y: F| (y.max(0.0) as usize).min(self.height - 1);
+
+        let (left, right) = (wrap_x(x0), wrap_x(x0 + 1.0));
+        let (top, bottom) = (clamp_y(y0), clamp_y(y0 + 1.0));
+
+        let upper = self.pixel_at(left, top) * (1.0 - tx) + self.pixel_at(right, top) * tx;
+        let lower = self.pixel_at(left, bottom) * (1.0 - tx) + self.pixel_at(right, bottom) * tx;
+
+        upper * (1.0 - ty) + lower * ty
+    }
+}
+
+impl fmt::Debug for Texture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The pixel data is way too large to be of any use in debug output
+        f.debug_struct("Texture")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::to_png::ToPNG;
+    use crate::canvas::Canvas;
+    use crate::fuzzy_eq::*;
+
+    fn checkers() -> Texture {
+        Texture::new(
+            2,
+            2,
+            vec![Color::white(), Color::black(), Color::black(), Color::white()],
+        )
+    }
+
+    #[test]
+    fn sampling_a_texture_at_pixel_centers() {
+        let texture = checkers();
+
+        assert_fuzzy_eq!(texture.sample(0.25, 0.75), Color::white());
+        assert_fuzzy_eq!(texture.sample(0.75, 0.75), Color::black());
+        assert_fuzzy_eq!(texture.sample(0.25, 0.25), Color::black());
+        assert_fuzzy_eq!(texture.sample(0.75, 0.25), Color::white());
+    }
+
+    #[test]
+    fn sampling_a_texture_between_pixels_blends_colors() {
+        let texture = checkers();
+
+        assert_fuzzy_eq!(texture.sample(0.5, 0.75), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn sampling_a_texture_wraps_around_horizontally() {
+        let texture = checkers();
+
+        assert_fuzzy_eq!(texture.sample(1.25, 0.75), texture.sample(0.25, 0.75));
+        assert_fuzzy_eq!(texture.sample(-0.75, 0.75), texture.sample(0.25, 0.75));
+    }
+
+    #[test]
+    fn loading_a_texture_from_png() {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.write_pixel(0, 0, Color::new(1.0, 0.0, 0.0));
+        canvas.write_pixel(2, 1, Color::new(0.0, 0.0, 1.0));
+
+        let texture = Texture::from_png(&canvas.to_png()).unwrap();
+
+        assert_eq!(texture.width, 3);
+        assert_eq!(texture.height, 2);
+        assert_fuzzy_eq!(texture.pixel_at(0, 0), Color::new(1.0, 0.0, 0.0));
+        assert_fuzzy_eq!(texture.pixel_at(1, 0), Color::black());
+        assert_fuzzy_eq!(texture.pixel_at(2, 1), Color::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn loading_invalid_png_data_fails() {
+        assert!(Texture::from_png(&[1, 2, 3, 4]).is_err());
+    }
+}
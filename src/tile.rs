@@ -0,0 +1,310 @@
+//! Renders images in parallel, split into tiles. Every thread renders whole tiles into a
+//! buffer of its own, so threads never wait for each other to write pixels.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rayon::prelude::*;
+
+use crate::canvas::{Canvas, Color};
+
+/// Rectangle of pixels, rendered in one go by a single thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    /// Column of the upper left pixel
+    pub x: usize,
+    /// Row of the upper left pixel
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    /// Values of the pixels of the tile, row by row.
+    fn render<T, P>(&self, pixel: &P) -> Vec<T>
+    where
+        P: Fn(usize, usize) -> T,
+    {
+        (self.y..self.y + self.height)
+            .flat_map(|y| (self.x..self.x + self.width).map(move |x| pixel(x, y)))
+            .collect()
+    }
+}
+
+/// Order the tiles are started in. All of them are rendered in the end, the order decides
+/// which parts of the image are done first, and how close together the tiles in flight are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TileOrder {
+    /// Left to right, top to bottom
+    #[default]
+    Scanline,
+    /// Outwards from the center of the image, where the subject usually is
+    Spiral,
+    /// Along a Hilbert curve, whose neighboring tiles are always next to each other, which
+    /// keeps the parts of the world the threads work on close together
+    Hilbert,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileRenderer {
+    /// Width and height of the tiles in pixels, the tiles at the right and bottom edges may
+    /// be smaller
+    pub tile_size: usize,
+    pub order: TileOrder,
+}
+
+impl Default for TileRenderer {
+    fn default() -> Self {
+        TileRenderer {
+            tile_size: 32,
+            order: TileOrder::default(),
+        }
+    }
+}
+
+impl TileRenderer {
+    pub fn new(tile_size: usize) -> Self {
+        TileRenderer {
+            tile_size,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_order(mut self, order: TileOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// The tiles covering an image of the given size, in the order they are rendered in.
+    pub fn tiles(&self, width: usize, height: usize) -> Vec<Tile> {
+        let size = self.tile_size.max(1);
+        let columns = width.div_ceil(size);
+        let rows = height.div_ceil(size);
+
+        let grid: Vec<(usize, usize)> = match self.order {
+            TileOrder::Scanline => (0..rows)
+                .flat_map(|row| (0..columns).map(move |column| (column, row)))
+                .collect(),
+            TileOrder::Spiral => spiral(columns, rows),
+            TileOrder::Hilbert => {
+                let mut grid: Vec<(usize, usize)> = (0..rows)
+                    .flat_map(|row| (0..columns).map(move |column| (column, row)))
+                    .collect();
+                let side = columns.max(rows).next_power_of_two();
+                grid.sort_by_key(|&(column, row)| hilbert_index(side, column, row));
+                grid
+            }
+        };
+
+        grid.into_iter()
+            .map(|(column, row)| Tile {
+                x: column * size,
+                y: row * size,
+                width: size.min(width - column * size),
+                height: size.min(height - row * size),
+            })
+            .collect()
+    }
+
+    /// Fills a canvas with the colors of the given function, tile by tile in parallel.
+    pub fn render<P>(&self, width: usize, height: usize, pixel_color: P) -> Canvas
+    where
+        P: Fn(usize, usize) -> Color + Sync,
+    {
+        let colors = self.map_pixels(width, height, pixel_color);
+
+        let mut canvas = Canvas::new(width, height);
+        for (index, color) in colors.into_iter().enumerate() {
+            canvas.write_pixel(index % width, index / width, color);
+        }
+
+        canvas
+    }
+
+    /// Computes a value for every pixel, tile by tile in parallel, returned row by row.
+    pub fn map_pixels<T, P>(&self, width: usize, height: usize, pixel: P) -> Vec<T>
+    where
+        T: Send,
+        P: Fn(usize, usize) -> T + Sync,
+    {
+        let rendered = self.map_tiles(&self.tiles(width, height), pixel);
+
+        let mut pixels: Vec<Option<T>> = (0..width * height).map(|_| None).collect();
+        for (tile, values) in rendered {
+            for (index, value) in values.into_iter().enumerate() {
+                let (x, y) = (tile.x + index % tile.width, tile.y + index / tile.width);
+                pixels[y * width + x] = Some(value);
+            }
+        }
+
+        pixels
+            .into_iter()
+            .map(|value| value.expect("The tiles cover every pixel"))
+            .collect()
+    }
+
+    /// Computes a value for every pixel of the given tiles in parallel. Returns the values of
+    /// every tile row by row, next to the tile.
+    pub fn map_tiles<T, P>(&self, tiles: &[Tile], pixel: P) -> Vec<(Tile, Vec<T>)>
+    where
+        T: Send,
+        P: Fn(usize, usize) -> T + Sync,
+    {
+        // Threads take the next tile from the queue when they are done with one, so tiles
+        // are started in order
+        let next = AtomicUsize::new(0);
+
+        (0..rayon::current_num_threads())
+            .into_par_iter()
+            .flat_map_iter(|_| {
+                let mut rendered = vec![];
+                while let Some(tile) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    rendered.push((*tile, tile.render(&pixel)));
+                }
+                rendered
+            })
+            .collect()
+    }
+}
+
+/// Cells of the grid in a square spiral, starting at the center and turning clockwise.
+fn spiral(columns: usize, rows: usize) -> Vec<(usize, usize)> {
+    const DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+    let mut cells = Vec::with_capacity(columns * rows);
+    let (mut x, mut y) = ((columns as isize - 1) / 2, (rows as isize - 1) / 2);
+    let visit = |x: isize, y: isize, cells: &mut Vec<(usize, usize)>| {
+        if (0..columns as isize).contains(&x) && (0..rows as isize).contains(&y) {
+            cells.push((x as usize, y as usize));
+        }
+    };
+
+    visit(x, y, &mut cells);
+
+    // The legs of the spiral grow by one every second turn: 1, 1, 2, 2, 3, 3, ...
+    let mut leg = 0;
+    while cells.len() < columns * rows {
+        let (dx, dy) = DIRECTIONS[leg % 4];
+        for _ in 0..leg / 2 + 1 {
+            x += dx;
+            y += dy;
+            visit(x, y, &mut cells);
+        }
+        leg += 1;
+    }
+
+    cells
+}
+
+/// Position of the cell along the Hilbert curve filling a square grid with the given side,
+/// which has to be a power of two.
+fn hilbert_index(side: usize, x: usize, y: usize) -> usize {
+    let (mut x, mut y) = (x, y);
+    let mut index = 0;
+
+    let mut s = side / 2;
+    while s > 0 {
+        let rx = usize::from(x & s > 0);
+        let ry = usize::from(y & s > 0);
+        index += s * s * ((3 * rx) ^ ry);
+
+        // Rotate the quadrant, so the curve inside of it lines up with its neighbors
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+
+        s /= 2;
+    }
+
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzy_eq::*;
+    use crate::F;
+
+    fn covers_every_pixel_once(tiles: &[Tile], width: usize, height: usize) -> bool {
+        let mut covered = vec![0; width * height];
+        for tile in tiles {
+            for y in tile.y..tile.y + tile.height {
+                for x in tile.x..tile.x + tile.width {
+                    covered[y * width + x] += 1;
+                }
+            }
+        }
+
+        covered.iter().all(|&count| count == 1)
+    }
+
+    #[test]
+    fn tiles_cover_the_image_in_every_order() {
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            let tiles = TileRenderer::new(4).with_order(order).tiles(10, 7);
+
+            assert_eq!(tiles.len(), 6);
+            assert!(covers_every_pixel_once(&tiles, 10, 7));
+        }
+    }
+
+    #[test]
+    fn tiles_at_the_edges_are_cut_off() {
+        let tiles = TileRenderer::new(4).tiles(10, 7);
+        let size = |tile: Tile| (tile.x, tile.y, tile.width, tile.height);
+
+        assert_eq!(size(tiles[0]), (0, 0, 4, 4));
+        assert_eq!(size(tiles[2]), (8, 0, 2, 4));
+        assert_eq!(size(tiles[5]), (8, 4, 2, 3));
+    }
+
+    #[test]
+    fn scanline_order_runs_row_by_row() {
+        let tiles = TileRenderer::new(2).tiles(4, 4);
+        let corners: Vec<(usize, usize)> = tiles.iter().map(|tile| (tile.x, tile.y)).collect();
+
+        assert_eq!(corners, vec![(0, 0), (2, 0), (0, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn spiral_order_starts_in_the_center() {
+        let tiles = TileRenderer::new(1)
+            .with_order(TileOrder::Spiral)
+            .tiles(5, 5);
+        let corners: Vec<(usize, usize)> = tiles.iter().map(|tile| (tile.x, tile.y)).collect();
+
+        assert_eq!(corners[..4], [(2, 2), (3, 2), (3, 3), (2, 3)]);
+        assert_eq!(corners.len(), 25);
+    }
+
+    #[test]
+    fn consecutive_tiles_along_the_hilbert_curve_are_neighbors() {
+        let tiles = TileRenderer::new(1)
+            .with_order(TileOrder::Hilbert)
+            .tiles(8, 8);
+
+        assert_eq!((tiles[0].x, tiles[0].y), (0, 0));
+        for pair in tiles.windows(2) {
+            let distance = pair[0].x.abs_diff(pair[1].x) + pair[0].y.abs_diff(pair[1].y);
+            assert_eq!(distance, 1);
+        }
+    }
+
+    #[test]
+    fn rendering_tiles_fills_in_every_pixel() {
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            let canvas = TileRenderer::new(3)
+                .with_order(order)
+                .render(7, 5, |x, y| Color::new(x as F, y as F, 1.0));
+
+            for y in 0..5 {
+                for x in 0..7 {
+                    assert_fuzzy_eq!(canvas.pixel_at(x, y), Color::new(x as F, y as F, 1.0));
+                }
+            }
+        }
+    }
+}
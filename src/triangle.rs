@@ -0,0 +1,308 @@
+use crate::body::*;
+use crate::emitter::Emitter;
+use crate::intersections::*;
+use crate::material::Material;
+use crate::matrix::Matrix;
+use crate::motion::AnimatedTransform;
+use crate::random::Rng;
+use crate::ray::*;
+use crate::tuple::*;
+use crate::{EPSILON, F};
+
+/// A flat triangle, with its vertices given in world space.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Triangle {
+    pub p1: Tuple,
+    pub p2: Tuple,
+    pub p3: Tuple,
+    uv1: (F, F),
+    uv2: (F, F),
+    uv3: (F, F),
+    e1: Tuple,
+    e2: Tuple,
+    normal: Tuple,
+    tangent: Tuple,
+    bitangent: Tuple,
+    material: Material,
+    motion: Option<AnimatedTransform>,
+}
+
+impl Triangle {
+    pub fn new(p1: Tuple, p2: Tuple, p3: Tuple) -> Self {
+        let e1 = p2 - p1;
+        let e2 = p3 - p1;
+        let normal = e2.cross(e1).normalize();
+
+        let mut triangle = Triangle {
+            p1,
+            p2,
+            p3,
+            uv1: (0.0, 0.0),
+            uv2: (1.0, 0.0),
+            uv3: (0.0, 1.0),
+            e1,
+            e2,
+            normal,
+            tangent: Tuple::vector(0.0, 0.0, 0.0),
+            bitangent: Tuple::vector(0.0, 0.0, 0.0),
+            material: Material::default(),
+            motion: None,
+        };
+        triangle.compute_tangents();
+        triangle
+    }
+
+    pub fn with_material(material: Material, p1: Tuple, p2: Tuple, p3: Tuple) -> Self {
+        let mut t = Self::new(p1, p2, p3);
+        t.material = material;
+        t
+    }
+
+    /// Assigns texture coordinates to the three vertices.
+    pub fn with_uvs(mut self, uv1: (F, F), uv2: (F, F), uv3: (F, F)) -> Self {
+        self.uv1 = uv1;
+        self.uv2 = uv2;
+        self.uv3 = uv3;
+        self.compute_tangents();
+        self
+    }
+
+    /// Moves the triangle while the shutter is open, its vertices being transformed by
+    /// `end_transform` at time 1.
+    pub fn with_motion(mut self, end_transform: Matrix<4>) -> Self {
+        self.motion = Some(AnimatedTransform::new(Matrix::identity(), end_transform));
+        self
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.motion.is_some()
+    }
+
+    /// The triangle frozen where it is at the given time.
+    pub fn at_time(&self, time: F) -> Self {
+        match self.motion {
+            Some(motion) => {
+                let transform = motion.at(time);
+
+                Triangle::with_material(
+                    self.material.clone(),
+                    transform * self.p1,
+                    transform * self.p2,
+                    transform * self.p3,
+                )
+                .with_uvs(self.uv1, self.uv2, self.uv3)
+            }
+            None => self.clone(),
+        }
+    }
+
+    fn compute_tangents(&mut self) {
+        let (du1, dv1) = (self.uv2.0 - self.uv1.0, self.uv2.1 - self.uv1.1);
+        let (du2, dv2) = (self.uv3.0 - self.uv1.0, self.uv3.1 - self.uv1.1);
+
+        let determinant = du1 * dv2 - du2 * dv1;
+
+        if determinant.abs() < EPSILON {
+            // Degenerated texture coordinates, fall back to an arbitrary frame on the surface.
+            self.tangent = self.e1.normalize();
+            self.bitangent = self.normal.cross(self.tangent);
+        } else {
+            self.tangent = ((self.e1 * dv2 - self.e2 * dv1) / determinant).normalize();
+            self.bitangent = ((self.e2 * du1 - self.e1 * du2) / determinant).normalize();
+        }
+    }
+
+    /// Weights of the three vertices for a point lying on the triangle.
+    fn barycentric(&self, point: Tuple) -> (F, F, F) {
+        let p1_to_point = point - self.p1;
+
+        let d00 = self.e1.dot(self.e1);
+        let d01 = self.e1.dot(self.e2);
+        let d11 = self.e2.dot(self.e2);
+        let d20 = p1_to_point.dot(self.e1);
+        let d21 = p1_to_point.dot(self.e2);
+
+        let denominator = d00 * d11 - d01 * d01;
+        let w2 = (d11 * d20 - d01 * d21) / denominator;
+        let w3 = (d00 * d21 - d01 * d20) / denominator;
+
+        (1.0 - w2 - w3, w2, w3)
+    }
+}
+
+impl Intersectable for Triangle {
+    fn intersect(&self, ray: Ray) -> Intersections {
+        // The hits belong to the triangle as it was at the time, which then shades them
+        if self.motion.is_some() {
+            return self.at_time(ray.time).intersect(ray);
+        }
+
+        let dir_cross_e2 = ray.direction.cross(self.e2);
+        let determinant = self.e1.dot(dir_cross_e2);
+
+        // Ray is parallel to the triangle
+        if determinant.abs() < EPSILON {
+            return Intersections::new(vec![]);
+        }
+
+        let f = 1.0 / determinant;
+
+        let p1_to_origin = ray.origin - self.p1;
+        let u = f * p1_to_origin.dot(dir_cross_e2);
+        if !(0.0..=1.0).contains(&u) {
+            return Intersections::new(vec![]);
+        }
+
+        let origin_cross_e1 = p1_to_origin.cross(self.e1);
+        let v = f * ray.direction.dot(origin_cross_e1);
+        if v < 0.0 || (u + v) > 1.0 {
+            return Intersections::new(vec![]);
+        }
+
+        let t = f * self.e2.dot(origin_cross_e1);
+
+        Intersections::new(vec![Intersection::new(t, Body::from(self.clone()))])
+    }
+
+    fn normal_vector(&self, _point: Tuple) -> Tuple {
+        self.normal
+    }
+
+    fn material(&self) -> Material {
+        self.material.clone()
+    }
+
+    fn uv(&self, point: Tuple) -> (F, F) {
+        let (w1, w2, w3) = self.barycentric(point);
+
+        (
+            self.uv1.0 * w1 + self.uv2.0 * w2 + self.uv3.0 * w3,
+            self.uv1.1 * w1 + self.uv2.1 * w2 + self.uv3.1 * w3,
+        )
+    }
+
+    fn tangents(&self, _point: Tuple) -> (Tuple, Tuple) {
+        (self.tangent, self.bitangent)
+    }
+}
+
+impl Emitter for Triangle {
+    fn sample_surface(&self, rng: &mut Rng) -> Tuple {
+        // Folding the unit square onto the triangle would cluster points at p1
+        let root = rng.next_f64().sqrt();
+        let v = rng.next_f64();
+
+        self.p1 + self.e1 * (root * (1.0 - v)) + self.e2 * (root * v)
+    }
+
+    fn area_pdf(&self, _point: Tuple) -> F {
+        2.0 / self.e1.cross(self.e2).magnitude()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzy_eq::*;
+
+    fn default_triangle() -> Triangle {
+        Triangle::new(
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(-1.0, 0.0, 0.0),
+            Tuple::point(1.0, 0.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn constructing_a_triangle() {
+        let t = default_triangle();
+
+        assert_fuzzy_eq!(t.e1, Tuple::vector(-1.0, -1.0, 0.0));
+        assert_fuzzy_eq!(t.e2, Tuple::vector(1.0, -1.0, 0.0));
+        assert_fuzzy_eq!(t.normal, Tuple::vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn finding_the_normal_on_a_triangle() {
+        let t = default_triangle();
+
+        assert_fuzzy_eq!(t.normal_vector(Tuple::point(0.0, 0.5, 0.0)), t.normal);
+        assert_fuzzy_eq!(t.normal_vector(Tuple::point(-0.5, 0.75, 0.0)), t.normal);
+        assert_fuzzy_eq!(t.normal_vector(Tuple::point(0.5, 0.25, 0.0)), t.normal);
+    }
+
+    #[test]
+    fn intersecting_a_ray_parallel_to_the_triangle() {
+        let t = default_triangle();
+        let r = Ray::new(Tuple::point(0.0, -1.0, -2.0), Tuple::vector(0.0, 1.0, 0.0));
+
+        assert!(t.intersect(r).is_empty());
+    }
+
+    #[test]
+    fn a_ray_misses_the_p1_p3_edge() {
+        let t = default_triangle();
+        let r = Ray::new(Tuple::point(1.0, 1.0, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert!(t.intersect(r).is_empty());
+    }
+
+    #[test]
+    fn a_ray_misses_the_p1_p2_edge() {
+        let t = default_triangle();
+        let r = Ray::new(Tuple::point(-1.0, 1.0, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert!(t.intersect(r).is_empty());
+    }
+
+    #[test]
+    fn a_ray_misses_the_p2_p3_edge() {
+        let t = default_triangle();
+        let r = Ray::new(Tuple::point(0.0, -1.0, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert!(t.intersect(r).is_empty());
+    }
+
+    #[test]
+    fn a_ray_strikes_a_triangle() {
+        let t = default_triangle();
+        let r = Ray::new(Tuple::point(0.0, 0.5, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let xs = t.intersect(r);
+
+        assert_eq!(xs.len(), 1);
+        assert_fuzzy_eq!(xs[0].t, 2.0);
+    }
+
+    #[test]
+    fn texture_coordinates_are_interpolated_across_the_triangle() {
+        let t = default_triangle().with_uvs((0.5, 1.0), (0.0, 0.0), (1.0, 0.0));
+
+        let (u, v) = t.uv(Tuple::point(0.0, 1.0, 0.0));
+        assert_fuzzy_eq!(u, 0.5);
+        assert_fuzzy_eq!(v, 1.0);
+
+        let (u, v) = t.uv(Tuple::point(0.0, 0.0, 0.0));
+        assert_fuzzy_eq!(u, 0.5);
+        assert_fuzzy_eq!(v, 0.0);
+    }
+
+    #[test]
+    fn tangents_follow_the_texture_coordinates() {
+        let t = default_triangle().with_uvs((0.5, 1.0), (0.0, 0.0), (1.0, 0.0));
+
+        let (tangent, bitangent) = t.tangents(Tuple::point(0.0, 0.5, 0.0));
+
+        assert_fuzzy_eq!(tangent, Tuple::vector(1.0, 0.0, 0.0));
+        assert_fuzzy_eq!(bitangent, Tuple::vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn a_moving_triangle_is_hit_where_it_is_at_the_time_of_the_ray() {
+        let t = default_triangle().with_motion(Matrix::translation(0.0, 0.0, 2.0));
+        let r = Ray::new(Tuple::point(0.0, 0.5, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert_fuzzy_eq!(t.intersect(r).data[0].t, 2.0);
+        assert_fuzzy_eq!(t.intersect(r.with_time(0.25)).data[0].t, 2.5);
+    }
+}
@@ -320,6 +320,7 @@ mod tests {
   }
 
   #[test]
+  #[allow(clippy::unnecessary_cast)]
   fn compute_the_magnitude_of_vector_1_2_3() {
     let v = Tuple::vector(1.0, 2.0, 3.0);
 
@@ -330,6 +331,7 @@ mod tests {
   }
 
   #[test]
+  #[allow(clippy::unnecessary_cast)]
   fn compute_the_magnitude_of_negative_vector_1_2_3() {
     let v = Tuple::vector(-1.0, -2.0, -3.0);
 
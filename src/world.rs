@@ -0,0 +1,846 @@
+use crate::ambient_occlusion::AmbientOcclusion;
+use crate::body::*;
+use crate::canvas::Color;
+use crate::computed_intersection::ComputedIntersection;
+use crate::environment::Background;
+use crate::intersections::*;
+use crate::light::*;
+use crate::material::*;
+use crate::random::Rng;
+use crate::ray::Ray;
+use crate::tuple::Tuple;
+use crate::{EPSILON, F};
+use std::cell::Cell;
+
+/// How often rays may bounce off reflective surfaces, before we stop following them.
+pub const DEFAULT_RECURSION_DEPTH: usize = 5;
+
+thread_local! {
+    /// Bodies tested against rays on this thread so far, for spotting costly parts of a scene
+    static INTERSECTION_TESTS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Number of times a body was tested against a ray on the current thread, by worlds counting
+/// them.
+pub(crate) fn intersection_tests() -> usize {
+    INTERSECTION_TESTS.with(Cell::get)
+}
+
+#[derive(Clone)]
+pub struct World {
+    pub bodies: Vec<Body>,
+    pub lights: Vec<Light>,
+    pub recursion_depth: usize,
+    /// Seen by rays which miss every body
+    pub background: Background,
+    /// Darkens the ambient light of surfaces close to other bodies
+    pub ambient_occlusion: Option<AmbientOcclusion>,
+    /// Whether `intersect` adds to the count of `intersection_tests`, which only debug renders
+    /// want to pay for
+    count_intersection_tests: bool,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        World {
+            bodies: vec![],
+            lights: vec![],
+            recursion_depth: DEFAULT_RECURSION_DEPTH,
+            background: Background::default(),
+            ambient_occlusion: None,
+            count_intersection_tests: false,
+        }
+    }
+}
+
+impl World {
+    pub fn new(bodies: Vec<Body>, lights: Vec<Light>) -> Self {
+        World {
+            bodies,
+            lights,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_background(mut self, background: Background) -> Self {
+        self.background = background;
+        self
+    }
+
+    pub fn with_ambient_occlusion(mut self, ambient_occlusion: AmbientOcclusion) -> Self {
+        self.ambient_occlusion = Some(ambient_occlusion);
+        self
+    }
+
+    /// The same world, counting the bodies tested against rays in `intersection_tests`.
+    pub(crate) fn counting_intersection_tests(&self) -> World {
+        World {
+            count_intersection_tests: true,
+            ..self.clone()
+        }
+    }
+
+    pub fn intersect(&self, ray: Ray) -> Intersections {
+        if self.count_intersection_tests {
+            INTERSECTION_TESTS.with(|tests| tests.set(tests.get() + self.bodies.len()));
+        }
+
+        let intersections = self
+            .bodies
+            .iter()
+            .enumerate()
+            .flat_map(|(index, body)| {
+                body.intersect(ray)
+                    .data
+                    .into_iter()
+                    .map(move |intersection| intersection.with_body_index(index))
+            })
+            .collect::<Vec<Intersection>>();
+
+        Intersections::new(intersections)
+    }
+
+    /// Whether any body blocks the straight line between the two points at the given time.
+    pub fn is_occluded(&self, from: Tuple, to: Tuple, time: F) -> bool {
+        let to_target = to - from;
+        let distance = to_target.magnitude();
+
+        self.is_occluded_along(from, to_target / distance, distance, time)
+    }
+
+    /// Whether any body lies within `distance` of `from`, in the given direction.
+    pub fn is_occluded_along(&self, from: Tuple, direction: Tuple, distance: F, time: F) -> bool {
+        match self.intersect(Ray::new(from, direction).with_time(time)).hit() {
+            // Leave some leeway, for the target might be lying on a body itself
+            Some(hit) => hit.t < distance - EPSILON,
+            None => false,
+        }
+    }
+
+    /// Fraction of the light reaching the given point, 0 when the point is in full shadow.
+    /// Area lights are partially blocked in the penumbra, which makes for soft shadows.
+    pub fn intensity_at<L: LightSource>(&self, light: &L, point: Tuple, time: F) -> F {
+        let samples = light.samples(point);
+        let visible = samples
+            .iter()
+            .filter(|sample| {
+                !self.is_occluded_along(point, sample.direction, sample.distance, time)
+            })
+            .count();
+
+        visible as F / samples.len() as F
+    }
+
+    pub fn color_at(&self, ray: Ray) -> Color {
+        self.color_at_depth(ray, self.recursion_depth)
+    }
+
+    fn color_at_depth(&self, ray: Ray, remaining: usize) -> Color {
+        let intersections = self.intersect(ray);
+
+        match intersections.hit() {
+            Some(hit) => self.shade_hit(&hit.get_computed(ray, &intersections), remaining),
+            None => self.background.radiance(ray.direction),
+        }
+    }
+
+    fn shade_hit(&self, computed: &ComputedIntersection, remaining: usize) -> Color {
+        let material = computed.body.material();
+
+        // Seeded by the point, like the samples of area lights, so renders are repeatable
+        let ambient_visibility = match self.ambient_occlusion {
+            Some(ao) => ao.visibility(self, computed, &mut Rng::for_point(computed.over_point)),
+            None => 1.0,
+        };
+
+        let surface_color = self.lights.iter().fold(Color::black(), |color, light| {
+            color
+                + material.occluded_lighting(
+                    light,
+                    computed.over_point,
+                    computed.eye_vec,
+                    computed.normal_vec,
+                    self.intensity_at(light, computed.over_point, computed.time),
+                    ambient_visibility,
+                )
+        });
+
+        // Glowing surfaces are visible even without any light source shining on them
+        let surface_color = surface_color + material.emissive();
+
+        let reflected = self.reflected_color(computed, remaining);
+        let refracted = self.refracted_color(computed, remaining);
+
+        if material.reflective() > 0.0 && material.transparency() > 0.0 {
+            let reflectance = computed.schlick();
+
+            surface_color + reflected * reflectance + refracted * (1.0 - reflectance)
+        } else {
+            surface_color + reflected + refracted
+        }
+    }
+
+    fn reflected_color(&self, computed: &ComputedIntersection, remaining: usize) -> Color {
+        let reflective = computed.body.material().reflective();
+
+        if remaining == 0 || reflective == 0.0 {
+            return Color::black();
+        }
+
+        let reflected_ray =
+            Ray::new(computed.over_point, computed.reflect_vec).with_time(computed.time);
+
+        self.color_at_depth(reflected_ray, remaining - 1) * reflective
+    }
+
+    fn refracted_color(&self, computed: &ComputedIntersection, remaining: usize) -> Color {
+        let transparency = computed.body.material().transparency();
+
+        if remaining == 0 || transparency == 0.0 {
+            return Color::black();
+        }
+
+        // Snell's law: sin(theta_t) = n1 / n2 * sin(theta_i)
+        let n_ratio = computed.n1 / computed.n2;
+        let cos_i = computed.eye_vec.dot(computed.normal_vec);
+        let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
+
+        if sin2_t > 1.0 {
+            // Total internal reflection, nothing gets through
+            return Color::black();
+        }
+
+        let cos_t = (1.0 - sin2_t).sqrt();
+        let direction =
+            computed.normal_vec * (n_ratio * cos_i - cos_t) - computed.eye_vec * n_ratio;
+        let refracted_ray = Ray::new(computed.under_point, direction).with_time(computed.time);
+
+        self.color_at_depth(refracted_ray, remaining - 1) * transparency
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn default_world() -> World {
+    use crate::matrix::Matrix;
+    use crate::sphere::Sphere;
+    use crate::tuple::Tuple;
+
+    let light = PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
+
+    let outer_material = Material::from(Phong {
+        color: Color::new(0.8, 1.0, 0.6),
+        diffuse: 0.7,
+        specular: 0.2,
+        ..Phong::default()
+    });
+    let outer_sphere = Sphere::with_material(outer_material, None);
+    let inner_sphere = Sphere::new(Some(Matrix::scaling(0.5, 0.5, 0.5)));
+
+    World::new(
+        vec![Body::from(outer_sphere), Body::from(inner_sphere)],
+        vec![Light::from(light)],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzy_eq::*;
+    use crate::matrix::Matrix;
+    use crate::sphere::Sphere;
+    use crate::triangle::Triangle;
+    use std::f64::consts::PI;
+
+    // A triangle large enough to act like an infinite floor at y = -1
+    fn reflective_floor(reflective: F) -> Body {
+        let material = Material::from(Phong {
+            reflective,
+            ..Phong::default()
+        });
+
+        Body::from(Triangle::with_material(
+            material,
+            Tuple::point(-1000.0, -1.0, -1000.0),
+            Tuple::point(0.0, -1.0, 1000.0),
+            Tuple::point(1000.0, -1.0, -1000.0),
+        ))
+    }
+
+    #[test]
+    fn creating_a_world() {
+        let w = World::default();
+
+        assert!(w.bodies.is_empty());
+        assert!(w.lights.is_empty());
+        assert_eq!(w.recursion_depth, DEFAULT_RECURSION_DEPTH);
+    }
+
+    #[test]
+    fn intersect_a_world_with_a_ray() {
+        let w = default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let xs = w.intersect(r);
+
+        assert_eq!(xs.len(), 4);
+        assert_fuzzy_eq!(xs[0].t, 4.0);
+        assert_fuzzy_eq!(xs[1].t, 4.5);
+        assert_fuzzy_eq!(xs[2].t, 5.5);
+        assert_fuzzy_eq!(xs[3].t, 6.0);
+    }
+
+    #[test]
+    fn intersections_with_a_world_know_the_index_of_their_body() {
+        let w = default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let xs = w.intersect(r);
+        let indices: Vec<Option<usize>> = xs.data.iter().map(|i| i.body_index).collect();
+
+        assert_eq!(indices, vec![Some(0), Some(1), Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn only_counting_worlds_count_intersection_tests() {
+        let w = default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let before = intersection_tests();
+        w.intersect(r);
+        assert_eq!(intersection_tests(), before);
+
+        w.counting_intersection_tests().intersect(r);
+        assert_eq!(intersection_tests(), before + 2);
+    }
+
+    #[test]
+    fn nothing_is_occluded_when_nothing_is_collinear_with_the_points() {
+        let w = default_world();
+        let light = Tuple::point(-10.0, 10.0, -10.0);
+
+        assert!(!w.is_occluded(Tuple::point(0.0, 10.0, 0.0), light, 0.0));
+    }
+
+    #[test]
+    fn occlusion_when_a_body_is_between_the_points() {
+        let w = default_world();
+        let light = Tuple::point(-10.0, 10.0, -10.0);
+
+        assert!(w.is_occluded(Tuple::point(10.0, -10.0, 10.0), light, 0.0));
+    }
+
+    #[test]
+    fn no_occlusion_when_a_body_is_behind_the_points() {
+        let w = default_world();
+        let light = Tuple::point(-10.0, 10.0, -10.0);
+
+        assert!(!w.is_occluded(Tuple::point(-20.0, 20.0, -20.0), light, 0.0));
+        assert!(!w.is_occluded(Tuple::point(-2.0, 2.0, -2.0), light, 0.0));
+    }
+
+    #[test]
+    fn a_point_on_a_body_is_not_occluded_by_that_body() {
+        let w = default_world();
+
+        assert!(!w.is_occluded(Tuple::point(0.0, 0.0, -5.0), Tuple::point(0.0, 0.0, -1.0), 0.0));
+    }
+
+    #[test]
+    fn shading_an_intersection() {
+        let w = default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let i = Intersection::new(4.0, w.bodies[0].clone());
+
+        let xs = Intersections::from(vec![i.clone()]);
+        let comps = i.get_computed(r, &xs);
+        let c = w.shade_hit(&comps, w.recursion_depth);
+
+        assert_fuzzy_eq!(c, Color::new(0.38066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    fn shading_an_intersection_from_the_inside() {
+        let mut w = default_world();
+        w.lights = vec![Light::from(PointLight::new(
+            Tuple::point(0.0, 0.25, 0.0),
+            Color::new(1.0, 1.0, 1.0),
+        ))];
+        let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let i = Intersection::new(0.5, w.bodies[1].clone());
+
+        let xs = Intersections::from(vec![i.clone()]);
+        let comps = i.get_computed(r, &xs);
+        let c = w.shade_hit(&comps, w.recursion_depth);
+
+        assert_fuzzy_eq!(c, Color::new(0.90498, 0.90498, 0.90498));
+    }
+
+    #[test]
+    fn the_color_when_a_ray_misses() {
+        let w = default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 1.0, 0.0));
+
+        assert_fuzzy_eq!(w.color_at(r), Color::black());
+    }
+
+    #[test]
+    fn shade_hit_is_given_an_intersection_in_shadow() {
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let s1 = Body::from(Sphere::new(None));
+        let s2 = Body::from(Sphere::new(Some(Matrix::translation(0.0, 0.0, 10.0))));
+        let w = World::new(vec![s1, s2.clone()], vec![Light::from(light)]);
+        let r = Ray::new(Tuple::point(0.0, 0.0, 5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let i = Intersection::new(4.0, s2);
+
+        let computed = i.get_computed(r, &Intersections::from(vec![i.clone()]));
+        let c = w.shade_hit(&computed, DEFAULT_RECURSION_DEPTH);
+
+        assert_fuzzy_eq!(c, Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn intensity_at_a_point_lit_by_a_point_light() {
+        let w = default_world();
+        let light = w.lights[0];
+
+        assert_fuzzy_eq!(w.intensity_at(&light, Tuple::point(0.0, 1.0001, 0.0), 0.0), 1.0);
+        assert_fuzzy_eq!(w.intensity_at(&light, Tuple::point(-1.0001, 0.0, 0.0), 0.0), 1.0);
+        assert_fuzzy_eq!(w.intensity_at(&light, Tuple::point(0.0, 0.0, -1.0001), 0.0), 1.0);
+        assert_fuzzy_eq!(w.intensity_at(&light, Tuple::point(0.0, 0.0, 1.0001), 0.0), 0.0);
+        assert_fuzzy_eq!(w.intensity_at(&light, Tuple::point(1.0001, 0.0, 0.0), 0.0), 0.0);
+        assert_fuzzy_eq!(w.intensity_at(&light, Tuple::point(0.0, -1.0001, 0.0), 0.0), 0.0);
+        assert_fuzzy_eq!(w.intensity_at(&light, Tuple::point(0.0, 0.0, 0.0), 0.0), 0.0);
+    }
+
+    #[test]
+    fn area_lights_are_partially_occluded_in_the_penumbra() {
+        let w = default_world();
+        let light = RectangleLight::new(
+            Tuple::point(-0.5, -0.5, -5.0),
+            Tuple::vector(1.0, 0.0, 0.0),
+            4,
+            Tuple::vector(0.0, 1.0, 0.0),
+            4,
+            Color::white(),
+        );
+
+        // Straight behind the sphere, next to it and at the edge of its shadow
+        assert_fuzzy_eq!(w.intensity_at(&light, Tuple::point(0.0, 0.0, 2.0), 0.0), 0.0);
+        assert_fuzzy_eq!(w.intensity_at(&light, Tuple::point(3.0, 0.0, 2.0), 0.0), 1.0);
+
+        let penumbra = w.intensity_at(&light, Tuple::point(1.25, 0.0, 2.0), 0.0);
+        assert!(penumbra > 0.0 && penumbra < 1.0);
+    }
+
+    #[test]
+    fn sphere_lights_cast_soft_shadows_as_well() {
+        let w = default_world();
+        let light = SphereLight::new(Tuple::point(0.0, 0.0, -5.0), 0.5, 4, Color::white());
+
+        assert_fuzzy_eq!(w.intensity_at(&light, Tuple::point(0.0, 0.0, 2.0), 0.0), 0.0);
+        assert_fuzzy_eq!(w.intensity_at(&light, Tuple::point(3.0, 0.0, 2.0), 0.0), 1.0);
+
+        let penumbra = w.intensity_at(&light, Tuple::point(1.25, 0.0, 2.0), 0.0);
+        assert!(penumbra > 0.0 && penumbra < 1.0);
+    }
+
+    #[test]
+    fn directional_lights_cast_shadows_from_infinitely_far_away() {
+        let w = default_world();
+        let sun = DirectionalLight::new(Tuple::vector(0.0, -1.0, 0.0), Color::white());
+
+        assert_fuzzy_eq!(w.intensity_at(&sun, Tuple::point(0.0, -1000.0, 0.0), 0.0), 0.0);
+        assert_fuzzy_eq!(w.intensity_at(&sun, Tuple::point(2.0, -1000.0, 0.0), 0.0), 1.0);
+    }
+
+    #[test]
+    fn shading_with_a_spot_light_pointing_away() {
+        let mut w = default_world();
+        w.lights = vec![Light::from(SpotLight::new(
+            Tuple::point(-10.0, 10.0, -10.0),
+            Tuple::vector(1.0, 0.0, 0.0),
+            PI / 8.0,
+            PI / 6.0,
+            Color::white(),
+        ))];
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Only ambient light is left
+        assert_fuzzy_eq!(w.color_at(r), Color::new(0.08, 0.1, 0.06));
+    }
+
+    #[test]
+    fn shading_with_a_spot_light_pointing_at_the_hit() {
+        let mut w = default_world();
+        w.lights = vec![Light::from(SpotLight::new(
+            Tuple::point(-10.0, 10.0, -10.0),
+            Tuple::vector(10.0, -10.0, 9.0),
+            PI / 8.0,
+            PI / 6.0,
+            Color::white(),
+        ))];
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Same as lit by a point light at the same position
+        assert_fuzzy_eq!(w.color_at(r), Color::new(0.38066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    fn the_color_when_a_ray_misses_is_the_background() {
+        let w = default_world().with_background(Background::from(Color::new(0.2, 0.3, 0.4)));
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 1.0, 0.0));
+
+        assert_fuzzy_eq!(w.color_at(r), Color::new(0.2, 0.3, 0.4));
+    }
+
+    #[test]
+    fn the_color_when_a_ray_hits() {
+        let w = default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert_fuzzy_eq!(w.color_at(r), Color::new(0.38066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    fn the_reflected_color_for_a_nonreflective_material() {
+        let w = default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+        let inner = Body::from(Sphere::with_material(
+            Material::from(Phong {
+                ambient: 1.0,
+                ..Phong::default()
+            }),
+            Some(Matrix::scaling(0.5, 0.5, 0.5)),
+        ));
+        let i = Intersection::new(1.0, inner);
+
+        let xs = Intersections::from(vec![i.clone()]);
+        let comps = i.get_computed(r, &xs);
+        let c = w.reflected_color(&comps, w.recursion_depth);
+
+        assert_fuzzy_eq!(c, Color::black());
+    }
+
+    #[test]
+    fn the_reflected_color_for_a_reflective_material() {
+        let mut w = default_world();
+        let floor = reflective_floor(0.5);
+        w.bodies.push(floor.clone());
+        let sqrt2_over_2 = (2.0 as F).sqrt() / 2.0;
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -3.0),
+            Tuple::vector(0.0, -sqrt2_over_2, sqrt2_over_2),
+        );
+        let i = Intersection::new((2.0 as F).sqrt(), floor);
+
+        let xs = Intersections::from(vec![i.clone()]);
+        let comps = i.get_computed(r, &xs);
+        let c = w.reflected_color(&comps, w.recursion_depth);
+
+        assert_fuzzy_eq!(c, Color::new(0.19033, 0.23791, 0.14274));
+    }
+
+    #[test]
+    fn shade_hit_with_a_reflective_material() {
+        let mut w = default_world();
+        let floor = reflective_floor(0.5);
+        w.bodies.push(floor.clone());
+        let sqrt2_over_2 = (2.0 as F).sqrt() / 2.0;
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -3.0),
+            Tuple::vector(0.0, -sqrt2_over_2, sqrt2_over_2),
+        );
+        let i = Intersection::new((2.0 as F).sqrt(), floor);
+
+        let xs = Intersections::from(vec![i.clone()]);
+        let comps = i.get_computed(r, &xs);
+        let c = w.shade_hit(&comps, w.recursion_depth);
+
+        assert_fuzzy_eq!(c, Color::new(0.87676, 0.92434, 0.82917));
+    }
+
+    #[test]
+    fn color_at_with_mutually_reflective_surfaces_terminates() {
+        let light = PointLight::new(Tuple::point(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0));
+        let mirror = |y: F| {
+            let material = Material::from(Phong {
+                reflective: 1.0,
+                ..Phong::default()
+            });
+
+            Body::from(Triangle::with_material(
+                material,
+                Tuple::point(-1000.0, y, -1000.0),
+                Tuple::point(0.0, y, 1000.0),
+                Tuple::point(1000.0, y, -1000.0),
+            ))
+        };
+        let w = World::new(vec![mirror(-1.0), mirror(1.0)], vec![Light::from(light)]);
+        let r = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+
+        // Must not overflow the stack
+        w.color_at(r);
+    }
+
+    #[test]
+    fn the_reflected_color_at_the_maximum_recursive_depth() {
+        let mut w = default_world();
+        let floor = reflective_floor(0.5);
+        w.bodies.push(floor.clone());
+        let sqrt2_over_2 = (2.0 as F).sqrt() / 2.0;
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -3.0),
+            Tuple::vector(0.0, -sqrt2_over_2, sqrt2_over_2),
+        );
+        let i = Intersection::new((2.0 as F).sqrt(), floor);
+
+        let xs = Intersections::from(vec![i.clone()]);
+        let comps = i.get_computed(r, &xs);
+        let c = w.reflected_color(&comps, 0);
+
+        assert_fuzzy_eq!(c, Color::black());
+    }
+
+    #[test]
+    fn the_recursion_depth_is_configurable() {
+        let mut w = default_world();
+        w.recursion_depth = 0;
+        let floor = reflective_floor(0.5);
+        w.bodies.push(floor);
+        let sqrt2_over_2 = (2.0 as F).sqrt() / 2.0;
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -3.0),
+            Tuple::vector(0.0, -sqrt2_over_2, sqrt2_over_2),
+        );
+
+        let expected_result = Color::new(0.68643, 0.68643, 0.68643);
+
+        assert_fuzzy_eq!(w.color_at(r), expected_result);
+    }
+
+    fn glass(transparency: F, refractive_index: F) -> Phong {
+        Phong {
+            transparency,
+            refractive_index,
+            ..Phong::default()
+        }
+    }
+
+    #[test]
+    fn the_refracted_color_with_an_opaque_surface() {
+        let w = default_world();
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = Intersections::from(vec![
+            Intersection::new(4.0, w.bodies[0].clone()),
+            Intersection::new(6.0, w.bodies[0].clone()),
+        ]);
+
+        let comps = xs[0].get_computed(r, &xs);
+        let c = w.refracted_color(&comps, 5);
+
+        assert_fuzzy_eq!(c, Color::black());
+    }
+
+    #[test]
+    fn the_refracted_color_at_the_maximum_recursive_depth() {
+        let shape = Body::from(Sphere::with_material(Material::from(glass(1.0, 1.5)), None));
+        let w = World::new(vec![shape.clone()], vec![]);
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let xs = Intersections::from(vec![
+            Intersection::new(4.0, shape.clone()),
+            Intersection::new(6.0, shape),
+        ]);
+
+        let comps = xs[0].get_computed(r, &xs);
+        let c = w.refracted_color(&comps, 0);
+
+        assert_fuzzy_eq!(c, Color::black());
+    }
+
+    #[test]
+    fn the_refracted_color_under_total_internal_reflection() {
+        let shape = Body::from(Sphere::with_material(
+            Material::from(Phong {
+                ambient: 1.0,
+                ..glass(1.0, 1.5)
+            }),
+            None,
+        ));
+        let w = World::new(vec![shape.clone()], vec![]);
+        let sqrt2_over_2 = (2.0 as F).sqrt() / 2.0;
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, sqrt2_over_2),
+            Tuple::vector(0.0, 1.0, 0.0),
+        );
+        let xs = Intersections::from(vec![
+            Intersection::new(-sqrt2_over_2, shape.clone()),
+            Intersection::new(sqrt2_over_2, shape),
+        ]);
+
+        // Inside of the sphere, so look at the second intersection
+        let comps = xs[1].get_computed(r, &xs);
+        let c = w.refracted_color(&comps, 5);
+
+        assert_fuzzy_eq!(c, Color::black());
+    }
+
+    #[test]
+    fn the_refracted_color_with_a_refracted_ray() {
+        let light = PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        // Only lit by its ambient term, so the color does not depend on the viewing angle
+        let backdrop = Body::from(Sphere::with_material(
+            Material::from(Phong {
+                ambient: 1.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                ..Phong::with_color(Color::new(0.2, 0.4, 0.6))
+            }),
+            Some(Matrix::scaling(10.0, 10.0, 10.0)),
+        ));
+        // No surface lighting either, so only light passing through the glass is seen
+        let shape = Body::from(Sphere::with_material(
+            Material::from(Phong {
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                ..glass(0.5, 1.5)
+            }),
+            None,
+        ));
+        let w = World::new(vec![backdrop, shape.clone()], vec![Light::from(light)]);
+        let r = Ray::new(Tuple::point(0.0, 0.1, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let xs = w.intersect(r);
+        let hit = xs.hit().unwrap();
+        let comps = hit.get_computed(r, &xs);
+        let c = w.refracted_color(&comps, 5);
+
+        // Passing both the entry and the exit, each letting half of the light through
+        assert_eq!(hit.body, shape);
+        assert_fuzzy_eq!(c, Color::new(0.05, 0.1, 0.15));
+    }
+
+    #[test]
+    fn shade_hit_with_a_transparent_material() {
+        let mut w = default_world();
+        let floor = Body::from(Triangle::with_material(
+            Material::from(glass(0.5, 1.5)),
+            Tuple::point(-1000.0, -1.0, -1000.0),
+            Tuple::point(0.0, -1.0, 1000.0),
+            Tuple::point(1000.0, -1.0, -1000.0),
+        ));
+        let ball = Body::from(Sphere::with_material(
+            Material::from(Phong {
+                ambient: 0.5,
+                diffuse: 0.0,
+                specular: 0.0,
+                ..Phong::with_color(Color::new(1.0, 0.0, 0.0))
+            }),
+            Some(Matrix::translation(0.0, -3.5, -0.5)),
+        ));
+        w.bodies.push(floor.clone());
+        w.bodies.push(ball);
+        let sqrt2_over_2 = (2.0 as F).sqrt() / 2.0;
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -3.0),
+            Tuple::vector(0.0, -sqrt2_over_2, sqrt2_over_2),
+        );
+        let xs = Intersections::from(vec![Intersection::new((2.0 as F).sqrt(), floor)]);
+
+        let comps = xs[0].get_computed(r, &xs);
+        let c = w.shade_hit(&comps, 5);
+
+        assert_fuzzy_eq!(c, Color::new(0.93642, 0.68642, 0.68642));
+    }
+
+    #[test]
+    fn shade_hit_with_a_reflective_transparent_material() {
+        let mut w = default_world();
+        let floor = Body::from(Triangle::with_material(
+            Material::from(Phong {
+                reflective: 0.5,
+                ..glass(0.5, 1.5)
+            }),
+            Tuple::point(-1000.0, -1.0, -1000.0),
+            Tuple::point(0.0, -1.0, 1000.0),
+            Tuple::point(1000.0, -1.0, -1000.0),
+        ));
+        let ball = Body::from(Sphere::with_material(
+            Material::from(Phong {
+                ambient: 0.5,
+                diffuse: 0.0,
+                specular: 0.0,
+                ..Phong::with_color(Color::new(1.0, 0.0, 0.0))
+            }),
+            Some(Matrix::translation(0.0, -3.5, -0.5)),
+        ));
+        w.bodies.push(floor.clone());
+        w.bodies.push(ball);
+        let sqrt2_over_2 = (2.0 as F).sqrt() / 2.0;
+        let r = Ray::new(
+            Tuple::point(0.0, 0.0, -3.0),
+            Tuple::vector(0.0, -sqrt2_over_2, sqrt2_over_2),
+        );
+        let xs = Intersections::from(vec![Intersection::new((2.0 as F).sqrt(), floor)]);
+
+        let comps = xs[0].get_computed(r, &xs);
+        let c = w.shade_hit(&comps, 5);
+
+        assert_fuzzy_eq!(c, Color::new(0.93391, 0.69643, 0.69243));
+    }
+
+    #[test]
+    fn an_emissive_body_is_visible_without_lights() {
+        let glowing = Body::from(Sphere::with_material(
+            Material::from(Phong {
+                ambient: 0.0,
+                emissive: Color::new(0.8, 0.2, 0.1),
+                ..Phong::default()
+            }),
+            None,
+        ));
+        let w = World::new(vec![glowing], vec![]);
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert_fuzzy_eq!(w.color_at(r), Color::new(0.8, 0.2, 0.1));
+    }
+
+    #[test]
+    fn emission_is_added_to_the_lit_surface_color() {
+        let mut w = default_world();
+        w.bodies[0] = Body::from(Sphere::with_material(
+            Material::from(Phong {
+                color: Color::new(0.8, 1.0, 0.6),
+                diffuse: 0.7,
+                specular: 0.2,
+                emissive: Color::new(0.5, 0.0, 0.0),
+                ..Phong::default()
+            }),
+            None,
+        ));
+        let r = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        assert_fuzzy_eq!(w.color_at(r), Color::new(0.88066, 0.47583, 0.2855));
+    }
+
+    #[test]
+    fn ambient_occlusion_darkens_the_ambient_light_of_crowded_surfaces() {
+        // The inner sphere, seen from within the outer one, is hemmed in on every side
+        let r = Ray::new(Tuple::point(0.0, 0.0, 0.75), Tuple::vector(0.0, 0.0, -1.0));
+        let mut w = default_world();
+        w.lights = vec![Light::from(PointLight::new(
+            Tuple::point(0.0, 0.0, 0.75),
+            Color::white(),
+        ))];
+        let lit = w.color_at(r);
+
+        let w = World {
+            ambient_occlusion: Some(AmbientOcclusion::new(16, 1.0)),
+            ..w
+        };
+        let occluded = w.color_at(r);
+
+        // Only the ambient term of 0.1 goes away
+        assert_fuzzy_eq!(lit - occluded, Color::new(0.1, 0.1, 0.1));
+    }
+}